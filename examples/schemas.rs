@@ -0,0 +1,18 @@
+// Writes the wire-type JSON Schemas into `schemas/`. With `--check`, only
+// compares them against the committed files and fails on drift.
+use corelib::common::schema::{stale_schemas, write_schemas};
+use std::path::Path;
+use std::process;
+
+fn main() {
+  let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("schemas");
+  if std::env::args().any(|arg| arg == "--check") {
+    let stale = stale_schemas(&dir).expect("failed to read schemas");
+    if !stale.is_empty() {
+      eprintln!("schemas out of date: {}", stale.join(", "));
+      process::exit(1);
+    }
+  } else {
+    write_schemas(&dir).expect("failed to write schemas");
+  }
+}
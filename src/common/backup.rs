@@ -0,0 +1,250 @@
+// Splits and rebuilds keystores through their raw fields.
+#![allow(deprecated)]
+
+#[cfg(feature = "mnemonic")]
+use crate::common::mnemonic::{self, Language};
+#[cfg(feature = "schemars")]
+use crate::common::schema;
+use crate::common::types::{Keystore, PublicKeystore};
+use crate::common::utils::shamir_combine;
+use crate::errors::{CoreErrors, ValidationKind};
+#[cfg(feature = "mnemonic")]
+use curv::arithmetic::traits::Converter;
+use curv::cryptographic_primitives::secret_sharing::feldman_vss::VerifiableSS;
+use curv::elliptic::curves::traits::ECPoint;
+#[cfg(feature = "mnemonic")]
+use curv::elliptic::curves::traits::ECScalar;
+use curv::{BigInt, FE, GE};
+use multi_party_ecdsa::protocols::multi_party_ecdsa::gg_2018::party_i::{Keys, SharedKeys};
+use paillier::{KeyGeneration, Paillier};
+use serde::{Deserialize, Serialize};
+
+/// One piece of a personal `k`-of-`m` Shamir split of a party's share and
+/// its keygen secret `u_i`. Fewer than `k` pieces are independent of both;
+/// the metadata only says which keystore and party the piece belongs to.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct BackupPiece {
+  pub party_index: usize,
+  #[cfg_attr(feature = "schemars", schemars(schema_with = "schema::bigint"))]
+  pub fingerprint: BigInt,
+  /// Pieces needed to restore (`k`).
+  pub threshold: u8,
+  /// 0-based piece index; the piece is the split polynomial at `index + 1`.
+  pub index: u8,
+  #[cfg_attr(feature = "schemars", schemars(schema_with = "schema::scalar"))]
+  pub share: FE,
+  /// Piece of `u_i`, split with the same polynomial degree and index.
+  #[cfg_attr(feature = "schemars", schemars(schema_with = "schema::scalar"))]
+  pub key_share: FE,
+}
+
+fn invalid(msg: String) -> CoreErrors {
+  CoreErrors::invalid_data(ValidationKind::Malformed(msg))
+}
+
+impl Keystore {
+  /// Splits this party's share `x_i` and keygen secret `u_i` into `m`
+  /// pieces, any `k` of which restore both. Independent of the group
+  /// threshold.
+  pub fn backup_share(&self, k: u8, m: u8) -> Result<Vec<BackupPiece>, CoreErrors> {
+    if k < 2 || k > m {
+      return Err(invalid(format!(
+        "Backup needs 2 <= k <= m, got {}-of-{}",
+        k, m
+      )));
+    }
+
+    let fingerprint = self.public().fingerprint();
+    let (_, shares) = VerifiableSS::share(k as usize - 1, m as usize, &self.shared_keys.x_i);
+    let (_, key_shares) = VerifiableSS::share(k as usize - 1, m as usize, &self.party_key.u_i);
+    Ok(
+      shares
+        .into_iter()
+        .zip(key_shares)
+        .enumerate()
+        .map(|(index, (share, key_share))| BackupPiece {
+          party_index: self.party_index,
+          fingerprint: fingerprint.clone(),
+          threshold: k,
+          index: index as u8,
+          share,
+          key_share,
+        })
+        .collect(),
+    )
+  }
+
+  /// Rebuilds a keystore from backup pieces and the public part of the
+  /// original one. The share is checked against the group's VSS commitments.
+  ///
+  /// The Paillier key pair is not part of the backup, so the restored
+  /// keystore carries a fresh one, also in its own `paillier_key_vec` entry;
+  /// run `rotate_paillier` with it before signing so the other parties learn
+  /// the new encryption key.
+  pub fn restore_share(
+    pieces: &[BackupPiece],
+    template: &PublicKeystore,
+  ) -> Result<Keystore, CoreErrors> {
+    let first = pieces
+      .first()
+      .ok_or_else(|| invalid("No backup pieces provided".to_string()))?;
+    let fingerprint = template.fingerprint();
+    if let Some(piece) = pieces.iter().find(|p| {
+      p.fingerprint != fingerprint
+        || p.party_index != template.party_index
+        || p.threshold != first.threshold
+    }) {
+      return Err(invalid(format!(
+        "Backup piece {} belongs to another keystore or split",
+        piece.index
+      )));
+    }
+    if pieces.len() < first.threshold as usize {
+      return Err(invalid(format!(
+        "Backup needs {} pieces, got {}",
+        first.threshold,
+        pieces.len()
+      )));
+    }
+
+    let shares = pieces
+      .iter()
+      .map(|p| (p.index as usize, p.share))
+      .collect::<Vec<(usize, FE)>>();
+    let x_i = shamir_combine(&shares)?;
+    let key_shares = pieces
+      .iter()
+      .map(|p| (p.index as usize, p.key_share))
+      .collect::<Vec<(usize, FE)>>();
+    let u_i = shamir_combine(&key_shares)?;
+
+    let g: GE = ECPoint::generator();
+    let commitments = Keys::get_commitments_to_xi(&template.vss_scheme_vec);
+    if commitments.get(template.party_index) != Some(&(g * x_i)) {
+      return Err(invalid(
+        "Restored share does not match the VSS commitments".to_string(),
+      ));
+    }
+    let y_i = g * u_i;
+    if template.vss_scheme_vec[template.party_index].commitments[0] != y_i {
+      return Err(invalid(
+        "Restored u_i does not match the own VSS scheme".to_string(),
+      ));
+    }
+
+    let (ek, dk) = Paillier::keypair().keys();
+    let mut paillier_key_vec = template.paillier_key_vec.clone();
+    paillier_key_vec[template.party_index] = ek.clone();
+    Ok(Keystore {
+      params: template.params.clone(),
+      party_key: Keys {
+        u_i,
+        y_i,
+        dk,
+        ek,
+        party_index: template.party_index + 1,
+      },
+      party_shares: vec![],
+      shared_keys: SharedKeys {
+        y: template.y_sum,
+        x_i,
+      },
+      party_index: template.party_index,
+      vss_scheme_vec: template.vss_scheme_vec.clone(),
+      paillier_key_vec,
+      y_sum: template.y_sum,
+    })
+  }
+}
+
+#[cfg(feature = "mnemonic")]
+const METADATA_PREFIX: &str = "corelib-backup:v2";
+
+#[cfg(feature = "mnemonic")]
+impl BackupPiece {
+  /// English mnemonic of the piece, 24 words for the share followed by 24
+  /// for the `u_i` piece, plus the metadata needed to restore it as
+  /// `corelib-backup:v2:<party>:<k>:<piece>:<fingerprint hex>`. Both have to
+  /// be written down.
+  pub fn to_mnemonic(&self) -> (String, String) {
+    let words = |scalar: &FE| {
+      let bytes = BigInt::to_vec(&scalar.to_big_int());
+      let mut secret = [0u8; 32];
+      secret[32 - bytes.len()..].copy_from_slice(&bytes);
+      mnemonic::encode(&secret, Language::English)
+    };
+    let metadata = format!(
+      "{}:{}:{}:{}:{}",
+      METADATA_PREFIX,
+      self.party_index,
+      self.threshold,
+      self.index,
+      self.fingerprint.to_str_radix(16)
+    );
+    (
+      format!("{} {}", words(&self.share), words(&self.key_share)),
+      metadata,
+    )
+  }
+
+  pub fn from_mnemonic(words: &str, metadata: &str) -> Result<Self, CoreErrors> {
+    let bad_metadata = || invalid(format!("Invalid backup metadata ({})", metadata));
+    let fields = metadata
+      .strip_prefix(METADATA_PREFIX)
+      .and_then(|rest| rest.strip_prefix(':'))
+      .ok_or_else(bad_metadata)?
+      .split(':')
+      .collect::<Vec<&str>>();
+    let (party_index, threshold, index, fingerprint) = match fields[..] {
+      [party_index, threshold, index, fingerprint] => (
+        party_index.parse().map_err(|_| bad_metadata())?,
+        threshold.parse().map_err(|_| bad_metadata())?,
+        index.parse().map_err(|_| bad_metadata())?,
+        BigInt::from_str_radix(fingerprint, 16).map_err(|_| bad_metadata())?,
+      ),
+      _ => return Err(bad_metadata()),
+    };
+
+    let words = words.split_whitespace().collect::<Vec<&str>>();
+    if words.len() != 48 {
+      return Err(invalid(format!(
+        "Expected 48 backup words, got {}",
+        words.len()
+      )));
+    }
+    let scalar = |words: &[&str]| -> Result<FE, CoreErrors> {
+      let secret = mnemonic::decode(&words.join(" "), Language::English)?;
+      Ok(ECScalar::from(&BigInt::from(&secret[..])))
+    };
+    Ok(BackupPiece {
+      party_index,
+      fingerprint,
+      threshold,
+      index,
+      share: scalar(&words[..24])?,
+      key_share: scalar(&words[24..])?,
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::testing::fixtures::generate_test_keystore_2_of_3;
+
+  #[test]
+  fn restored_keystore_is_consistent() {
+    let keystore = generate_test_keystore_2_of_3().remove(1);
+    let pieces = keystore.backup_share(2, 3).unwrap();
+    let restored = Keystore::restore_share(&pieces[1..], &keystore.public()).unwrap();
+
+    assert_eq!(restored.party_key.u_i, keystore.party_key.u_i);
+    assert_eq!(restored.party_key.y_i, keystore.party_key.y_i);
+    assert_eq!(
+      restored.paillier_key_vec[restored.party_index()].n,
+      restored.party_key.ek.n
+    );
+    Keystore::from_parts(restored.into_parts()).unwrap();
+  }
+}
@@ -0,0 +1,159 @@
+use crate::common::events::{EventSink, SinkClosed};
+use crate::common::messages::{Errors, Message, MessageData, RoundMetrics, RoundResult};
+use crate::errors::ErrorClass;
+use std::sync::mpsc::{Receiver, RecvTimeoutError, Sender};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Outgoing end of the channel a protocol run reports into; what the
+/// `*_with_channels` entry points accept besides std `Sender`s.
+pub trait ProtocolSender {
+  fn send(&self, msg: Message) -> Result<(), SinkClosed>;
+}
+
+/// Incoming end of the channel a protocol run reads other parties' messages
+/// from.
+pub trait ProtocolReceiver {
+  /// Waits up to `timeout` for the next message; a zero timeout only takes
+  /// one that is already there.
+  fn recv_timeout(&mut self, timeout: Duration) -> Result<Message, RecvTimeoutError>;
+}
+
+impl ProtocolSender for Sender<Message> {
+  fn send(&self, msg: Message) -> Result<(), SinkClosed> {
+    Sender::send(self, msg).map_err(|_| SinkClosed)
+  }
+}
+
+impl ProtocolReceiver for Receiver<Message> {
+  fn recv_timeout(&mut self, timeout: Duration) -> Result<Message, RecvTimeoutError> {
+    Receiver::recv_timeout(self, timeout)
+  }
+}
+
+#[cfg(feature = "crossbeam")]
+impl ProtocolSender for crossbeam_channel::Sender<Message> {
+  fn send(&self, msg: Message) -> Result<(), SinkClosed> {
+    crossbeam_channel::Sender::send(self, msg).map_err(|_| SinkClosed)
+  }
+}
+
+#[cfg(feature = "crossbeam")]
+impl ProtocolReceiver for crossbeam_channel::Receiver<Message> {
+  fn recv_timeout(&mut self, timeout: Duration) -> Result<Message, RecvTimeoutError> {
+    crossbeam_channel::Receiver::recv_timeout(self, timeout).map_err(|e| match e {
+      crossbeam_channel::RecvTimeoutError::Timeout => RecvTimeoutError::Timeout,
+      crossbeam_channel::RecvTimeoutError::Disconnected => RecvTimeoutError::Disconnected,
+    })
+  }
+}
+
+#[cfg(feature = "tokio")]
+impl ProtocolSender for tokio::sync::mpsc::UnboundedSender<Message> {
+  fn send(&self, msg: Message) -> Result<(), SinkClosed> {
+    tokio::sync::mpsc::UnboundedSender::send(self, msg).map_err(|_| SinkClosed)
+  }
+}
+
+/// Blocks while the channel is full. Runs have to be on their own thread,
+/// as `blocking_send` panics inside an async context.
+#[cfg(feature = "tokio")]
+impl ProtocolSender for tokio::sync::mpsc::Sender<Message> {
+  fn send(&self, msg: Message) -> Result<(), SinkClosed> {
+    self.blocking_send(msg).map_err(|_| SinkClosed)
+  }
+}
+
+// tokio receivers have no blocking receive with a deadline, so they are
+// polled.
+#[cfg(feature = "tokio")]
+fn poll_tokio<F>(timeout: Duration, mut try_recv: F) -> Result<Message, RecvTimeoutError>
+where
+  F: FnMut() -> Result<Message, tokio::sync::mpsc::error::TryRecvError>,
+{
+  use tokio::sync::mpsc::error::TryRecvError;
+  const TOKIO_POLL: Duration = Duration::from_millis(5);
+
+  let deadline = std::time::Instant::now() + timeout;
+  loop {
+    match try_recv() {
+      Ok(msg) => return Ok(msg),
+      Err(TryRecvError::Disconnected) => return Err(RecvTimeoutError::Disconnected),
+      Err(TryRecvError::Empty) => {
+        let now = std::time::Instant::now();
+        if now >= deadline {
+          return Err(RecvTimeoutError::Timeout);
+        }
+        std::thread::sleep(TOKIO_POLL.min(deadline - now));
+      }
+    }
+  }
+}
+
+#[cfg(feature = "tokio")]
+impl ProtocolReceiver for tokio::sync::mpsc::UnboundedReceiver<Message> {
+  fn recv_timeout(&mut self, timeout: Duration) -> Result<Message, RecvTimeoutError> {
+    poll_tokio(timeout, || self.try_recv())
+  }
+}
+
+#[cfg(feature = "tokio")]
+impl ProtocolReceiver for tokio::sync::mpsc::Receiver<Message> {
+  fn recv_timeout(&mut self, timeout: Duration) -> Result<Message, RecvTimeoutError> {
+    poll_tokio(timeout, || self.try_recv())
+  }
+}
+
+/// Reports a run as `Message`s on any `ProtocolSender`, the way
+/// the std `Sender` sink does.
+pub(crate) struct ChannelSink<S>(pub(crate) S);
+
+impl<S: ProtocolSender> EventSink for ChannelSink<S> {
+  fn on_send(&self, sender: u8, target: u8, data: MessageData) -> Result<(), SinkClosed> {
+    self.on_send_shared(sender, target, Arc::new(data))
+  }
+
+  fn on_send_shared(
+    &self,
+    sender: u8,
+    target: u8,
+    data: Arc<MessageData>,
+  ) -> Result<(), SinkClosed> {
+    self.0.send(Message::Send {
+      sender,
+      target,
+      session: None,
+      data,
+      sent_at_ms: None,
+      attempt: None,
+    })
+  }
+
+  fn on_log(&self, line: String) -> Result<(), SinkClosed> {
+    self.0.send(Message::Log(line))
+  }
+
+  fn on_progress(&self, metrics: RoundMetrics) -> Result<(), SinkClosed> {
+    self.0.send(Message::Metrics(metrics))
+  }
+
+  fn on_error(&self, error: Errors) -> Result<(), SinkClosed> {
+    self.0.send(Message::Error(error))
+  }
+
+  fn on_error_class(&self, class: ErrorClass) -> Result<(), SinkClosed> {
+    self.0.send(Message::Classification(class))
+  }
+
+  fn on_sends_issued(&self) -> Result<(), SinkClosed> {
+    self.0.send(Message::SendsIssued)
+  }
+
+  fn on_complete(&self, result: RoundResult) -> Result<(), SinkClosed> {
+    self.0.send(Message::Complete(result))
+  }
+
+  fn on_quit(&self) -> Result<(), SinkClosed> {
+    self.0.send(Message::Quit)
+  }
+}
@@ -0,0 +1,251 @@
+// Encrypted keystore backup blob. The layout is frozen; every field is
+// big-endian and read back exactly as written:
+//
+//   magic        8   b"CLBACKUP"
+//   version      2   FORMAT_VERSION
+//   kdf          1   1 = Argon2id v1.3
+//   memory_kib   4
+//   iterations   4
+//   parallelism  4
+//   salt         16
+//   cipher       1   1 = AES-256-GCM
+//   nonce        12
+//   length       4   ciphertext length
+//   ciphertext   length
+//   tag          16
+//   checksum     32  SHA-256 of everything above
+//
+// Everything from magic to nonce is the AEAD's associated data, so the KDF
+// parameters cannot be lowered without the passphrase. The checksum tells
+// a damaged blob apart from a wrong passphrase. The plaintext is a 2-byte
+// keystore encoding version followed by the keystore as JSON.
+
+use crate::common::types::Keystore;
+use crate::common::utils::{constant_time_bytes_eq, random_bytes};
+use crate::errors::{CoreErrors, ValidationKind};
+use argon2::{Algorithm, Argon2, Params, Version};
+use crypto::{
+  aead::{AeadDecryptor, AeadEncryptor},
+  aes::KeySize::KeySize256,
+  aes_gcm::AesGcm,
+  digest::Digest,
+  sha2::Sha256,
+};
+
+const MAGIC: &[u8; 8] = b"CLBACKUP";
+const FORMAT_VERSION: u16 = 1;
+const KEYSTORE_VERSION: u16 = 1;
+const KDF_ARGON2ID: u8 = 1;
+const CIPHER_AES256_GCM: u8 = 1;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+const CHECKSUM_LEN: usize = 32;
+const HEADER_LEN: usize = 8 + 2 + 1 + 4 + 4 + 4 + SALT_LEN + 1 + NONCE_LEN;
+
+/// Argon2id cost parameters. Stored in the blob, so opening always uses the
+/// parameters the blob was created with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KdfParams {
+  pub memory_kib: u32,
+  pub iterations: u32,
+  pub parallelism: u32,
+}
+
+impl KdfParams {
+  /// Weakest parameters accepted when creating or opening a blob.
+  pub const MINIMUM: KdfParams = KdfParams {
+    memory_kib: 19 * 1024,
+    iterations: 2,
+    parallelism: 1,
+  };
+
+  /// Strongest parameters accepted when opening a blob, so a crafted blob
+  /// cannot make `open` allocate without bound.
+  pub const MAXIMUM: KdfParams = KdfParams {
+    memory_kib: 4 * 1024 * 1024,
+    iterations: 64,
+    parallelism: 16,
+  };
+
+  fn check(&self) -> Result<(), CoreErrors> {
+    let (min, max) = (KdfParams::MINIMUM, KdfParams::MAXIMUM);
+    let within = |v: u32, lo: u32, hi: u32| lo <= v && v <= hi;
+    if within(self.memory_kib, min.memory_kib, max.memory_kib)
+      && within(self.iterations, min.iterations, max.iterations)
+      && within(self.parallelism, min.parallelism, max.parallelism)
+    {
+      Ok(())
+    } else {
+      Err(CoreErrors::invalid_data(ValidationKind::Malformed(
+        format!("KDF parameters out of range ({:?})", self),
+      )))
+    }
+  }
+
+  fn derive_key(&self, passphrase: &[u8], salt: &[u8]) -> Result<[u8; 32], CoreErrors> {
+    let failed = |e: argon2::Error| {
+      CoreErrors::invalid_data(ValidationKind::Malformed(format!(
+        "Key derivation failed ({})",
+        e
+      )))
+    };
+    let params =
+      Params::new(self.memory_kib, self.iterations, self.parallelism, Some(32)).map_err(failed)?;
+    let mut key = [0u8; 32];
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+      .hash_password_into(passphrase, salt, &mut key)
+      .map_err(failed)?;
+    Ok(key)
+  }
+}
+
+impl Default for KdfParams {
+  fn default() -> Self {
+    KdfParams {
+      memory_kib: 64 * 1024,
+      iterations: 3,
+      parallelism: 1,
+    }
+  }
+}
+
+pub struct CloudBackup;
+
+fn checksum(data: &[u8]) -> [u8; CHECKSUM_LEN] {
+  let mut hasher = Sha256::new();
+  hasher.input(data);
+  let mut out = [0u8; CHECKSUM_LEN];
+  hasher.result(&mut out);
+  out
+}
+
+fn corrupted(reason: &str) -> CoreErrors {
+  CoreErrors::invalid_data(ValidationKind::Corrupted(reason.to_string()))
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+  fn take(&mut self, len: usize) -> Result<&'a [u8], CoreErrors> {
+    if self.0.len() < len {
+      return Err(corrupted("truncated"));
+    }
+    let (head, tail) = self.0.split_at(len);
+    self.0 = tail;
+    Ok(head)
+  }
+
+  fn u8(&mut self) -> Result<u8, CoreErrors> {
+    Ok(self.take(1)?[0])
+  }
+
+  fn u16(&mut self) -> Result<u16, CoreErrors> {
+    let b = self.take(2)?;
+    Ok(u16::from_be_bytes([b[0], b[1]]))
+  }
+
+  fn u32(&mut self) -> Result<u32, CoreErrors> {
+    let b = self.take(4)?;
+    Ok(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+  }
+}
+
+impl CloudBackup {
+  /// Encrypts `keystore` under a key derived from `passphrase`.
+  pub fn create(
+    keystore: &Keystore,
+    passphrase: &[u8],
+    params: &KdfParams,
+  ) -> Result<Vec<u8>, CoreErrors> {
+    params.check()?;
+    let salt = random_bytes(SALT_LEN);
+    let nonce = random_bytes(NONCE_LEN);
+    let key = params.derive_key(passphrase, &salt)?;
+
+    let mut blob = Vec::with_capacity(HEADER_LEN);
+    blob.extend_from_slice(MAGIC);
+    blob.extend_from_slice(&FORMAT_VERSION.to_be_bytes());
+    blob.push(KDF_ARGON2ID);
+    blob.extend_from_slice(&params.memory_kib.to_be_bytes());
+    blob.extend_from_slice(&params.iterations.to_be_bytes());
+    blob.extend_from_slice(&params.parallelism.to_be_bytes());
+    blob.extend_from_slice(&salt);
+    blob.push(CIPHER_AES256_GCM);
+    blob.extend_from_slice(&nonce);
+
+    let mut plaintext = KEYSTORE_VERSION.to_be_bytes().to_vec();
+    plaintext.extend(
+      serde_json::to_vec(keystore)
+        .map_err(|e| CoreErrors::invalid_data(ValidationKind::Malformed(e.to_string())))?,
+    );
+    let mut ciphertext = vec![0u8; plaintext.len()];
+    let mut tag = [0u8; TAG_LEN];
+    AesGcm::new(KeySize256, &key, &nonce, &blob).encrypt(&plaintext, &mut ciphertext, &mut tag);
+
+    blob.extend_from_slice(&(ciphertext.len() as u32).to_be_bytes());
+    blob.extend_from_slice(&ciphertext);
+    blob.extend_from_slice(&tag);
+    let sum = checksum(&blob);
+    blob.extend_from_slice(&sum);
+    Ok(blob)
+  }
+
+  /// Decrypts a blob made by `create`. Fails with `Corrupted` when the blob
+  /// is damaged, `UnsupportedVersion` when it comes from a newer format and
+  /// `WrongPassphrase` when it is intact but does not decrypt.
+  pub fn open(blob: &[u8], passphrase: &[u8]) -> Result<Keystore, CoreErrors> {
+    if blob.len() < HEADER_LEN + 4 + TAG_LEN + CHECKSUM_LEN || blob[..8] != MAGIC[..] {
+      return Err(corrupted("not a cloud backup"));
+    }
+    let (body, sum) = blob.split_at(blob.len() - CHECKSUM_LEN);
+    if !constant_time_bytes_eq(&checksum(body), sum) {
+      return Err(corrupted("checksum mismatch"));
+    }
+
+    let mut reader = Reader(&body[8..]);
+    let version = reader.u16()?;
+    if version != FORMAT_VERSION {
+      return Err(CoreErrors::invalid_data(
+        ValidationKind::UnsupportedVersion(version),
+      ));
+    }
+    if reader.u8()? != KDF_ARGON2ID {
+      return Err(corrupted("unknown KDF"));
+    }
+    let params = KdfParams {
+      memory_kib: reader.u32()?,
+      iterations: reader.u32()?,
+      parallelism: reader.u32()?,
+    };
+    params.check()?;
+    let salt = reader.take(SALT_LEN)?;
+    if reader.u8()? != CIPHER_AES256_GCM {
+      return Err(corrupted("unknown cipher"));
+    }
+    let nonce = reader.take(NONCE_LEN)?;
+    let length = reader.u32()? as usize;
+    let ciphertext = reader.take(length)?;
+    let tag = reader.take(TAG_LEN)?;
+    if !reader.0.is_empty() {
+      return Err(corrupted("trailing data"));
+    }
+
+    let key = params.derive_key(passphrase, salt)?;
+    let mut plaintext = vec![0u8; ciphertext.len()];
+    let header = &body[..HEADER_LEN];
+    if !AesGcm::new(KeySize256, &key, nonce, header).decrypt(ciphertext, &mut plaintext, tag) {
+      return Err(CoreErrors::invalid_data(ValidationKind::WrongPassphrase));
+    }
+
+    let mut reader = Reader(&plaintext);
+    let keystore_version = reader.u16()?;
+    if keystore_version != KEYSTORE_VERSION {
+      return Err(CoreErrors::invalid_data(
+        ValidationKind::UnsupportedVersion(keystore_version),
+      ));
+    }
+    serde_json::from_slice(reader.0)
+      .map_err(|e| CoreErrors::invalid_data(ValidationKind::Malformed(e.to_string())))
+  }
+}
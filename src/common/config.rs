@@ -0,0 +1,87 @@
+use serde::{Deserialize, Serialize};
+
+/// Wire revision of the protocols. Every party of a run has to use the same
+/// one; mixed runs fail in the round where the revisions differ.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub enum ProtocolVersion {
+  /// Keygen round 3 encrypts both directions of a pair under the same AES
+  /// key and nonce. Only for runs with parties that can't be upgraded.
+  V1,
+  /// Keygen round 3 keys are derived per direction.
+  V2,
+  /// Keygen round 3 shares are encrypted as exactly 32 bytes, so the
+  /// ciphertext length doesn't depend on the share.
+  V3,
+  /// Sign round 2 responses name their recipient and the round-1 message
+  /// they answer, so misrouted ones are told apart from bad proofs.
+  V4,
+  /// Keygen round 3 is sent as versioned `SealedData` under a random nonce
+  /// instead of an `AEAD` under the fixed one.
+  V5,
+}
+
+impl ProtocolVersion {
+  pub const LATEST: ProtocolVersion = ProtocolVersion::V5;
+}
+
+impl Default for ProtocolVersion {
+  fn default() -> Self {
+    ProtocolVersion::LATEST
+  }
+}
+
+/// Numbering of parties in the `sender`/`target` fields of messages.
+/// Internally, and in keystores and `signers_vec`, parties are always
+/// numbered from 0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CompatibilityMode {
+  /// Parties `0..n`, as everywhere else in this crate.
+  ZeroBased,
+  /// Parties `1..=n`, as in the GG2018 paper and its reference
+  /// implementation.
+  OneBased,
+}
+
+impl CompatibilityMode {
+  pub(crate) fn id_offset(self) -> u8 {
+    match self {
+      CompatibilityMode::ZeroBased => 0,
+      CompatibilityMode::OneBased => 1,
+    }
+  }
+}
+
+impl Default for CompatibilityMode {
+  fn default() -> Self {
+    CompatibilityMode::ZeroBased
+  }
+}
+
+/// Options for a protocol run. `Default` reproduces the behaviour of the
+/// entry points that don't take a config.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ProtocolConfig {
+  /// Normalize signatures so that `s` is in the lower half of the curve order.
+  pub enforce_low_s: bool,
+  /// Leave send timestamps off outgoing `Message`s, so peers and relays learn
+  /// nothing about this party's clock or timing.
+  #[serde(default)]
+  pub private_timing: bool,
+  #[serde(default)]
+  pub protocol_version: ProtocolVersion,
+  /// Retry attempt of the session. Messages stamped with another attempt are
+  /// dropped; preferred over `max_message_age_ms`, which depends on clocks.
+  #[serde(default)]
+  pub attempt: Option<u32>,
+  /// Messages sent longer ago than this are dropped. Only applies to
+  /// messages without a comparable attempt id.
+  #[serde(default)]
+  pub max_message_age_ms: Option<u64>,
+  #[serde(default)]
+  pub compatibility: CompatibilityMode,
+  /// Writes a snapshot of every collected round; see `common::dump`.
+  #[cfg(feature = "debug-dump")]
+  #[serde(skip)]
+  pub debug_dump: Option<crate::common::dump::DebugDump>,
+}
@@ -0,0 +1,219 @@
+use crate::common::hash::{hash, HashAlg};
+use crate::common::messages::{now_ms, MessageData, RoundMetrics};
+use crate::common::types::{Digest32, RoundId};
+use crate::errors::{CoreErrors, ValidationKind};
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Receives every snapshot instead of it being written to a directory.
+#[derive(Clone)]
+pub struct DumpCallback(pub Arc<dyn Fn(&RoundSnapshot) + Send + Sync>);
+
+impl fmt::Debug for DumpCallback {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str("DumpCallback")
+  }
+}
+
+impl PartialEq for DumpCallback {
+  fn eq(&self, other: &Self) -> bool {
+    Arc::ptr_eq(&self.0, &other.0)
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DumpTarget {
+  /// One `<run>_p<party>_<round>.json` file per round, e.g.
+  /// `1700000000000_p0_KeyGenRound1.json`, where `<run>` is the start of the
+  /// run in ms since the epoch.
+  Directory(PathBuf),
+  Callback(DumpCallback),
+}
+
+/// Per-round snapshots of a run for debugging failed ceremonies offline.
+/// Secret-bearing payloads are replaced by their hash unless
+/// `unsafe_full_dump` is set; never set it outside a test setup.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DebugDump {
+  pub target: DumpTarget,
+  pub unsafe_full_dump: bool,
+}
+
+impl DebugDump {
+  pub fn to_directory<P: Into<PathBuf>>(dir: P) -> Self {
+    DebugDump {
+      target: DumpTarget::Directory(dir.into()),
+      unsafe_full_dump: false,
+    }
+  }
+
+  pub fn to_callback<F>(callback: F) -> Self
+  where
+    F: Fn(&RoundSnapshot) + Send + Sync + 'static,
+  {
+    DebugDump {
+      target: DumpTarget::Callback(DumpCallback(Arc::new(callback))),
+      unsafe_full_dump: false,
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DumpedPayload {
+  Full(serde_json::Value),
+  /// SHA-256 of the JSON encoding of the data.
+  Redacted {
+    sha256: Digest32,
+  },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DumpedMessage {
+  /// Sender of a collected message, target of a sent one.
+  pub party: u8,
+  /// `Display` of the data, e.g. `KeyGenRound3`.
+  pub kind: String,
+  pub payload: DumpedPayload,
+}
+
+/// One party's view of one collected round.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RoundSnapshot {
+  pub party_id: u8,
+  pub round: RoundId,
+  pub metrics: RoundMetrics,
+  pub written_at_ms: u64,
+  /// Ordered by sender; only the first message from a party is kept, as
+  /// in the round itself.
+  pub collected: Vec<DumpedMessage>,
+  pub sent: Vec<DumpedMessage>,
+}
+
+// Keygen round 3 carries the encrypted secret shares.
+fn is_secret_bearing(data: &MessageData) -> bool {
+  matches!(
+    data,
+    MessageData::KeyGenRound3(_) | MessageData::KeyGenRound3Sealed(_)
+  )
+}
+
+fn dump_message(party: u8, data: &MessageData, full: bool) -> DumpedMessage {
+  let value = serde_json::to_value(data).unwrap_or(serde_json::Value::Null);
+  let payload = if full || !is_secret_bearing(data) {
+    DumpedPayload::Full(value)
+  } else {
+    DumpedPayload::Redacted {
+      sha256: hash(HashAlg::Sha256, value.to_string().as_bytes()),
+    }
+  };
+  DumpedMessage {
+    party,
+    kind: data.to_string(),
+    payload,
+  }
+}
+
+/// Messages of the current rounds of a `RoundRunner`, until the round is
+/// collected and written out.
+pub(crate) struct RoundDumper {
+  config: DebugDump,
+  party_id: u8,
+  run_started_ms: u64,
+  sent: RefCell<Vec<(u8, MessageData)>>,
+  collected: RefCell<Vec<(u8, MessageData)>>,
+}
+
+impl RoundDumper {
+  pub(crate) fn new(config: DebugDump, party_id: u8) -> Self {
+    RoundDumper {
+      config,
+      party_id,
+      run_started_ms: now_ms(),
+      sent: RefCell::new(vec![]),
+      collected: RefCell::new(vec![]),
+    }
+  }
+
+  pub(crate) fn sent(&self, target: u8, data: &MessageData) {
+    self.sent.borrow_mut().push((target, data.clone()));
+  }
+
+  pub(crate) fn collected(&self, sender: u8, data: &MessageData) {
+    self.collected.borrow_mut().push((sender, data.clone()));
+  }
+
+  /// Takes the messages of `metrics.round` and hands their snapshot to the
+  /// target. Messages of other rounds stay for their own snapshot.
+  pub(crate) fn round_done(&self, metrics: RoundMetrics) -> Result<(), String> {
+    let round = metrics.round;
+    let full = self.config.unsafe_full_dump;
+    let take = |messages: &RefCell<Vec<(u8, MessageData)>>| -> Vec<DumpedMessage> {
+      let mut messages = messages.borrow_mut();
+      let (ours, rest): (Vec<_>, Vec<_>) = messages
+        .drain(..)
+        .partition(|(_, data)| data.round() == Some(round));
+      *messages = rest;
+      ours
+        .iter()
+        .map(|(party, data)| dump_message(*party, data, full))
+        .collect()
+    };
+
+    let mut collected = take(&self.collected);
+    collected.sort_by_key(|m| m.party);
+    collected.dedup_by_key(|m| m.party);
+    let snapshot = RoundSnapshot {
+      party_id: self.party_id,
+      round,
+      metrics,
+      written_at_ms: now_ms(),
+      collected,
+      sent: take(&self.sent),
+    };
+
+    match &self.config.target {
+      DumpTarget::Callback(callback) => {
+        (callback.0)(&snapshot);
+        Ok(())
+      }
+      DumpTarget::Directory(dir) => {
+        let name = format!("{}_p{}_{}.json", self.run_started_ms, self.party_id, round);
+        let json = serde_json::to_vec_pretty(&snapshot).map_err(|e| e.to_string())?;
+        fs::write(dir.join(name), json).map_err(|e| e.to_string())
+      }
+    }
+  }
+}
+
+/// Reads back every snapshot in `dir`, in the order they were written.
+pub fn load_snapshots<P: AsRef<Path>>(dir: P) -> Result<Vec<RoundSnapshot>, CoreErrors> {
+  let unreadable =
+    |e: std::io::Error| CoreErrors::invalid_data(ValidationKind::Unreadable(e.to_string()));
+  let mut paths = fs::read_dir(dir)
+    .map_err(unreadable)?
+    .map(|entry| entry.map(|e| e.path()))
+    .collect::<Result<Vec<PathBuf>, _>>()
+    .map_err(unreadable)?;
+  paths.retain(|p| p.extension().map_or(false, |ext| ext == "json"));
+
+  let mut snapshots = paths
+    .iter()
+    .map(|path| {
+      let bytes = fs::read(path).map_err(unreadable)?;
+      serde_json::from_slice(&bytes).map_err(|e| {
+        CoreErrors::invalid_data(ValidationKind::Malformed(format!(
+          "Snapshot {}: {}",
+          path.display(),
+          e
+        )))
+      })
+    })
+    .collect::<Result<Vec<RoundSnapshot>, CoreErrors>>()?;
+  snapshots.sort_by_key(|s| (s.written_at_ms, s.party_id));
+  Ok(snapshots)
+}
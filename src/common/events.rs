@@ -0,0 +1,253 @@
+use crate::common::messages::{unshare, Errors, Message, MessageData, RoundMetrics, RoundResult};
+use crate::errors::ErrorClass;
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// The receiving side of a sink is gone; the run cannot report anything more.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SinkClosed;
+
+/// Receives what a protocol run emits, as callbacks rather than
+/// `Message`s on a channel. Callbacks are invoked on the protocol
+/// thread, in the order the events happen. A callback returning `SinkClosed`
+/// or panicking fails the run with a transport error; the panic does not
+/// unwind through the protocol.
+pub trait EventSink {
+  /// Message for party `target`, to be delivered by the transport.
+  fn on_send(&self, sender: u8, target: u8, data: MessageData) -> Result<(), SinkClosed>;
+
+  /// Like `on_send`, with a payload that other targets of the same
+  /// broadcast share. Sinks that can pass it on as it is override this to
+  /// save a copy per target.
+  fn on_send_shared(
+    &self,
+    sender: u8,
+    target: u8,
+    data: Arc<MessageData>,
+  ) -> Result<(), SinkClosed> {
+    self.on_send(sender, target, unshare(data))
+  }
+
+  fn on_log(&self, _line: String) -> Result<(), SinkClosed> {
+    Ok(())
+  }
+
+  /// Metrics of a round that was just collected.
+  fn on_progress(&self, _metrics: RoundMetrics) -> Result<(), SinkClosed> {
+    Ok(())
+  }
+
+  fn on_error(&self, error: Errors) -> Result<(), SinkClosed>;
+
+  /// Retry guidance for the failure just reported through `on_error`.
+  fn on_error_class(&self, _class: ErrorClass) -> Result<(), SinkClosed> {
+    Ok(())
+  }
+
+  /// Every protocol message of the run went through `on_send`; only
+  /// `flush` and `on_quit` follow. Transports without `flush` can order
+  /// their teardown on this.
+  fn on_sends_issued(&self) -> Result<(), SinkClosed> {
+    Ok(())
+  }
+
+  /// Waits up to `timeout` until everything passed to `on_send` has left
+  /// this party, and says whether it did. Called before `on_quit`, which
+  /// embedders may take as the signal to tear down the transport. Sinks
+  /// that can't tell return `true`.
+  fn flush(&self, _timeout: Duration) -> bool {
+    true
+  }
+
+  fn on_complete(&self, result: RoundResult) -> Result<(), SinkClosed>;
+
+  /// Nothing follows.
+  fn on_quit(&self) -> Result<(), SinkClosed> {
+    Ok(())
+  }
+}
+
+/// The outgoing channel of the channel entry points.
+impl EventSink for Sender<Message> {
+  fn on_send(&self, sender: u8, target: u8, data: MessageData) -> Result<(), SinkClosed> {
+    self.on_send_shared(sender, target, Arc::new(data))
+  }
+
+  fn on_send_shared(
+    &self,
+    sender: u8,
+    target: u8,
+    data: Arc<MessageData>,
+  ) -> Result<(), SinkClosed> {
+    self
+      .send(Message::Send {
+        sender,
+        target,
+        session: None,
+        data,
+        sent_at_ms: None,
+        attempt: None,
+      })
+      .map_err(|_| SinkClosed)
+  }
+
+  fn on_log(&self, line: String) -> Result<(), SinkClosed> {
+    self.send(Message::Log(line)).map_err(|_| SinkClosed)
+  }
+
+  fn on_progress(&self, metrics: RoundMetrics) -> Result<(), SinkClosed> {
+    self.send(Message::Metrics(metrics)).map_err(|_| SinkClosed)
+  }
+
+  fn on_error(&self, error: Errors) -> Result<(), SinkClosed> {
+    self.send(Message::Error(error)).map_err(|_| SinkClosed)
+  }
+
+  fn on_error_class(&self, class: ErrorClass) -> Result<(), SinkClosed> {
+    self
+      .send(Message::Classification(class))
+      .map_err(|_| SinkClosed)
+  }
+
+  fn on_sends_issued(&self) -> Result<(), SinkClosed> {
+    self.send(Message::SendsIssued).map_err(|_| SinkClosed)
+  }
+
+  fn on_complete(&self, result: RoundResult) -> Result<(), SinkClosed> {
+    self.send(Message::Complete(result)).map_err(|_| SinkClosed)
+  }
+
+  fn on_quit(&self) -> Result<(), SinkClosed> {
+    self.send(Message::Quit).map_err(|_| SinkClosed)
+  }
+}
+
+impl<T: EventSink + ?Sized> EventSink for &T {
+  fn on_send(&self, sender: u8, target: u8, data: MessageData) -> Result<(), SinkClosed> {
+    (**self).on_send(sender, target, data)
+  }
+
+  fn on_send_shared(
+    &self,
+    sender: u8,
+    target: u8,
+    data: Arc<MessageData>,
+  ) -> Result<(), SinkClosed> {
+    (**self).on_send_shared(sender, target, data)
+  }
+
+  fn on_log(&self, line: String) -> Result<(), SinkClosed> {
+    (**self).on_log(line)
+  }
+
+  fn on_progress(&self, metrics: RoundMetrics) -> Result<(), SinkClosed> {
+    (**self).on_progress(metrics)
+  }
+
+  fn on_error(&self, error: Errors) -> Result<(), SinkClosed> {
+    (**self).on_error(error)
+  }
+
+  fn on_error_class(&self, class: ErrorClass) -> Result<(), SinkClosed> {
+    (**self).on_error_class(class)
+  }
+
+  fn on_sends_issued(&self) -> Result<(), SinkClosed> {
+    (**self).on_sends_issued()
+  }
+
+  fn flush(&self, timeout: Duration) -> bool {
+    (**self).flush(timeout)
+  }
+
+  fn on_complete(&self, result: RoundResult) -> Result<(), SinkClosed> {
+    (**self).on_complete(result)
+  }
+
+  fn on_quit(&self) -> Result<(), SinkClosed> {
+    (**self).on_quit()
+  }
+}
+
+/// Both sinks get every event, the first one first; e.g. callbacks in
+/// addition to the channel.
+impl<A: EventSink, B: EventSink> EventSink for (A, B) {
+  fn on_send(&self, sender: u8, target: u8, data: MessageData) -> Result<(), SinkClosed> {
+    self.0.on_send(sender, target, data.clone())?;
+    self.1.on_send(sender, target, data)
+  }
+
+  fn on_send_shared(
+    &self,
+    sender: u8,
+    target: u8,
+    data: Arc<MessageData>,
+  ) -> Result<(), SinkClosed> {
+    self.0.on_send_shared(sender, target, data.clone())?;
+    self.1.on_send_shared(sender, target, data)
+  }
+
+  fn on_log(&self, line: String) -> Result<(), SinkClosed> {
+    self.0.on_log(line.clone())?;
+    self.1.on_log(line)
+  }
+
+  fn on_progress(&self, metrics: RoundMetrics) -> Result<(), SinkClosed> {
+    self.0.on_progress(metrics)?;
+    self.1.on_progress(metrics)
+  }
+
+  fn on_error(&self, error: Errors) -> Result<(), SinkClosed> {
+    self.0.on_error(error)?;
+    self.1.on_error(error)
+  }
+
+  fn on_error_class(&self, class: ErrorClass) -> Result<(), SinkClosed> {
+    self.0.on_error_class(class.clone())?;
+    self.1.on_error_class(class)
+  }
+
+  fn on_sends_issued(&self) -> Result<(), SinkClosed> {
+    self.0.on_sends_issued()?;
+    self.1.on_sends_issued()
+  }
+
+  /// Flushes both, one after the other.
+  fn flush(&self, timeout: Duration) -> bool {
+    let first = self.0.flush(timeout);
+    self.1.flush(timeout) && first
+  }
+
+  fn on_complete(&self, result: RoundResult) -> Result<(), SinkClosed> {
+    self.0.on_complete(result.clone())?;
+    self.1.on_complete(result)
+  }
+
+  fn on_quit(&self) -> Result<(), SinkClosed> {
+    self.0.on_quit()?;
+    self.1.on_quit()
+  }
+}
+
+/// Hands `msg` to the callback for its kind.
+pub(crate) fn emit(sink: &dyn EventSink, msg: Message) -> Result<(), SinkClosed> {
+  match msg {
+    Message::Send {
+      sender,
+      target,
+      data,
+      ..
+    } => sink.on_send_shared(sender, target, data),
+    // Runs address every party themselves; `RoundRunner::broadcast` sends
+    // one message per target.
+    Message::Broadcast { .. } => unreachable!("runs never emit a broadcast"),
+    Message::Complete(result) => sink.on_complete(result),
+    Message::Quit => sink.on_quit(),
+    Message::Error(error) => sink.on_error(error),
+    Message::Log(line) => sink.on_log(line),
+    Message::Metrics(metrics) => sink.on_progress(metrics),
+    Message::Classification(class) => sink.on_error_class(class),
+    Message::SendsIssued => sink.on_sends_issued(),
+  }
+}
@@ -0,0 +1,101 @@
+use crate::common::types::Digest32;
+use crate::errors::{CoreErrors, ValidationKind};
+use crypto::{digest::Digest, sha2::Sha256, sha3::Sha3};
+use serde::{Deserialize, Serialize};
+use std::io::{ErrorKind, Read};
+
+const READ_CHUNK: usize = 64 * 1024;
+
+/// Hash functions with a 32-byte output that a digest can be built with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub enum HashAlg {
+  Sha256,
+  Sha3_256,
+  Keccak256,
+}
+
+/// Incremental digest of input that is fed in pieces, e.g. a file too large
+/// to hold in memory. Chunking does not affect the result: it always equals
+/// `hash(alg, whole_input)`.
+pub struct DigestBuilder {
+  alg: HashAlg,
+  hasher: Box<dyn Digest + Send>,
+}
+
+impl DigestBuilder {
+  pub fn new(alg: HashAlg) -> Self {
+    let hasher: Box<dyn Digest + Send> = match alg {
+      HashAlg::Sha256 => Box::new(Sha256::new()),
+      HashAlg::Sha3_256 => Box::new(Sha3::sha3_256()),
+      HashAlg::Keccak256 => Box::new(Sha3::keccak256()),
+    };
+    DigestBuilder { alg, hasher }
+  }
+
+  pub fn alg(&self) -> HashAlg {
+    self.alg
+  }
+
+  pub fn update(&mut self, data: &[u8]) -> &mut Self {
+    self.hasher.input(data);
+    self
+  }
+
+  /// Feeds everything `reader` yields, a fixed-size chunk at a time.
+  pub fn update_reader<R: Read>(&mut self, mut reader: R) -> Result<&mut Self, CoreErrors> {
+    let mut buf = vec![0u8; READ_CHUNK];
+    loop {
+      match reader.read(&mut buf) {
+        Ok(0) => return Ok(self),
+        Ok(n) => {
+          self.hasher.input(&buf[..n]);
+        }
+        Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+        Err(e) => {
+          return Err(CoreErrors::invalid_data(ValidationKind::Unreadable(
+            e.to_string(),
+          )))
+        }
+      }
+    }
+  }
+
+  pub fn finalize(mut self) -> Digest32 {
+    let mut out = [0u8; 32];
+    self.hasher.result(&mut out);
+    Digest32::from_hash_output(out)
+  }
+}
+
+/// One-shot digest of `data`.
+pub fn hash(alg: HashAlg, data: &[u8]) -> Digest32 {
+  let mut builder = DigestBuilder::new(alg);
+  builder.update(data);
+  builder.finalize()
+}
+
+/// Binds `digest` to a signing context, so a signature requested for one
+/// purpose cannot be passed off as one for another:
+///
+/// `SHA-256(len(context) as u32 big-endian || context || digest)`
+///
+/// with the digest as its 32 bytes. The length prefix stops bytes from
+/// shifting between context and digest.
+pub fn domain_separated(context: &[u8], digest: &Digest32) -> Digest32 {
+  let mut hasher = Sha256::new();
+  hasher.input(&(context.len() as u32).to_be_bytes());
+  hasher.input(context);
+  hasher.input(digest.as_bytes());
+  let mut out = [0u8; 32];
+  hasher.result(&mut out);
+  Digest32::from_hash_output(out)
+}
+
+/// The digest a signing run signs: `digest` itself when there is no context.
+pub fn effective_digest(context: Option<&[u8]>, digest: &Digest32) -> Digest32 {
+  match context {
+    Some(context) => domain_separated(context, digest),
+    None => *digest,
+  }
+}
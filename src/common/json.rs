@@ -0,0 +1,218 @@
+// Adjacently tagged JSON (`{"type": "SignRound4", "payload": ...}`) for the
+// wire enums, for clients that find serde's default externally tagged form
+// awkward to switch on. Each function accepts only this representation.
+
+use crate::common::hash::HashAlg;
+use crate::common::messages::*;
+use crate::common::types::{
+  CeremonyReport, HealthReport, Keystore, PublicKey, SchnorrSignature, SealedData, Signature, AEAD,
+};
+use crate::errors::{CoreErrors, ErrorClass, ValidationKind};
+use curv::cryptographic_primitives::{
+  proofs::sigma_dlog::DLogProof, secret_sharing::feldman_vss::VerifiableSS,
+};
+use curv::FE;
+use multi_party_ecdsa::protocols::multi_party_ecdsa::gg_2018::party_i::{
+  KeyGenBroadcastMessage1, KeyGenDecommitMessage1, Phase5Com1, Phase5Com2, Phase5DDecom2,
+  SignDecommitPhase1,
+};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::sync::Arc;
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "MessageData", tag = "type", content = "payload")]
+enum MessageDataDef {
+  None,
+  KeyGenRound1(KeyGenBroadcastMessage1),
+  KeyGenRound2(KeyGenDecommitMessage1),
+  KeyGenRound3(AEAD),
+  KeyGenRound4(VerifiableSS),
+  KeyGenRound5(DLogProof),
+
+  SignRound1(SignRound1Data),
+  SignRound2(SignRound2Data),
+  SignRound3(FE),
+  SignRound4(SignDecommitPhase1),
+  SignRound5(Phase5Com1),
+  SignRound6(SignRound6Data),
+  SignRound7(Phase5Com2),
+  SignRound8(Phase5DDecom2),
+  SignRound9(FE),
+
+  PaillierRotationRound1(PaillierRotationData),
+  PaillierRotationRound2(PaillierRotationConfirm),
+  PaillierRotationRound3(PaillierRotationCommit),
+
+  HealthCheckChallenge(HealthCheckChallenge),
+  HealthCheckResponse(HealthCheckResponse),
+
+  SchnorrSignRound1(SchnorrNonces),
+  SchnorrSignRound2(SchnorrPartial),
+
+  Abort(String),
+  KeyGenRound3Sealed(SealedData),
+  KeyGenRound1Attested(AttestedBroadcast),
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "RoundResult", tag = "type", content = "payload")]
+enum RoundResultDef {
+  KeyGen {
+    private_key: Keystore,
+    public_key: PublicKey,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    report: Option<CeremonyReport>,
+  },
+  Sign {
+    signature: Signature,
+    #[serde(default)]
+    context: Option<Vec<u8>>,
+    #[serde(default)]
+    hash_alg: Option<HashAlg>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    policy_annotations: Vec<String>,
+  },
+  PaillierRotation {
+    private_key: Keystore,
+  },
+  HealthCheck {
+    report: HealthReport,
+  },
+  SchnorrSign {
+    sig: SchnorrSignature,
+  },
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "OutgoingMessages", tag = "type", content = "payload")]
+enum OutgoingMessagesDef {
+  Send {
+    sender: u8,
+    target: u8,
+    #[serde(with = "MessageDataDef")]
+    data: MessageData,
+  },
+  Complete(#[serde(with = "RoundResultDef")] RoundResult),
+  Quit,
+  Error(Errors),
+  Log(String),
+  Metrics(RoundMetrics),
+  Classification(ErrorClass),
+  SendsIssued,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "IncomingMessages", tag = "type", content = "payload")]
+enum IncomingMessagesDef {
+  Send {
+    sender: u8,
+    target: u8,
+    #[serde(with = "MessageDataDef")]
+    data: MessageData,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    sent_at_ms: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    attempt: Option<u32>,
+  },
+}
+
+// `Message` payloads are shared between the targets of a broadcast.
+mod shared_data {
+  use super::MessageDataDef;
+  use crate::common::messages::MessageData;
+  use serde::{Deserializer, Serializer};
+  use std::sync::Arc;
+
+  pub(super) fn serialize<S: Serializer>(
+    data: &Arc<MessageData>,
+    serializer: S,
+  ) -> Result<S::Ok, S::Error> {
+    MessageDataDef::serialize(data, serializer)
+  }
+
+  pub(super) fn deserialize<'de, D: Deserializer<'de>>(
+    deserializer: D,
+  ) -> Result<Arc<MessageData>, D::Error> {
+    MessageDataDef::deserialize(deserializer).map(Arc::new)
+  }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "Message", tag = "type", content = "payload")]
+enum MessageDef {
+  Send {
+    sender: u8,
+    target: u8,
+    #[serde(default)]
+    session: Option<Vec<u8>>,
+    #[serde(with = "shared_data")]
+    data: Arc<MessageData>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    sent_at_ms: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    attempt: Option<u32>,
+  },
+  Broadcast {
+    sender: u8,
+    #[serde(default)]
+    session: Option<Vec<u8>>,
+    #[serde(with = "shared_data")]
+    data: Arc<MessageData>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    sent_at_ms: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    attempt: Option<u32>,
+  },
+  Complete(#[serde(with = "RoundResultDef")] RoundResult),
+  Quit,
+  Error(Errors),
+  Log(String),
+  Metrics(RoundMetrics),
+  Classification(ErrorClass),
+  SendsIssued,
+}
+
+/// Types with an adjacently tagged JSON form.
+pub trait TaggedJson: Sized {
+  fn serialize_tagged<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error>;
+  fn deserialize_tagged<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error>;
+}
+
+macro_rules! tagged_json {
+  ($ty:ty, $def:ident) => {
+    impl TaggedJson for $ty {
+      fn serialize_tagged<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        $def::serialize(self, serializer)
+      }
+      fn deserialize_tagged<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        $def::deserialize(deserializer)
+      }
+    }
+  };
+}
+
+tagged_json!(MessageData, MessageDataDef);
+tagged_json!(RoundResult, RoundResultDef);
+tagged_json!(OutgoingMessages, OutgoingMessagesDef);
+tagged_json!(IncomingMessages, IncomingMessagesDef);
+tagged_json!(Message, MessageDef);
+
+fn malformed(e: serde_json::Error) -> CoreErrors {
+  CoreErrors::invalid_data(ValidationKind::Malformed(e.to_string()))
+}
+
+pub fn to_string<T: TaggedJson>(value: &T) -> Result<String, CoreErrors> {
+  let mut buf = Vec::new();
+  value
+    .serialize_tagged(&mut serde_json::Serializer::new(&mut buf))
+    .map_err(malformed)?;
+  // serde_json only ever writes UTF-8.
+  Ok(String::from_utf8(buf).expect("serde_json produced invalid UTF-8"))
+}
+
+pub fn from_str<T: TaggedJson>(json: &str) -> Result<T, CoreErrors> {
+  let mut deserializer = serde_json::Deserializer::from_str(json);
+  let value = T::deserialize_tagged(&mut deserializer).map_err(malformed)?;
+  deserializer.end().map_err(malformed)?;
+  Ok(value)
+}
@@ -0,0 +1,134 @@
+use crate::common::messages::Message;
+use std::collections::BTreeMap;
+
+/// One-way latency seen from one peer, in ms. Sender and receiver clocks
+/// are not synchronized, so the figures include their skew and can be
+/// negative; compare peers against each other rather than trusting the
+/// absolute values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PeerLatency {
+  pub peer: u8,
+  pub samples: usize,
+  pub min: i64,
+  pub median: i64,
+  pub max: i64,
+}
+
+/// Collects `receive time - send time` per sender for timestamped messages.
+#[derive(Debug, Clone, Default)]
+pub struct LatencyTracker {
+  samples: BTreeMap<u8, Vec<i64>>,
+}
+
+impl LatencyTracker {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Records `msg` received at `received_at_ms` (see `messages::now_ms`).
+  /// Messages without a send timestamp are ignored.
+  pub fn record(&mut self, msg: &Message, received_at_ms: u64) {
+    let sender = match msg {
+      Message::Send { sender, .. } | Message::Broadcast { sender, .. } => *sender,
+      _ => return,
+    };
+    if let Some(sent_at_ms) = msg.sent_at_ms() {
+      self
+        .samples
+        .entry(sender)
+        .or_insert_with(Vec::new)
+        .push(received_at_ms as i64 - sent_at_ms as i64);
+    }
+  }
+
+  /// Per-peer summary, in peer order. Peers without samples are left out.
+  pub fn summary(&self) -> Vec<PeerLatency> {
+    self
+      .samples
+      .iter()
+      .filter(|(_, s)| !s.is_empty())
+      .map(|(peer, samples)| {
+        let mut sorted = samples.clone();
+        sorted.sort_unstable();
+        PeerLatency {
+          peer: *peer,
+          samples: sorted.len(),
+          min: sorted[0],
+          median: sorted[sorted.len() / 2],
+          max: sorted[sorted.len() - 1],
+        }
+      })
+      .collect()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::common::config::ProtocolConfig;
+  use crate::common::messages::{now_ms, MessageData};
+  use std::sync::mpsc::channel;
+  use std::thread;
+  use std::time::Duration;
+
+  // Routes `count` messages from each peer `p + 1` to party 0, holding every
+  // one of them back for `delays_ms[p]` after it was stamped, and tracks
+  // them as they arrive.
+  fn route_with_delays(delays_ms: &[u64], count: usize, config: &ProtocolConfig) -> LatencyTracker {
+    let (routed_sender, routed_receiver) = channel::<Message>();
+    let handles: Vec<_> = delays_ms
+      .iter()
+      .enumerate()
+      .map(|(p, delay_ms)| {
+        let routed_sender = routed_sender.clone();
+        let config = config.clone();
+        let delay = Duration::from_millis(*delay_ms);
+        thread::spawn(move || {
+          for _ in 0..count {
+            let data = MessageData::Abort("ping".to_string());
+            let msg = Message::make_send(p as u8 + 1, 0, &data).stamped(&config);
+            thread::sleep(delay);
+            routed_sender.send(msg).unwrap();
+          }
+        })
+      })
+      .collect();
+    drop(routed_sender);
+
+    let mut tracker = LatencyTracker::new();
+    for msg in routed_receiver {
+      tracker.record(&msg, now_ms());
+    }
+    for handle in handles {
+      handle.join().unwrap();
+    }
+    tracker
+  }
+
+  #[test]
+  fn per_peer_latency_reflects_router_delays() {
+    const TOLERANCE_MS: i64 = 50;
+    let delays_ms = [0, 60, 150];
+    let summary = route_with_delays(&delays_ms, 5, &ProtocolConfig::default()).summary();
+
+    assert_eq!(summary.len(), delays_ms.len());
+    for (latency, delay_ms) in summary.iter().zip(delays_ms.iter()) {
+      let delay_ms = *delay_ms as i64;
+      assert_eq!(latency.samples, 5);
+      // Same clock on both ends, so nothing arrives before its delay.
+      assert!(latency.min >= delay_ms, "{:?}", latency);
+      assert!(latency.median <= delay_ms + TOLERANCE_MS, "{:?}", latency);
+    }
+  }
+
+  #[test]
+  fn private_timing_leaves_nothing_to_track() {
+    let config = ProtocolConfig {
+      private_timing: true,
+      ..ProtocolConfig::default()
+    };
+    assert!(route_with_delays(&[10, 20], 2, &config)
+      .summary()
+      .is_empty());
+  }
+}
@@ -1,19 +1,43 @@
-use crate::common::types::{Keystore, AEAD};
-use curv::{FE, GE};
+use crate::common::config::ProtocolConfig;
+use crate::common::hash::{hash, HashAlg};
+#[cfg(feature = "schemars")]
+use crate::common::schema;
+use crate::common::types::{
+    CeremonyReport, Digest32, HealthReport, Keystore, PublicKey, RoundId, SchnorrSignature,
+    SealedData, Signature, AEAD,
+};
+use crate::errors::{CoreErrors, ErrorClass, ValidationKind};
+use curv::arithmetic::traits::Converter;
+use curv::{BigInt, FE, GE};
 use multi_party_ecdsa::protocols::multi_party_ecdsa::gg_2018::mta::{MessageA, MessageB};
 use multi_party_ecdsa::protocols::multi_party_ecdsa::gg_2018::party_i::{
     KeyGenBroadcastMessage1, KeyGenDecommitMessage1, Phase5ADecom1, Phase5Com1, Phase5Com2,
-    Phase5DDecom2, SignBroadcastPhase1, SignDecommitPhase1, Signature,
+    Phase5DDecom2, SignBroadcastPhase1, SignDecommitPhase1,
 };
 
 use curv::cryptographic_primitives::{
     proofs::sigma_correct_homomorphic_elgamal_enc::HomoELGamalProof, proofs::sigma_dlog::DLogProof,
     secret_sharing::feldman_vss::VerifiableSS,
 };
+use paillier::EncryptionKey;
 use serde::{Deserialize, Serialize};
 use std::fmt::Display;
+use std::sync::mpsc::{Receiver, Sender};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use zk_paillier::zkproofs::NICorrectKeyProof;
+
+/// Channel a protocol run writes its messages, logs and results into.
+pub type OutgoingChannel = Sender<Message>;
+/// Channel a protocol run reads messages from other parties out of. Only
+/// `Message::Send` is taken; relays address broadcasts before handing them
+/// over.
+pub type IncomingChannel = Receiver<Message>;
+/// Both ends a protocol run needs, in `(outgoing, incoming)` order.
+pub type ChannelPair = (OutgoingChannel, IncomingChannel);
 
 #[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub enum Errors {
     Halted = 0,
     Unknown = 1,
@@ -21,6 +45,24 @@ pub enum Errors {
     CollectTimeout = 10,
     CollectUnexpectedData = 11,
     CollectDisconnected = 12,
+
+    /// A `SigningPolicy` denied the request; the reason is in the log.
+    PolicyDenied = 20,
+}
+
+impl Errors {
+    /// Outgoing signal for a run that failed with `e`.
+    pub fn from_core_error(e: &CoreErrors) -> Self {
+        match e {
+            CoreErrors::Timeout { .. } => Errors::CollectTimeout,
+            CoreErrors::TransportIssue { .. } => Errors::CollectDisconnected,
+            CoreErrors::InvalidData {
+                kind: ValidationKind::PolicyDenied(_),
+                ..
+            } => Errors::PolicyDenied,
+            _ => Errors::Halted,
+        }
+    }
 }
 
 impl std::fmt::Display for Errors {
@@ -36,7 +78,10 @@ impl std::error::Error for Errors {
     }
 }
 
+/// What protocol runs used to write, before their channels carried
+/// `Message`. Kept for a deprecation period; see `Message`.
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub enum OutgoingMessages {
     Send {
         sender: u8,
@@ -47,6 +92,294 @@ pub enum OutgoingMessages {
     Quit,
     Error(Errors),
     Log(String),
+    /// Statistics of a collected round, sent at every round boundary.
+    Metrics(RoundMetrics),
+    /// Retry guidance for a failed run, sent right after its `Error`.
+    Classification(ErrorClass),
+    /// Every protocol message of a successful run has been sent; only
+    /// `Quit` follows, once the sink's `flush` returned. Transports should
+    /// deliver what they still queue before tearing down on `Quit`.
+    SendsIssued,
+}
+
+/// How collecting one round went for this party.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct RoundMetrics {
+    pub round: RoundId,
+    /// Time from the start of collecting until the round was complete or
+    /// timed out.
+    pub duration_ms: u64,
+    pub messages_received: usize,
+    /// Further messages from a party that already delivered for the round;
+    /// they are dropped and the first one is kept. Also counted in
+    /// `messages_received`.
+    pub duplicate_messages_rejected: usize,
+}
+
+/// Single wire type for everything a transport carries, and what the
+/// channels of a protocol run (`OutgoingChannel`, `IncomingChannel`) speak.
+///
+/// Migration: `OutgoingMessages` and `IncomingMessages` are kept for a
+/// deprecation period. Code still producing or matching them converts with
+/// `Message::from` and `OutgoingMessages::try_from`/`IncomingMessages::try_from`.
+/// Messages recorded in their JSON shapes deserialize as `Message` unchanged
+/// (`session`, `sent_at_ms` and `attempt` default to `None`), and a
+/// `Message::Send` still deserializes as either of them.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub enum Message {
+    /// Protocol payload for one party. Relayable.
+    Send {
+        sender: u8,
+        target: u8,
+        #[serde(default)]
+        session: Option<Vec<u8>>,
+        /// Shared with the other targets of a broadcast; serialized as the
+        /// `MessageData` itself.
+        data: Arc<MessageData>,
+        /// Sender's wall clock at sending, in ms since the Unix epoch.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        sent_at_ms: Option<u64>,
+        /// Retry attempt of the session the message belongs to.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        attempt: Option<u32>,
+    },
+    /// Protocol payload for every party but the sender. Relayable.
+    Broadcast {
+        sender: u8,
+        #[serde(default)]
+        session: Option<Vec<u8>>,
+        data: Arc<MessageData>,
+        /// Sender's wall clock at sending, in ms since the Unix epoch.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        sent_at_ms: Option<u64>,
+        /// Retry attempt of the session the message belongs to.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        attempt: Option<u32>,
+    },
+    /// Result of the local run. Never relayed.
+    Complete(RoundResult),
+    /// End of the local run. Never relayed.
+    Quit,
+    /// Failure of the local run. Never relayed.
+    Error(Errors),
+    /// Diagnostic line of the local run. Never relayed.
+    Log(String),
+    /// Round statistics of the local run. Never relayed.
+    Metrics(RoundMetrics),
+    /// Retry guidance for the failure of the local run. Never relayed.
+    Classification(ErrorClass),
+    /// All protocol messages of the local run were sent. Never relayed.
+    SendsIssued,
+}
+
+/// Wall clock in ms since the Unix epoch; 0 if the clock is before it.
+pub fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Where a relay should deliver a `Message`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RelayDecision {
+    To(u8),
+    AllExcept(u8),
+    /// Control message for the local application only.
+    Local,
+}
+
+impl Message {
+    /// Converts a message of a local run for the wire, stamping protocol
+    /// payloads with `config.attempt` and, unless `config.private_timing` is
+    /// set, the send time.
+    pub fn from_outgoing(msg: OutgoingMessages, config: &ProtocolConfig) -> Self {
+        Message::from(msg).stamped(config)
+    }
+
+    /// Stamps a protocol payload of a local run with `config.attempt` and,
+    /// unless `config.private_timing` is set, the send time. Control
+    /// messages are returned as they are.
+    pub fn stamped(mut self, config: &ProtocolConfig) -> Self {
+        let now = now_ms();
+        match &mut self {
+            Message::Send {
+                sent_at_ms,
+                attempt,
+                ..
+            }
+            | Message::Broadcast {
+                sent_at_ms,
+                attempt,
+                ..
+            } => {
+                if !config.private_timing {
+                    *sent_at_ms = Some(now);
+                }
+                *attempt = config.attempt;
+            }
+            _ => {}
+        }
+        self
+    }
+
+    pub fn sent_at_ms(&self) -> Option<u64> {
+        match self {
+            Message::Send { sent_at_ms, .. } | Message::Broadcast { sent_at_ms, .. } => *sent_at_ms,
+            _ => None,
+        }
+    }
+
+    pub fn relay_target(&self) -> RelayDecision {
+        match self {
+            Message::Send { target, .. } => RelayDecision::To(*target),
+            Message::Broadcast { sender, .. } => RelayDecision::AllExcept(*sender),
+            Message::Complete(_)
+            | Message::Quit
+            | Message::Error(_)
+            | Message::Log(_)
+            | Message::Metrics(_)
+            | Message::Classification(_)
+            | Message::SendsIssued => RelayDecision::Local,
+        }
+    }
+}
+
+/// Takes the payload out of `data`, copying it only while another message
+/// still shares it.
+pub(crate) fn unshare(data: Arc<MessageData>) -> MessageData {
+    Arc::try_unwrap(data).unwrap_or_else(|shared| (*shared).clone())
+}
+
+impl From<OutgoingMessages> for Message {
+    fn from(msg: OutgoingMessages) -> Self {
+        match msg {
+            OutgoingMessages::Send {
+                sender,
+                target,
+                data,
+            } => Message::Send {
+                sender,
+                target,
+                session: None,
+                data: Arc::new(data),
+                sent_at_ms: None,
+                attempt: None,
+            },
+            OutgoingMessages::Complete(result) => Message::Complete(result),
+            OutgoingMessages::Quit => Message::Quit,
+            OutgoingMessages::Error(e) => Message::Error(e),
+            OutgoingMessages::Log(line) => Message::Log(line),
+            OutgoingMessages::Metrics(metrics) => Message::Metrics(metrics),
+            OutgoingMessages::Classification(class) => Message::Classification(class),
+            OutgoingMessages::SendsIssued => Message::SendsIssued,
+        }
+    }
+}
+
+impl From<IncomingMessages> for Message {
+    fn from(msg: IncomingMessages) -> Self {
+        match msg {
+            IncomingMessages::Send {
+                sender,
+                target,
+                data,
+                sent_at_ms,
+                attempt,
+            } => Message::Send {
+                sender,
+                target,
+                session: None,
+                data: Arc::new(data),
+                sent_at_ms,
+                attempt,
+            },
+        }
+    }
+}
+
+impl std::convert::TryFrom<Message> for IncomingMessages {
+    type Error = Message;
+
+    /// Hands a relayed message to a protocol run. A broadcast has to be
+    /// addressed first, and control messages are given back as they are.
+    fn try_from(msg: Message) -> Result<Self, Message> {
+        match msg {
+            Message::Send {
+                sender,
+                target,
+                data,
+                sent_at_ms,
+                attempt,
+                ..
+            } => Ok(IncomingMessages::Send {
+                sender,
+                target,
+                data: unshare(data),
+                sent_at_ms,
+                attempt,
+            }),
+            other => Err(other),
+        }
+    }
+}
+
+impl std::convert::TryFrom<Message> for OutgoingMessages {
+    type Error = Message;
+
+    /// Gives a message of a local run back in the old shape. A broadcast has
+    /// no such shape and is given back as it is.
+    fn try_from(msg: Message) -> Result<Self, Message> {
+        match msg {
+            Message::Send {
+                sender,
+                target,
+                data,
+                ..
+            } => Ok(OutgoingMessages::Send {
+                sender,
+                target,
+                data: unshare(data),
+            }),
+            Message::Complete(result) => Ok(OutgoingMessages::Complete(result)),
+            Message::Quit => Ok(OutgoingMessages::Quit),
+            Message::Error(e) => Ok(OutgoingMessages::Error(e)),
+            Message::Log(line) => Ok(OutgoingMessages::Log(line)),
+            Message::Metrics(metrics) => Ok(OutgoingMessages::Metrics(metrics)),
+            Message::Classification(class) => Ok(OutgoingMessages::Classification(class)),
+            Message::SendsIssued => Ok(OutgoingMessages::SendsIssued),
+            other @ Message::Broadcast { .. } => Err(other),
+        }
+    }
+}
+
+impl Display for Message {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Message::Send {
+                sender,
+                target,
+                data,
+                ..
+            } => write!(f, "Send from {} to {}: {}", sender, target, data),
+            Message::Broadcast { sender, data, .. } => {
+                write!(f, "Broadcast from {}: {}", sender, data)
+            }
+            Message::Complete(r) => write!(f, "Complete with {}", r),
+            Message::Quit => write!(f, "Quit"),
+            Message::Error(e) => write!(f, "Error (code {})", *e as i32),
+            Message::Log(e) => write!(f, "Log {}", e),
+            Message::Metrics(m) => write!(
+                f,
+                "Metrics of {}: {} ms, {} received, {} duplicates",
+                m.round, m.duration_ms, m.messages_received, m.duplicate_messages_rejected
+            ),
+            Message::Classification(c) => write!(f, "Classification {:?}", c),
+            Message::SendsIssued => write!(f, "Sends issued"),
+        }
+    }
 }
 
 impl Display for IncomingMessages {
@@ -56,6 +389,7 @@ impl Display for IncomingMessages {
                 sender,
                 target,
                 data,
+                ..
             } => write!(f, "Receive from {} to {}: {}", sender, target, data),
         }
     }
@@ -72,27 +406,69 @@ impl Display for OutgoingMessages {
             OutgoingMessages::Quit => write!(f, "Quit"),
             OutgoingMessages::Error(e) => write!(f, "Error (code {})", *e as i32),
             OutgoingMessages::Log(e) => write!(f, "Log {}", e),
+            OutgoingMessages::Metrics(m) => write!(
+                f,
+                "Metrics of {}: {} ms, {} received, {} duplicates",
+                m.round, m.duration_ms, m.messages_received, m.duplicate_messages_rejected
+            ),
+            OutgoingMessages::Classification(c) => write!(f, "Classification {:?}", c),
+            OutgoingMessages::SendsIssued => write!(f, "Sends issued"),
         }
     }
 }
 
+/// What protocol runs used to read, before their channels carried
+/// `Message`. Kept for a deprecation period; see `Message`.
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub enum IncomingMessages {
+    /// Build with `IncomingMessages::make_send`, or from a received
+    /// `Message` with `IncomingMessages::try_from`. Fields may be added, so
+    /// other crates can't write this variant as a literal.
+    #[non_exhaustive]
     Send {
         sender: u8,
         target: u8,
         data: MessageData,
+        /// Carried over from `Message`; a run configured with an attempt id
+        /// or a message TTL drops stale messages by these.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        sent_at_ms: Option<u64>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        attempt: Option<u32>,
     },
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub enum RoundResult {
     KeyGen {
         private_key: Keystore,
-        public_key: GE,
+        public_key: PublicKey,
+        /// Audit record of the run, identical for every party.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        report: Option<CeremonyReport>,
     },
     Sign {
         signature: Signature,
+        /// Domain-separation context the digest was bound to, if any.
+        #[serde(default)]
+        context: Option<Vec<u8>>,
+        /// Hash the digest was built with, when corelib built it.
+        #[serde(default)]
+        hash_alg: Option<HashAlg>,
+        /// What the signing policy attached when it approved the request.
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        policy_annotations: Vec<String>,
+    },
+    PaillierRotation {
+        private_key: Keystore,
+    },
+    HealthCheck {
+        report: HealthReport,
+    },
+    SchnorrSign {
+        sig: SchnorrSignature,
     },
 }
 
@@ -100,7 +476,10 @@ impl Display for RoundResult {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             RoundResult::KeyGen { public_key, .. } => write!(f, "KeyGen {:?}", public_key),
-            RoundResult::Sign { signature } => write!(f, "Signature: {:?}", signature),
+            RoundResult::Sign { signature, .. } => write!(f, "Signature: {:?}", signature),
+            RoundResult::PaillierRotation { .. } => write!(f, "PaillierRotation"),
+            RoundResult::HealthCheck { report } => write!(f, "HealthCheck {:?}", report),
+            RoundResult::SchnorrSign { sig } => write!(f, "SchnorrSignature: {:?}", sig),
         }
     }
 }
@@ -108,7 +487,21 @@ impl Display for RoundResult {
 impl RoundResult {
     pub fn as_signature(&self) -> Option<&Signature> {
         match self {
-            RoundResult::Sign { signature } => Some(signature),
+            RoundResult::Sign { signature, .. } => Some(signature),
+            _ => None,
+        }
+    }
+
+    pub fn as_schnorr_signature(&self) -> Option<&SchnorrSignature> {
+        match self {
+            RoundResult::SchnorrSign { sig } => Some(sig),
+            _ => None,
+        }
+    }
+
+    pub fn as_health_report(&self) -> Option<&HealthReport> {
+        match self {
+            RoundResult::HealthCheck { report } => Some(report),
             _ => None,
         }
     }
@@ -116,22 +509,31 @@ impl RoundResult {
     pub fn as_keystore(&self) -> Option<&Keystore> {
         match self {
             RoundResult::KeyGen { private_key, .. } => Some(private_key),
+            RoundResult::PaillierRotation { private_key } => Some(private_key),
             _ => None,
         }
     }
 
-    pub fn as_public_key(&self) -> Option<&GE> {
+    pub fn as_ceremony_report(&self) -> Option<&CeremonyReport> {
+        match self {
+            RoundResult::KeyGen { report, .. } => report.as_ref(),
+            _ => None,
+        }
+    }
+
+    pub fn as_public_key(&self) -> Option<&PublicKey> {
         match self {
             RoundResult::KeyGen { public_key, .. } => Some(public_key),
             _ => None,
         }
     }
 
-    pub fn as_keygen(&self) -> Option<(&Keystore, &GE)> {
+    pub fn as_keygen(&self) -> Option<(&Keystore, &PublicKey)> {
         match self {
             RoundResult::KeyGen {
                 public_key,
                 private_key,
+                ..
             } => Some((private_key, public_key)),
             _ => None,
         }
@@ -145,42 +547,179 @@ impl RoundResult {
 // #[derive(Debug, Serialize, Deserialize, Clone)]
 // pub struct SignRound6Message(pub Phase5ADecom1, pub HomoELGamalProof);
 
+/// Keygen round-1 broadcast with the sender's hardware attestation quote
+/// over it.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct AttestedBroadcast {
+    #[cfg_attr(feature = "schemars", schemars(schema_with = "schema::opaque"))]
+    pub broadcast: KeyGenBroadcastMessage1,
+    pub quote: Vec<u8>,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct SignRound1Data {
+    #[cfg_attr(feature = "schemars", schemars(schema_with = "schema::opaque"))]
     pub com: SignBroadcastPhase1,
+    #[cfg_attr(feature = "schemars", schemars(schema_with = "schema::opaque"))]
     pub enc: MessageA,
+    /// Signing context of the sender; every signer must use the same one.
+    #[serde(default)]
+    pub context: Option<Vec<u8>>,
+    /// Hash of the sender's Paillier key vector, so a signer still on a
+    /// keystore from before a rotation is caught in round 1.
+    #[serde(default)]
+    #[cfg_attr(feature = "schemars", schemars(schema_with = "schema::bigint"))]
+    pub paillier_keys_hash: Option<BigInt>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct SignRound2Data {
+    #[cfg_attr(feature = "schemars", schemars(schema_with = "schema::opaque"))]
     pub g: MessageB,
+    #[cfg_attr(feature = "schemars", schemars(schema_with = "schema::opaque"))]
     pub w: MessageB,
+    /// Signer position the responses are meant for (from V4).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub recipient: Option<u8>,
+    /// SHA-256 of the recipient's round-1 `MessageA` ciphertext the
+    /// responses answer (from V4).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub responding_to: Option<Digest32>,
+}
+
+impl SignRound2Data {
+    /// Identifies the round-1 `MessageA` a round-2 response answers.
+    pub fn message_a_digest(m_a: &MessageA) -> Digest32 {
+        hash(HashAlg::Sha256, &BigInt::to_vec(&m_a.c))
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct SignRound6Data {
+    #[cfg_attr(feature = "schemars", schemars(schema_with = "schema::opaque"))]
     pub com: Phase5ADecom1,
+    #[cfg_attr(feature = "schemars", schemars(schema_with = "schema::opaque"))]
     pub proof: HomoELGamalProof,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct PaillierRotationData {
+    #[cfg_attr(feature = "schemars", schemars(schema_with = "schema::opaque"))]
+    pub ek: EncryptionKey,
+    #[cfg_attr(feature = "schemars", schemars(schema_with = "schema::opaque"))]
+    pub proof: NICorrectKeyProof,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct PaillierRotationConfirm {
+    #[cfg_attr(feature = "schemars", schemars(schema_with = "schema::bigint"))]
+    pub keys_hash: BigInt,
+}
+
+/// Sent once every party confirmed the same key vector: the sender switches
+/// to the rotated keystore as soon as it has everyone's commit.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct PaillierRotationCommit {
+    #[cfg_attr(feature = "schemars", schemars(schema_with = "schema::bigint"))]
+    pub keys_hash: BigInt,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct HealthCheckChallenge {
+    pub session_id: Vec<u8>,
+    #[cfg_attr(feature = "schemars", schemars(schema_with = "schema::bigint"))]
+    pub challenge: BigInt,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct HealthCheckResponse {
+    #[cfg_attr(feature = "schemars", schemars(schema_with = "schema::point"))]
+    pub commitment: GE,
+    #[cfg_attr(feature = "schemars", schemars(schema_with = "schema::scalar"))]
+    pub response: FE,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct SchnorrNonces {
+    #[cfg_attr(feature = "schemars", schemars(schema_with = "schema::point"))]
+    pub d: GE,
+    #[cfg_attr(feature = "schemars", schemars(schema_with = "schema::point"))]
+    pub e: GE,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct SchnorrPartial {
+    #[cfg_attr(feature = "schemars", schemars(schema_with = "schema::scalar"))]
+    pub s: FE,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub enum MessageData {
     None,
-    KeyGenRound1(KeyGenBroadcastMessage1),
-    KeyGenRound2(KeyGenDecommitMessage1),
+    KeyGenRound1(
+        #[cfg_attr(feature = "schemars", schemars(schema_with = "schema::opaque"))]
+        KeyGenBroadcastMessage1,
+    ),
+    KeyGenRound2(
+        #[cfg_attr(feature = "schemars", schemars(schema_with = "schema::opaque"))]
+        KeyGenDecommitMessage1,
+    ),
     KeyGenRound3(AEAD),
-    KeyGenRound4(VerifiableSS),
-    KeyGenRound5(DLogProof),
+    KeyGenRound4(
+        #[cfg_attr(feature = "schemars", schemars(schema_with = "schema::opaque"))] VerifiableSS,
+    ),
+    KeyGenRound5(
+        #[cfg_attr(feature = "schemars", schemars(schema_with = "schema::opaque"))] DLogProof,
+    ),
 
     SignRound1(SignRound1Data),
     SignRound2(SignRound2Data),
-    SignRound3(FE),
-    SignRound4(SignDecommitPhase1),
-    SignRound5(Phase5Com1),
+    SignRound3(#[cfg_attr(feature = "schemars", schemars(schema_with = "schema::scalar"))] FE),
+    SignRound4(
+        #[cfg_attr(feature = "schemars", schemars(schema_with = "schema::opaque"))]
+        SignDecommitPhase1,
+    ),
+    SignRound5(
+        #[cfg_attr(feature = "schemars", schemars(schema_with = "schema::opaque"))] Phase5Com1,
+    ),
     SignRound6(SignRound6Data),
-    SignRound7(Phase5Com2),
-    SignRound8(Phase5DDecom2),
-    SignRound9(FE),
+    SignRound7(
+        #[cfg_attr(feature = "schemars", schemars(schema_with = "schema::opaque"))] Phase5Com2,
+    ),
+    SignRound8(
+        #[cfg_attr(feature = "schemars", schemars(schema_with = "schema::opaque"))] Phase5DDecom2,
+    ),
+    SignRound9(#[cfg_attr(feature = "schemars", schemars(schema_with = "schema::scalar"))] FE),
+
+    PaillierRotationRound1(PaillierRotationData),
+    PaillierRotationRound2(PaillierRotationConfirm),
+    PaillierRotationRound3(PaillierRotationCommit),
+
+    HealthCheckChallenge(HealthCheckChallenge),
+    HealthCheckResponse(HealthCheckResponse),
+
+    SchnorrSignRound1(SchnorrNonces),
+    SchnorrSignRound2(SchnorrPartial),
+
+    /// The sender refused to take part in the run, with its reason. Ends the
+    /// run for every party that receives it.
+    Abort(String),
+    /// Keygen round 3 from `ProtocolVersion::V5` on.
+    KeyGenRound3Sealed(SealedData),
+    /// Keygen round 1 of an attested ceremony.
+    KeyGenRound1Attested(AttestedBroadcast),
 }
 
 impl std::fmt::Display for MessageData {
@@ -201,11 +740,73 @@ impl std::fmt::Display for MessageData {
             MessageData::SignRound7(_) => write!(f, "Message: {}", "SignRound7"),
             MessageData::SignRound8(_) => write!(f, "Message: {}", "SignRound8"),
             MessageData::SignRound9(_) => write!(f, "Message: {}", "SignRound9"),
+
+            MessageData::PaillierRotationRound1(_) => {
+                write!(f, "Message: {}", "PaillierRotationRound1")
+            }
+            MessageData::PaillierRotationRound2(_) => {
+                write!(f, "Message: {}", "PaillierRotationRound2")
+            }
+            MessageData::PaillierRotationRound3(_) => {
+                write!(f, "Message: {}", "PaillierRotationRound3")
+            }
+
+            MessageData::HealthCheckChallenge(_) => {
+                write!(f, "Message: {}", "HealthCheckChallenge")
+            }
+            MessageData::HealthCheckResponse(_) => write!(f, "Message: {}", "HealthCheckResponse"),
+
+            MessageData::SchnorrSignRound1(_) => write!(f, "Message: {}", "SchnorrSignRound1"),
+            MessageData::SchnorrSignRound2(_) => write!(f, "Message: {}", "SchnorrSignRound2"),
+            MessageData::Abort(_) => write!(f, "Message: {}", "Abort"),
+            MessageData::KeyGenRound3Sealed(_) => write!(f, "Message: {}", "KeyGenRound3Sealed"),
+            MessageData::KeyGenRound1Attested(_) => {
+                write!(f, "Message: {}", "KeyGenRound1Attested")
+            }
             _ => write!(f, "Message: Error"),
         }
     }
 }
 
+impl MessageData {
+    pub fn round(&self) -> Option<RoundId> {
+        match self {
+            MessageData::None => None,
+            MessageData::KeyGenRound1(_) | MessageData::KeyGenRound1Attested(_) => {
+                Some(RoundId::KeyGen(1))
+            }
+            MessageData::KeyGenRound2(_) => Some(RoundId::KeyGen(2)),
+            MessageData::KeyGenRound3(_) | MessageData::KeyGenRound3Sealed(_) => {
+                Some(RoundId::KeyGen(3))
+            }
+            MessageData::KeyGenRound4(_) => Some(RoundId::KeyGen(4)),
+            MessageData::KeyGenRound5(_) => Some(RoundId::KeyGen(5)),
+
+            MessageData::SignRound1(_) => Some(RoundId::Sign(1)),
+            MessageData::SignRound2(_) => Some(RoundId::Sign(2)),
+            MessageData::SignRound3(_) => Some(RoundId::Sign(3)),
+            MessageData::SignRound4(_) => Some(RoundId::Sign(4)),
+            MessageData::SignRound5(_) => Some(RoundId::Sign(5)),
+            MessageData::SignRound6(_) => Some(RoundId::Sign(6)),
+            MessageData::SignRound7(_) => Some(RoundId::Sign(7)),
+            MessageData::SignRound8(_) => Some(RoundId::Sign(8)),
+            MessageData::SignRound9(_) => Some(RoundId::Sign(9)),
+
+            MessageData::PaillierRotationRound1(_) => Some(RoundId::PaillierRotation(1)),
+            MessageData::PaillierRotationRound2(_) => Some(RoundId::PaillierRotation(2)),
+            MessageData::PaillierRotationRound3(_) => Some(RoundId::PaillierRotation(3)),
+
+            MessageData::HealthCheckChallenge(_) => Some(RoundId::HealthCheck(1)),
+            MessageData::HealthCheckResponse(_) => Some(RoundId::HealthCheck(2)),
+
+            MessageData::SchnorrSignRound1(_) => Some(RoundId::SchnorrSign(1)),
+            MessageData::SchnorrSignRound2(_) => Some(RoundId::SchnorrSign(2)),
+
+            MessageData::Abort(_) => None,
+        }
+    }
+}
+
 pub trait FromData
 where
     Self: Sized,
@@ -237,6 +838,14 @@ impl FromData for AEAD {
         }
     }
 }
+impl FromData for SealedData {
+    fn get_from_data(data: MessageData) -> Option<Self> {
+        match data {
+            MessageData::KeyGenRound3Sealed(value) => Some(value),
+            _ => None,
+        }
+    }
+}
 impl FromData for VerifiableSS {
     fn get_from_data(data: MessageData) -> Option<Self> {
         match data {
@@ -254,6 +863,14 @@ impl FromData for DLogProof {
     }
 }
 
+impl FromData for AttestedBroadcast {
+    fn get_from_data(data: MessageData) -> Option<Self> {
+        match data {
+            MessageData::KeyGenRound1Attested(value) => Some(value),
+            _ => None,
+        }
+    }
+}
 impl FromData for SignRound1Data {
     fn get_from_data(data: MessageData) -> Option<Self> {
         match data {
@@ -319,6 +936,62 @@ impl FromData for Phase5DDecom2 {
         }
     }
 }
+impl FromData for PaillierRotationData {
+    fn get_from_data(data: MessageData) -> Option<Self> {
+        match data {
+            MessageData::PaillierRotationRound1(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+impl FromData for PaillierRotationConfirm {
+    fn get_from_data(data: MessageData) -> Option<Self> {
+        match data {
+            MessageData::PaillierRotationRound2(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+impl FromData for PaillierRotationCommit {
+    fn get_from_data(data: MessageData) -> Option<Self> {
+        match data {
+            MessageData::PaillierRotationRound3(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+impl FromData for HealthCheckChallenge {
+    fn get_from_data(data: MessageData) -> Option<Self> {
+        match data {
+            MessageData::HealthCheckChallenge(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+impl FromData for HealthCheckResponse {
+    fn get_from_data(data: MessageData) -> Option<Self> {
+        match data {
+            MessageData::HealthCheckResponse(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+impl FromData for SchnorrNonces {
+    fn get_from_data(data: MessageData) -> Option<Self> {
+        match data {
+            MessageData::SchnorrSignRound1(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+impl FromData for SchnorrPartial {
+    fn get_from_data(data: MessageData) -> Option<Self> {
+        match data {
+            MessageData::SchnorrSignRound2(value) => Some(value),
+            _ => None,
+        }
+    }
+}
 
 pub trait GetData<T> {
     fn get_data(self) -> Option<T>;
@@ -365,6 +1038,71 @@ impl GetData<DLogProof> for MessageData {
     }
 }
 
+impl IncomingMessages {
+    pub fn make_send(sender: u8, target: u8, data: &MessageData) -> Self {
+        IncomingMessages::Send {
+            sender,
+            target,
+            data: data.clone(),
+            sent_at_ms: None,
+            attempt: None,
+        }
+    }
+}
+
+impl Message {
+    pub fn make_send(sender: u8, target: u8, data: &MessageData) -> Self {
+        Self::make_send_shared(sender, target, Arc::new(data.clone()))
+    }
+
+    /// `make_send` for a payload other messages may share.
+    pub fn make_send_shared(sender: u8, target: u8, data: Arc<MessageData>) -> Self {
+        Message::Send {
+            sender,
+            target,
+            session: None,
+            data,
+            sent_at_ms: None,
+            attempt: None,
+        }
+    }
+
+    pub fn make_complete_keygen(keystore: &Keystore) -> Self {
+        OutgoingMessages::make_complete_keygen(keystore).into()
+    }
+
+    pub fn make_complete_keygen_with_report(keystore: &Keystore, report: CeremonyReport) -> Self {
+        OutgoingMessages::make_complete_keygen_with_report(keystore, report).into()
+    }
+
+    pub fn make_complete_schnorr_signature(sig: SchnorrSignature) -> Self {
+        OutgoingMessages::make_complete_schnorr_signature(sig).into()
+    }
+
+    pub fn make_complete_signature(
+        sig: Signature,
+        context: Option<Vec<u8>>,
+        hash_alg: Option<HashAlg>,
+    ) -> Self {
+        OutgoingMessages::make_complete_signature(sig, context, hash_alg).into()
+    }
+
+    pub fn make_complete_signature_with_annotations(
+        sig: Signature,
+        context: Option<Vec<u8>>,
+        hash_alg: Option<HashAlg>,
+        policy_annotations: Vec<String>,
+    ) -> Self {
+        OutgoingMessages::make_complete_signature_with_annotations(
+            sig,
+            context,
+            hash_alg,
+            policy_annotations,
+        )
+        .into()
+    }
+}
+
 impl OutgoingMessages {
     pub fn into_incoming(&self) -> Option<IncomingMessages> {
         match self {
@@ -372,11 +1110,7 @@ impl OutgoingMessages {
                 sender,
                 target,
                 data,
-            } => Some(IncomingMessages::Send {
-                sender: sender.clone(),
-                target: target.clone(),
-                data: data.clone(),
-            }),
+            } => Some(IncomingMessages::make_send(*sender, *target, data)),
             _ => None,
         }
     }
@@ -392,13 +1126,42 @@ impl OutgoingMessages {
     pub fn make_complete_keygen(keystore: &Keystore) -> Self {
         OutgoingMessages::Complete(RoundResult::KeyGen {
             private_key: keystore.clone(), // base64::encode(bincode::serialize(&keystore).unwrap().as_slice()),
-            public_key: keystore.y_sum.clone(),
+            public_key: keystore.public_key(),
+            report: None,
+        })
+    }
+
+    pub fn make_complete_keygen_with_report(keystore: &Keystore, report: CeremonyReport) -> Self {
+        OutgoingMessages::Complete(RoundResult::KeyGen {
+            private_key: keystore.clone(),
+            public_key: keystore.public_key(),
+            report: Some(report),
         })
     }
 
-    pub fn make_complete_signature(sig: Signature) -> Self {
+    pub fn make_complete_schnorr_signature(sig: SchnorrSignature) -> Self {
+        OutgoingMessages::Complete(RoundResult::SchnorrSign { sig })
+    }
+
+    pub fn make_complete_signature(
+        sig: Signature,
+        context: Option<Vec<u8>>,
+        hash_alg: Option<HashAlg>,
+    ) -> Self {
+        Self::make_complete_signature_with_annotations(sig, context, hash_alg, vec![])
+    }
+
+    pub fn make_complete_signature_with_annotations(
+        sig: Signature,
+        context: Option<Vec<u8>>,
+        hash_alg: Option<HashAlg>,
+        policy_annotations: Vec<String>,
+    ) -> Self {
         OutgoingMessages::Complete(RoundResult::Sign {
             signature: sig, //base64::encode(bincode::serialize(&sig).unwrap().as_slice()),
+            context,
+            hash_alg,
+            policy_annotations,
         })
     }
 }
@@ -408,3 +1171,133 @@ impl OutgoingMessages {
 //         bincode::deserialize::<MessageData>(&input.as_slice()).unwrap()
 //     }
 // }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+    use std::convert::TryFrom;
+
+    const OUTGOING_V1: &str = include_str!("../../tests/fixtures/messages_v1/outgoing.jsonl");
+    const INCOMING_V1: &str = include_str!("../../tests/fixtures/messages_v1/incoming.jsonl");
+
+    fn lines(recorded: &str) -> Vec<Value> {
+        recorded
+            .lines()
+            .filter(|line| !line.is_empty())
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn recorded_outgoing_messages_deserialize_as_message() {
+        let recorded = lines(OUTGOING_V1);
+        assert_eq!(recorded.len(), 8);
+        for old in recorded {
+            let msg: Message = serde_json::from_value(old.clone()).unwrap();
+            let legacy: OutgoingMessages = serde_json::from_value(old.clone()).unwrap();
+            assert_eq!(
+                serde_json::to_value(&msg).unwrap(),
+                serde_json::to_value(&Message::from(legacy)).unwrap()
+            );
+            let back = OutgoingMessages::try_from(msg).unwrap();
+            assert_eq!(serde_json::to_value(&back).unwrap(), old);
+        }
+    }
+
+    #[test]
+    fn recorded_incoming_messages_deserialize_as_message() {
+        let recorded = lines(INCOMING_V1);
+        assert_eq!(recorded.len(), 2);
+
+        let first: Message = serde_json::from_value(recorded[0].clone()).unwrap();
+        match &first {
+            Message::Send {
+                sender,
+                target,
+                session,
+                data,
+                sent_at_ms,
+                attempt,
+            } => {
+                assert_eq!((*sender, *target), (1, 0));
+                assert!(matches!(**data, MessageData::Abort(_)));
+                assert!(session.is_none() && sent_at_ms.is_none() && attempt.is_none());
+            }
+            other => panic!("not a send: {}", other),
+        }
+        let second: Message = serde_json::from_value(recorded[1].clone()).unwrap();
+        assert!(matches!(
+            &second,
+            Message::Send {
+                sender: 2,
+                sent_at_ms: Some(1_700_000_000_000),
+                attempt: Some(3),
+                ..
+            }
+        ));
+
+        for (old, msg) in recorded.iter().zip(vec![first, second]) {
+            let back = IncomingMessages::try_from(msg).unwrap();
+            assert_eq!(&serde_json::to_value(&back).unwrap(), old);
+        }
+    }
+
+    #[test]
+    fn message_send_deserializes_as_the_old_shapes() {
+        let msg = Message::Send {
+            sender: 1,
+            target: 2,
+            session: Some(b"session".to_vec()),
+            data: Arc::new(MessageData::Abort("stop".to_string())),
+            sent_at_ms: Some(5),
+            attempt: Some(1),
+        };
+        let json = serde_json::to_string(&msg).unwrap();
+
+        match serde_json::from_str::<IncomingMessages>(&json).unwrap() {
+            IncomingMessages::Send {
+                sender,
+                target,
+                sent_at_ms,
+                attempt,
+                ..
+            } => assert_eq!(
+                (sender, target, sent_at_ms, attempt),
+                (1, 2, Some(5), Some(1))
+            ),
+        }
+        assert!(matches!(
+            serde_json::from_str::<OutgoingMessages>(&json).unwrap(),
+            OutgoingMessages::Send {
+                sender: 1,
+                target: 2,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn shared_payload_serializes_as_the_payload() {
+        let data = MessageData::Abort("stop".to_string());
+        let msg = Message::make_send_shared(0, 1, Arc::new(data.clone()));
+        let json = serde_json::to_value(&msg).unwrap();
+        assert_eq!(json["Send"]["data"], serde_json::to_value(&data).unwrap());
+    }
+
+    #[test]
+    fn broadcast_has_no_old_shape() {
+        let broadcast = Message::Broadcast {
+            sender: 0,
+            session: None,
+            data: Arc::new(MessageData::Abort("stop".to_string())),
+            sent_at_ms: None,
+            attempt: None,
+        };
+        assert!(matches!(
+            OutgoingMessages::try_from(broadcast.clone()),
+            Err(Message::Broadcast { sender: 0, .. })
+        ));
+        assert!(IncomingMessages::try_from(broadcast).is_err());
+    }
+}
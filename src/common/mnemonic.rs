@@ -0,0 +1,39 @@
+use crate::errors::{CoreErrors, ValidationKind};
+use bip39::{Error, Mnemonic};
+
+pub use bip39::Language;
+
+/// 24-word BIP39 mnemonic of a 32-byte secret, checksum included.
+pub fn encode(secret: &[u8; 32], language: Language) -> String {
+  // 256 bits is always a valid BIP39 entropy length.
+  Mnemonic::from_entropy_in(language, secret)
+    .expect("32 bytes of entropy")
+    .to_string()
+}
+
+/// Inverse of `encode`. Unknown words are reported with their 1-based
+/// position in the phrase.
+pub fn decode(phrase: &str, language: Language) -> Result<[u8; 32], CoreErrors> {
+  let mnemonic = Mnemonic::parse_in(language, phrase).map_err(|e| match e {
+    Error::UnknownWord(i) => CoreErrors::invalid_data(ValidationKind::UnknownWord {
+      position: i + 1,
+      word: phrase
+        .split_whitespace()
+        .nth(i)
+        .unwrap_or_default()
+        .to_string(),
+    }),
+    Error::InvalidChecksum => CoreErrors::invalid_data(ValidationKind::BadChecksum),
+    e => CoreErrors::invalid_data(ValidationKind::Malformed(e.to_string())),
+  })?;
+
+  let entropy = mnemonic.to_entropy();
+  if entropy.len() != 32 {
+    return Err(CoreErrors::invalid_data(ValidationKind::Malformed(
+      format!("Expected 24 words, got {}", mnemonic.word_count()),
+    )));
+  }
+  let mut out = [0u8; 32];
+  out.copy_from_slice(&entropy);
+  Ok(out)
+}
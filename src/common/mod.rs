@@ -1,3 +1,20 @@
+pub mod backup;
+pub mod channel;
+#[cfg(feature = "cloud-backup")]
+pub mod cloud_backup;
+pub mod config;
+#[cfg(feature = "debug-dump")]
+pub mod dump;
+pub mod events;
+pub mod hash;
+#[cfg(feature = "json")]
+pub mod json;
+pub mod latency;
 pub mod messages;
+#[cfg(feature = "mnemonic")]
+pub mod mnemonic;
+#[cfg(feature = "schemars")]
+pub mod schema;
+pub mod signers;
 pub mod types;
 pub mod utils;
@@ -0,0 +1,151 @@
+use crate::common::messages::{
+  Errors, IncomingMessages, Message, MessageData, OutgoingMessages, RoundResult,
+};
+use crate::common::types::{Keystore, SealedData, AEAD};
+use schemars::gen::SchemaGenerator;
+use schemars::schema::{
+  InstanceType, Metadata, ObjectValidation, RootSchema, Schema, SchemaObject,
+};
+use schemars::schema_for;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+fn described(instance: InstanceType, description: &str) -> SchemaObject {
+  SchemaObject {
+    instance_type: Some(instance.into()),
+    metadata: Some(Box::new(Metadata {
+      description: Some(description.to_owned()),
+      ..Default::default()
+    })),
+    ..Default::default()
+  }
+}
+
+/// secp256k1 scalar, serialized by curv as a lowercase hex string.
+pub(crate) fn scalar(_: &mut SchemaGenerator) -> Schema {
+  let mut schema = described(InstanceType::String, "secp256k1 scalar (hex)");
+  schema.string().pattern = Some("^[0-9a-f]*$".to_owned());
+  schema.into()
+}
+
+/// secp256k1 point, serialized by curv as an object of hex coordinates.
+pub(crate) fn point(gen: &mut SchemaGenerator) -> Schema {
+  let mut schema = described(
+    InstanceType::Object,
+    "secp256k1 point (affine hex coordinates)",
+  );
+  let coordinate = scalar(gen);
+  *schema.object() = ObjectValidation {
+    properties: vec![
+      ("x".to_owned(), coordinate.clone()),
+      ("y".to_owned(), coordinate),
+    ]
+    .into_iter()
+    .collect(),
+    required: vec!["x".to_owned(), "y".to_owned()].into_iter().collect(),
+    ..Default::default()
+  };
+  schema.into()
+}
+
+/// List of secp256k1 scalars.
+pub(crate) fn scalars(gen: &mut SchemaGenerator) -> Schema {
+  let mut schema = described(InstanceType::Array, "secp256k1 scalars (hex)");
+  schema.array().items = Some(scalar(gen).into());
+  schema.into()
+}
+
+/// ECDSA signature as the `r` and `s` scalars.
+pub(crate) fn signature(gen: &mut SchemaGenerator) -> Schema {
+  let mut schema = described(InstanceType::Object, "ECDSA signature");
+  let component = scalar(gen);
+  *schema.object() = ObjectValidation {
+    properties: vec![
+      ("r".to_owned(), component.clone()),
+      ("s".to_owned(), component),
+    ]
+    .into_iter()
+    .collect(),
+    required: vec!["r".to_owned(), "s".to_owned()].into_iter().collect(),
+    ..Default::default()
+  };
+  schema.into()
+}
+
+/// 32-byte digest as lowercase hex.
+pub(crate) fn digest32(_: &mut SchemaGenerator) -> Schema {
+  let mut schema = described(InstanceType::String, "32-byte digest (hex)");
+  schema.string().pattern = Some("^[0-9a-f]{64}$".to_owned());
+  schema.into()
+}
+
+/// Arbitrary-precision integer.
+pub(crate) fn bigint(_: &mut SchemaGenerator) -> Schema {
+  described(InstanceType::String, "arbitrary-precision integer").into()
+}
+
+/// Structures owned by curv / multi-party-ecdsa / paillier. Their shape
+/// follows the pinned dependency versions and is not described further.
+pub(crate) fn opaque(_: &mut SchemaGenerator) -> Schema {
+  described(
+    InstanceType::Object,
+    "protocol payload defined by the pinned dependencies",
+  )
+  .into()
+}
+
+/// Every root schema, keyed by the file name it is stored under.
+pub fn schemas() -> Vec<(&'static str, RootSchema)> {
+  vec![
+    ("message.json", schema_for!(Message)),
+    ("incoming_messages.json", schema_for!(IncomingMessages)),
+    ("outgoing_messages.json", schema_for!(OutgoingMessages)),
+    ("message_data.json", schema_for!(MessageData)),
+    ("round_result.json", schema_for!(RoundResult)),
+    ("errors.json", schema_for!(Errors)),
+    ("aead.json", schema_for!(AEAD)),
+    ("sealed_data.json", schema_for!(SealedData)),
+    ("keystore.json", schema_for!(Keystore)),
+  ]
+}
+
+fn render(schema: &RootSchema) -> io::Result<String> {
+  let mut json = serde_json::to_string_pretty(schema)?;
+  json.push('\n');
+  Ok(json)
+}
+
+/// Writes every schema into `dir`, replacing existing files.
+pub fn write_schemas(dir: &Path) -> io::Result<()> {
+  fs::create_dir_all(dir)?;
+  for (name, schema) in schemas() {
+    fs::write(dir.join(name), render(&schema)?)?;
+  }
+  Ok(())
+}
+
+/// Returns the names of schemas in `dir` that are missing or differ from
+/// what the current types generate.
+pub fn stale_schemas(dir: &Path) -> io::Result<Vec<&'static str>> {
+  let mut stale = vec![];
+  for (name, schema) in schemas() {
+    let committed = fs::read_to_string(dir.join(name)).unwrap_or_default();
+    if committed != render(&schema)? {
+      stale.push(name);
+    }
+  }
+  Ok(stale)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // Regenerate with `cargo run --example schemas --features schemars`.
+  #[test]
+  fn committed_schemas_are_current() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("schemas");
+    assert_eq!(stale_schemas(&dir).unwrap(), Vec::<&str>::new());
+  }
+}
@@ -0,0 +1,137 @@
+use crate::common::types::{KeystoreParameters, SignerCount};
+use crate::errors::{CoreErrors, RequestField, ValidationKind};
+use std::collections::BTreeSet;
+
+/// What the coordinator knows about a party when picking signers.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PartyStatus {
+  pub index: usize,
+  pub online: bool,
+  /// Signing sessions the party is currently part of.
+  pub load: Option<u32>,
+  /// Recent failed sessions the party took part in.
+  pub failures: Option<u32>,
+  /// When the party last signed, on any monotonic scale; `None` if never.
+  pub last_used: Option<u64>,
+}
+
+/// How to choose among the parties that qualify (online, valid index).
+pub enum SelectionPolicy {
+  LowestIndices,
+  /// Uniform choice, reproducible for a given seed.
+  Random(u64),
+  /// Parties that signed least recently first, then fewest failures.
+  LeastRecentlyUsed,
+  /// Returns the chosen indices out of the qualifying parties. The choice is
+  /// validated like any other.
+  Custom(Box<dyn Fn(&[PartyStatus]) -> Vec<usize>>),
+}
+
+/// A validated signing set: exactly `threshold + 1` distinct party indices,
+/// ascending. The position of a party in it is its `party_num_id` for
+/// `sign`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Signers(Vec<usize>);
+
+// splitmix64, enough for a reproducible shuffle.
+fn next_random(state: &mut u64) -> u64 {
+  *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+  let mut z = *state;
+  z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+  z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+  z ^ (z >> 31)
+}
+
+impl Signers {
+  pub fn select(
+    params: &KeystoreParameters,
+    candidates: &[PartyStatus],
+    policy: SelectionPolicy,
+  ) -> Result<Signers, CoreErrors> {
+    let needed = SignerCount::required_for(params.threshold).get() as usize;
+    let share_count = params.share_count as usize;
+
+    let mut seen = BTreeSet::new();
+    let mut qualified: Vec<PartyStatus> = candidates
+      .iter()
+      .filter(|c| c.online && c.index < share_count && seen.insert(c.index))
+      .cloned()
+      .collect();
+    if qualified.len() < needed {
+      return Err(CoreErrors::mismatch(
+        RequestField::SignerCount,
+        needed,
+        qualified.len(),
+      ));
+    }
+
+    let chosen: Vec<usize> = match policy {
+      SelectionPolicy::LowestIndices => {
+        qualified.sort_by_key(|c| c.index);
+        qualified.iter().take(needed).map(|c| c.index).collect()
+      }
+      SelectionPolicy::Random(seed) => {
+        qualified.sort_by_key(|c| c.index);
+        let mut state = seed;
+        for i in (1..qualified.len()).rev() {
+          let j = (next_random(&mut state) % (i as u64 + 1)) as usize;
+          qualified.swap(i, j);
+        }
+        qualified.iter().take(needed).map(|c| c.index).collect()
+      }
+      SelectionPolicy::LeastRecentlyUsed => {
+        qualified.sort_by_key(|c| (c.last_used, c.failures.unwrap_or(0), c.index));
+        qualified.iter().take(needed).map(|c| c.index).collect()
+      }
+      SelectionPolicy::Custom(choose) => {
+        let chosen = choose(&qualified);
+        if let Some(i) = chosen
+          .iter()
+          .find(|i| !qualified.iter().any(|c| c.index == **i))
+        {
+          return Err(CoreErrors::invalid_data(ValidationKind::Malformed(
+            format!("Selection policy chose party {} which does not qualify", i),
+          )));
+        }
+        chosen
+      }
+    };
+
+    Signers::new(params, chosen)
+  }
+
+  /// Validates a signing set chosen elsewhere.
+  pub fn new(params: &KeystoreParameters, mut indices: Vec<usize>) -> Result<Signers, CoreErrors> {
+    indices.sort();
+    indices.dedup();
+    let needed = SignerCount::required_for(params.threshold).get() as usize;
+    if indices.len() != needed {
+      return Err(CoreErrors::mismatch(
+        RequestField::SignerCount,
+        needed,
+        indices.len(),
+      ));
+    }
+    if let Some(index) = indices.iter().find(|i| **i >= params.share_count as usize) {
+      return Err(CoreErrors::mismatch(
+        RequestField::SignerIndex,
+        params.share_count as usize,
+        *index,
+      ));
+    }
+    Ok(Signers(indices))
+  }
+
+  pub fn indices(&self) -> &[usize] {
+    &self.0
+  }
+
+  /// Position of party `index` in the set, if it signs.
+  pub fn position(&self, index: usize) -> Option<u8> {
+    self.0.iter().position(|i| *i == index).map(|p| p as u8)
+  }
+
+  pub fn into_vec(self) -> Vec<usize> {
+    self.0
+  }
+}
@@ -1,40 +1,1023 @@
+// Defines `Keystore` and works on its deprecated raw fields.
+#![allow(deprecated)]
+
+use crate::common::config::ProtocolVersion;
+#[cfg(feature = "schemars")]
+use crate::common::schema;
+use crate::common::utils::{bigint_to_hex, check_vss_shape};
+use crate::errors::{CoreErrors, RequestField, ValidationKind};
+use curv::arithmetic::traits::Converter;
+use curv::cryptographic_primitives::hashing::{hash_sha256::HSha256, traits::Hash};
 use curv::cryptographic_primitives::secret_sharing::feldman_vss::VerifiableSS;
-use curv::{FE, GE};
+use curv::elliptic::curves::traits::{ECPoint, ECScalar};
+use curv::{BigInt, FE, GE};
 use multi_party_ecdsa::protocols::multi_party_ecdsa::gg_2018::party_i::{
-  Keys, Parameters, SharedKeys,
+  self, Keys, Parameters, SharedKeys,
 };
 use paillier::EncryptionKey;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+
+pub type PartyId = u8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub enum RoundId {
+  KeyGen(u8),
+  Sign(u8),
+  PaillierRotation(u8),
+  HealthCheck(u8),
+  SchnorrSign(u8),
+}
 
+impl RoundId {
+  /// Name of the protocol the round belongs to, e.g. for metric labels.
+  pub fn protocol(&self) -> &'static str {
+    match self {
+      RoundId::KeyGen(_) => "keygen",
+      RoundId::Sign(_) => "sign",
+      RoundId::PaillierRotation(_) => "paillier_rotation",
+      RoundId::HealthCheck(_) => "health_check",
+      RoundId::SchnorrSign(_) => "schnorr_sign",
+    }
+  }
+
+  pub fn number(&self) -> u8 {
+    match self {
+      RoundId::KeyGen(r)
+      | RoundId::Sign(r)
+      | RoundId::PaillierRotation(r)
+      | RoundId::HealthCheck(r)
+      | RoundId::SchnorrSign(r) => *r,
+    }
+  }
+}
+
+impl std::fmt::Display for RoundId {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      RoundId::KeyGen(r) => write!(f, "KeyGenRound{}", r),
+      RoundId::Sign(r) => write!(f, "SignRound{}", r),
+      RoundId::PaillierRotation(r) => write!(f, "PaillierRotationRound{}", r),
+      RoundId::HealthCheck(r) => write!(f, "HealthCheckRound{}", r),
+      RoundId::SchnorrSign(r) => write!(f, "SchnorrSignRound{}", r),
+    }
+  }
+}
+
+/// Unversioned AES-GCM ciphertext of keygen round 3 before
+/// `ProtocolVersion::V5`, always sealed under `LEGACY_NONCE`. New code uses
+/// `SealedData`, which also reads this shape.
 #[derive(Clone, PartialEq, Default, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct AEAD {
   pub ciphertext: Vec<u8>,
   pub tag: Vec<u8>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// The nonce every `AEAD` was sealed under.
+pub const LEGACY_NONCE: [u8; 12] = [3; 12];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub enum CipherId {
+  Aes256Gcm,
+}
+
+impl CipherId {
+  fn from_name(name: &str) -> Result<Self, CoreErrors> {
+    match name {
+      "Aes256Gcm" => Ok(CipherId::Aes256Gcm),
+      other => Err(CoreErrors::invalid_data(ValidationKind::UnsupportedCipher(
+        other.to_string(),
+      ))),
+    }
+  }
+}
+
+/// Authenticated ciphertext along with what it takes to open it. New
+/// ciphers or layouts come as new versions; the old `AEAD` shape still
+/// deserializes, as a `V1` under `LEGACY_NONCE`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub enum SealedData {
+  V1 {
+    cipher: CipherId,
+    nonce: [u8; 12],
+    ciphertext: Vec<u8>,
+    tag: [u8; 16],
+  },
+}
+
+impl SealedData {
+  /// The unversioned shape, for peers before `ProtocolVersion::V5`. Only
+  /// AES-GCM under `LEGACY_NONCE` has one.
+  pub fn into_legacy(self) -> Result<AEAD, CoreErrors> {
+    match self {
+      SealedData::V1 {
+        cipher: CipherId::Aes256Gcm,
+        nonce,
+        ciphertext,
+        tag,
+      } if nonce == LEGACY_NONCE => Ok(AEAD {
+        ciphertext,
+        tag: tag.to_vec(),
+      }),
+      SealedData::V1 { .. } => Err(CoreErrors::invalid_data(ValidationKind::Malformed(
+        "Sealed data has no legacy form".to_string(),
+      ))),
+    }
+  }
+}
+
+impl std::convert::TryFrom<AEAD> for SealedData {
+  type Error = CoreErrors;
+
+  fn try_from(aead: AEAD) -> Result<Self, CoreErrors> {
+    Ok(SealedData::V1 {
+      cipher: CipherId::Aes256Gcm,
+      nonce: LEGACY_NONCE,
+      ciphertext: aead.ciphertext,
+      tag: fixed_bytes(&aead.tag, "tag")?,
+    })
+  }
+}
+
+fn fixed_bytes<const N: usize>(bytes: &[u8], what: &str) -> Result<[u8; N], CoreErrors> {
+  let mut out = [0u8; N];
+  if bytes.len() != N {
+    return Err(CoreErrors::invalid_data(ValidationKind::Malformed(
+      format!(
+        "Sealed data {} is {} bytes, expected {}",
+        what,
+        bytes.len(),
+        N
+      ),
+    )));
+  }
+  out.copy_from_slice(bytes);
+  Ok(out)
+}
+
+// Every shape `SealedData` was ever written in; the version tag and cipher
+// are read as strings so unknown ones fail with their own error.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SealedDataRepr {
+  Legacy(AEAD),
+  Versioned(std::collections::BTreeMap<String, SealedBody>),
+}
+
+#[derive(Deserialize)]
+struct SealedBody {
+  cipher: String,
+  nonce: Vec<u8>,
+  ciphertext: Vec<u8>,
+  tag: Vec<u8>,
+}
+
+impl SealedDataRepr {
+  fn into_sealed(self) -> Result<SealedData, CoreErrors> {
+    use std::convert::TryFrom;
+    let versioned = match self {
+      SealedDataRepr::Legacy(aead) => return SealedData::try_from(aead),
+      SealedDataRepr::Versioned(versioned) => versioned,
+    };
+    let mut entries = versioned.into_iter();
+    let (version, body) = match (entries.next(), entries.next()) {
+      (Some(entry), None) => entry,
+      _ => {
+        return Err(CoreErrors::invalid_data(ValidationKind::Malformed(
+          "Sealed data must have exactly one version".to_string(),
+        )))
+      }
+    };
+    match version.as_str() {
+      "V1" => Ok(SealedData::V1 {
+        cipher: CipherId::from_name(&body.cipher)?,
+        nonce: fixed_bytes(&body.nonce, "nonce")?,
+        ciphertext: body.ciphertext,
+        tag: fixed_bytes(&body.tag, "tag")?,
+      }),
+      other => Err(CoreErrors::invalid_data(
+        match other.strip_prefix('V').and_then(|n| n.parse().ok()) {
+          Some(number) => ValidationKind::UnsupportedVersion(number),
+          None => ValidationKind::Malformed(format!("Unknown sealed data version {}", other)),
+        },
+      )),
+    }
+  }
+}
+
+impl<'de> Deserialize<'de> for SealedData {
+  fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+    SealedDataRepr::deserialize(deserializer)?
+      .into_sealed()
+      .map_err(serde::de::Error::custom)
+  }
+}
+
+/// `t` of a `t`-of-`n` setup: the largest number of shares that can NOT sign
+/// together. Signing takes `SignerCount::required_for(t)`, i.e. `t + 1`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct Threshold(u16);
+
+impl Threshold {
+  pub const fn new(t: u16) -> Self {
+    Threshold(t)
+  }
+
+  pub const fn get(self) -> u16 {
+    self.0
+  }
+}
+
+impl fmt::Display for Threshold {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}", self.0)
+  }
+}
+
+/// Number of parties taking part in one signing run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct SignerCount(u16);
+
+impl SignerCount {
+  pub const fn new(signers: u16) -> Self {
+    SignerCount(signers)
+  }
+
+  /// The number of signers a keystore with threshold `t` needs: `t + 1`.
+  pub const fn required_for(threshold: Threshold) -> Self {
+    SignerCount(threshold.0 + 1)
+  }
+
+  /// The threshold this many signers exactly satisfy, `None` for zero.
+  pub fn threshold(self) -> Option<Threshold> {
+    self.0.checked_sub(1).map(Threshold)
+  }
+
+  pub const fn get(self) -> u16 {
+    self.0
+  }
+}
+
+impl fmt::Display for SignerCount {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}", self.0)
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct KeystoreParameters {
-  pub threshold: u16,   //t
-  pub share_count: u16, //n
+  pub threshold: Threshold, //t
+  pub share_count: u16,     //n
+}
+
+impl KeystoreParameters {
+  /// Validated `t`-of-`n` parameters. The degenerate setups (a single party,
+  /// or any single share being enough to sign) would be plain single-key
+  /// ECDSA behind an MPC-looking API, so they are refused.
+  pub fn new(threshold: Threshold, share_count: u16) -> Result<Self, CoreErrors> {
+    let unsupported = |reason: &str| {
+      Err(CoreErrors::invalid_data(ValidationKind::UnsupportedSetup(
+        reason.to_string(),
+      )))
+    };
+    if share_count < 2 {
+      return unsupported("share_count must be at least 2");
+    }
+    if threshold.get() < 1 {
+      return unsupported("threshold must be at least 1");
+    }
+    if threshold.get() >= share_count {
+      return unsupported("threshold must be below share_count");
+    }
+    Ok(KeystoreParameters {
+      threshold,
+      share_count,
+    })
+  }
 }
 
 impl From<Parameters> for KeystoreParameters {
   fn from(param: Parameters) -> Self {
     return KeystoreParameters {
-      threshold: param.threshold,
+      threshold: Threshold::new(param.threshold),
       share_count: param.share_count,
     };
   }
 }
 
+/// Outcome of a share health check, as seen by the initiator.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct HealthReport {
+  /// Parties that answered with a valid proof for their share.
+  pub healthy: Vec<PartyId>,
+  /// Parties that answered, but with a proof that does not verify.
+  pub invalid: Vec<PartyId>,
+  /// Parties that did not answer before the timeout.
+  pub silent: Vec<PartyId>,
+}
+
+impl HealthReport {
+  pub fn all_healthy(&self) -> bool {
+    self.invalid.is_empty() && self.silent.is_empty()
+  }
+}
+
+// The raw fields expose curv types and stay public, deprecated, for one more
+// release; new code should go through the accessors below.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct Keystore {
+  #[deprecated(note = "use `Keystore::params`")]
   pub params: KeystoreParameters,
+  #[deprecated(note = "exposes curv types; use `Keystore::into_parts`")]
+  #[cfg_attr(feature = "schemars", schemars(schema_with = "schema::opaque"))]
   pub party_key: Keys,
+  #[deprecated(note = "exposes curv types")]
+  #[cfg_attr(feature = "schemars", schemars(schema_with = "schema::scalars"))]
   pub party_shares: Vec<FE>,
+  #[deprecated(note = "exposes curv types; use `Keystore::into_parts`")]
+  #[cfg_attr(feature = "schemars", schemars(schema_with = "schema::opaque"))]
   pub shared_keys: SharedKeys,
+  #[deprecated(note = "use `Keystore::party_index`")]
+  pub party_index: usize,
+  #[deprecated(note = "exposes curv types; use `Keystore::public` or `Keystore::into_parts`")]
+  #[cfg_attr(feature = "schemars", schemars(schema_with = "schema::opaque"))]
+  pub vss_scheme_vec: Vec<VerifiableSS>,
+  #[deprecated(note = "exposes curv types; use `Keystore::public` or `Keystore::into_parts`")]
+  #[cfg_attr(feature = "schemars", schemars(schema_with = "schema::opaque"))]
+  pub paillier_key_vec: Vec<EncryptionKey>,
+  #[deprecated(note = "use `Keystore::public_key`")]
+  #[cfg_attr(feature = "schemars", schemars(schema_with = "schema::point"))]
+  pub y_sum: GE,
+}
+
+impl Keystore {
+  pub fn public_key(&self) -> PublicKey {
+    PublicKey(self.y_sum)
+  }
+
+  pub fn party_index(&self) -> usize {
+    self.party_index
+  }
+
+  pub fn params(&self) -> &KeystoreParameters {
+    &self.params
+  }
+
+  pub fn threshold(&self) -> Threshold {
+    self.params.threshold
+  }
+
+  /// Signers a run with this keystore takes.
+  pub fn signer_count(&self) -> SignerCount {
+    SignerCount::required_for(self.params.threshold)
+  }
+
+  pub fn share_count(&self) -> u16 {
+    self.params.share_count
+  }
+
+  pub fn public(&self) -> PublicKeystore {
+    PublicKeystore {
+      params: self.params.clone(),
+      party_index: self.party_index,
+      vss_scheme_vec: self.vss_scheme_vec.clone(),
+      paillier_key_vec: self.paillier_key_vec.clone(),
+      y_sum: self.y_sum,
+    }
+  }
+}
+
+/// A keystore as the first release persisted it (format v1). Deserialize
+/// stored keystores into this and pass them to `Keystore::upgrade_format`.
+/// The struct is frozen: it keeps describing v1 when `Keystore` changes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OldKeystore {
+  params: OldKeystoreParameters,
+  party_key: Keys,
+  party_shares: Vec<FE>,
+  shared_keys: SharedKeys,
+  party_index: usize,
+  vss_scheme_vec: Vec<VerifiableSS>,
+  paillier_key_vec: Vec<EncryptionKey>,
+  y_sum: GE,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct OldKeystoreParameters {
+  threshold: u16,
+  share_count: u16,
+}
+
+/// A keystore as the raw gg_2018 values: the party's `Keys` and
+/// `SharedKeys`, every party's VSS scheme and Paillier key, and the group key.
+pub type KeystoreParts = (Keys, SharedKeys, Vec<VerifiableSS>, Vec<EncryptionKey>, GE);
+
+impl Keystore {
+  /// Splits the keystore into the values multi_party_ecdsa works with.
+  /// `Keys::party_index` is 1-based there, while `Keystore::party_index` is
+  /// 0-based; the two are always kept in step. `party_shares` is dropped.
+  pub fn into_parts(self) -> KeystoreParts {
+    (
+      self.party_key,
+      self.shared_keys,
+      self.vss_scheme_vec,
+      self.paillier_key_vec,
+      self.y_sum,
+    )
+  }
+
+  /// Reassembles a keystore from `into_parts`, or from values produced with
+  /// multi_party_ecdsa directly. The party is taken from the 1-based
+  /// `Keys::party_index` and the parameters from the VSS schemes, and
+  /// everything is cross-checked: the schemes' shapes, one Paillier key per
+  /// party including ours, our share against the commitments, and the group
+  /// key.
+  pub fn from_parts(parts: KeystoreParts) -> Result<Keystore, CoreErrors> {
+    let (party_key, shared_keys, vss_scheme_vec, paillier_key_vec, y_sum) = parts;
+    let inconsistent = |reason: &str| {
+      CoreErrors::invalid_data(ValidationKind::Malformed(format!(
+        "Inconsistent keystore parts ({})",
+        reason
+      )))
+    };
+
+    let first = vss_scheme_vec
+      .first()
+      .ok_or_else(|| inconsistent("no VSS schemes"))?;
+    let params = KeystoreParameters::new(
+      Threshold::new(first.parameters.threshold as u16),
+      first.parameters.share_count as u16,
+    )?;
+    let share_count = params.share_count as usize;
+    if vss_scheme_vec.len() != share_count {
+      return Err(CoreErrors::mismatch(
+        RequestField::ShareCount,
+        share_count,
+        vss_scheme_vec.len(),
+      ));
+    }
+    if paillier_key_vec.len() != share_count {
+      return Err(CoreErrors::mismatch(
+        RequestField::ShareCount,
+        share_count,
+        paillier_key_vec.len(),
+      ));
+    }
+    for vss in &vss_scheme_vec {
+      check_vss_shape(vss, params.threshold.get(), params.share_count, None)?;
+    }
+
+    // The only place the 1-based gg_2018 index is translated.
+    if party_key.party_index < 1 || party_key.party_index > share_count {
+      return Err(CoreErrors::mismatch(
+        RequestField::OwnIndex,
+        share_count + 1,
+        party_key.party_index,
+      ));
+    }
+    let party_index = party_key.party_index - 1;
+
+    if vss_scheme_vec[party_index].commitments[0] != party_key.y_i {
+      return Err(inconsistent("own VSS scheme does not commit to y_i"));
+    }
+    if paillier_key_vec[party_index].n != party_key.ek.n {
+      return Err(inconsistent("own Paillier key is not in the key list"));
+    }
+    let g: GE = ECPoint::generator();
+    if Keys::get_commitments_to_xi(&vss_scheme_vec)[party_index] != g * shared_keys.x_i {
+      return Err(inconsistent("share does not match the VSS commitments"));
+    }
+    let (head, tail) = vss_scheme_vec.split_at(1);
+    let y = tail
+      .iter()
+      .fold(head[0].commitments[0], |acc, vss| acc + vss.commitments[0]);
+    if y != y_sum || shared_keys.y != y_sum {
+      return Err(inconsistent("group key does not match the commitments"));
+    }
+
+    Ok(Keystore {
+      params,
+      party_key,
+      party_shares: vec![],
+      shared_keys,
+      party_index,
+      vss_scheme_vec,
+      paillier_key_vec,
+      y_sum,
+    })
+  }
+
+  /// Migrates a keystore read in the previous format. It is rebuilt through
+  /// `from_parts`, so a keystore that was damaged or hand-edited while at
+  /// rest is refused rather than carried forward.
+  pub fn upgrade_format(old: OldKeystore) -> Result<Keystore, CoreErrors> {
+    let old_params = KeystoreParameters {
+      threshold: Threshold::new(old.params.threshold),
+      share_count: old.params.share_count,
+    };
+    let mut keystore = Keystore::from_parts((
+      old.party_key,
+      old.shared_keys,
+      old.vss_scheme_vec,
+      old.paillier_key_vec,
+      old.y_sum,
+    ))?;
+    if keystore.params != old_params {
+      return Err(CoreErrors::invalid_data(ValidationKind::Malformed(
+        "Keystore parameters do not match its VSS schemes".to_string(),
+      )));
+    }
+    if keystore.party_index != old.party_index {
+      return Err(CoreErrors::mismatch(
+        RequestField::OwnIndex,
+        keystore.party_index,
+        old.party_index,
+      ));
+    }
+    keystore.party_shares = old.party_shares;
+    Ok(keystore)
+  }
+}
+
+/// The parts of a keystore that are not secret: enough to check a share
+/// against the group's commitments, and to rebuild a keystore around it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct PublicKeystore {
+  pub params: KeystoreParameters,
   pub party_index: usize,
+  #[cfg_attr(feature = "schemars", schemars(schema_with = "schema::opaque"))]
   pub vss_scheme_vec: Vec<VerifiableSS>,
+  #[cfg_attr(feature = "schemars", schemars(schema_with = "schema::opaque"))]
   pub paillier_key_vec: Vec<EncryptionKey>,
+  #[cfg_attr(feature = "schemars", schemars(schema_with = "schema::point"))]
   pub y_sum: GE,
 }
+
+impl PublicKeystore {
+  /// Hash of the group parameters and commitments. Equal for every party of
+  /// one keygen, different across keygens.
+  pub fn fingerprint(&self) -> BigInt {
+    let mut items = vec![
+      self.y_sum.bytes_compressed_to_big_int(),
+      BigInt::from(self.params.threshold.get() as u64),
+      BigInt::from(self.params.share_count as u64),
+    ];
+    items.extend(
+      self
+        .vss_scheme_vec
+        .iter()
+        .flat_map(|vss| vss.commitments.iter())
+        .map(|c| c.bytes_compressed_to_big_int()),
+    );
+    HSha256::create_hash(&items.iter().collect::<Vec<&BigInt>>())
+  }
+}
+
+/// Public record of one key generation, for auditors. It is built from the
+/// transcript alone, so every party of the run produces the same report.
+/// A report only exists for a run that passed every check in `checks`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct CeremonyReport {
+  /// SHA-256 over `commitment_hashes`; names the ceremony.
+  pub ceremony_id: Digest32,
+  pub params: KeystoreParameters,
+  pub parties: Vec<PartyId>,
+  pub public_key: PublicKey,
+  /// `PublicKeystore::fingerprint` as hex.
+  pub fingerprint: String,
+  /// SHA-256 of each party's round-1 commitment, in party order.
+  pub commitment_hashes: Vec<Digest32>,
+  pub protocol_version: ProtocolVersion,
+  pub checks: Vec<String>,
+}
+
+impl CeremonyReport {
+  /// Checks that `keystore` came out of the ceremony this report describes.
+  pub fn verify_matches(&self, keystore: &Keystore) -> Result<(), CoreErrors> {
+    let mismatch = |what: &str| {
+      CoreErrors::invalid_data(ValidationKind::Malformed(format!(
+        "Ceremony report does not match the keystore ({})",
+        what
+      )))
+    };
+    if self.params != keystore.params || self.parties.len() != keystore.share_count() as usize {
+      return Err(mismatch("parameters"));
+    }
+    if self.public_key != keystore.public_key() {
+      return Err(mismatch("public key"));
+    }
+    if self.fingerprint != bigint_to_hex(&keystore.public().fingerprint()) {
+      return Err(mismatch("fingerprint"));
+    }
+    Ok(())
+  }
+}
+
+impl fmt::Display for Keystore {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(
+      f,
+      "Keystore(party={}, threshold={}, n={}, pubkey=",
+      self.party_index, self.params.threshold, self.params.share_count
+    )?;
+    self
+      .public_key()
+      .to_bytes()
+      .iter()
+      .try_for_each(|b| write!(f, "{:02x}", b))?;
+    write!(f, ")")
+  }
+}
+
+impl AsRef<GE> for Keystore {
+  fn as_ref(&self) -> &GE {
+    &self.y_sum
+  }
+}
+
+/// Message digest as an arbitrary big-endian integer. Signing takes a
+/// `Digest32`; this type remains for the deprecated `*_bigint` entry points.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct Digest(
+  #[cfg_attr(feature = "schemars", schemars(schema_with = "schema::bigint"))] BigInt,
+);
+
+impl Digest {
+  pub fn from_bytes(bytes: &[u8]) -> Self {
+    Digest(BigInt::from(bytes))
+  }
+
+  pub fn to_bytes(&self) -> Vec<u8> {
+    BigInt::to_vec(&self.0)
+  }
+
+  pub(crate) fn as_bigint(&self) -> &BigInt {
+    &self.0
+  }
+}
+
+/// 32-byte message digest to sign, big-endian as the hash produced it.
+/// Serialized as a 64-character lowercase hex string.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct Digest32(
+  #[cfg_attr(feature = "schemars", schemars(schema_with = "schema::digest32"))] [u8; 32],
+);
+
+impl Digest32 {
+  /// Rejects the all-zero digest, as `from_bigint` rejects zero.
+  pub fn from_bytes(bytes: &[u8; 32]) -> Result<Self, CoreErrors> {
+    if bytes.iter().all(|b| *b == 0) {
+      return Err(CoreErrors::invalid_data(ValidationKind::Malformed(
+        "Invalid digest (all zero)".to_string(),
+      )));
+    }
+    Ok(Digest32(*bytes))
+  }
+
+  // Hash outputs skip the zero check: SHA-256 does not produce it in
+  // practice and the hashing helpers stay infallible.
+  pub(crate) fn from_hash_output(bytes: [u8; 32]) -> Self {
+    Digest32(bytes)
+  }
+
+  /// Exactly 64 hex digits, either case, no sign or prefix.
+  pub fn from_hex(hex: &str) -> Result<Self, CoreErrors> {
+    let malformed = || {
+      CoreErrors::invalid_data(ValidationKind::Malformed(format!(
+        "Invalid digest hex ({})",
+        hex
+      )))
+    };
+    if hex.len() != 64 || !hex.bytes().all(|c| c.is_ascii_hexdigit()) {
+      return Err(malformed());
+    }
+    let mut out = [0u8; 32];
+    for (i, byte) in out.iter_mut().enumerate() {
+      *byte = u8::from_str_radix(&hex[2 * i..2 * i + 2], 16).map_err(|_| malformed())?;
+    }
+    Digest32::from_bytes(&out)
+  }
+
+  /// Accepts integers in `1..2^256`, left padding them to 32 bytes.
+  pub fn from_bigint(value: &BigInt) -> Result<Self, CoreErrors> {
+    let invalid = |reason: &str| {
+      CoreErrors::invalid_data(ValidationKind::Malformed(format!(
+        "Invalid digest ({})",
+        reason
+      )))
+    };
+    if *value <= BigInt::from(0) {
+      return Err(invalid("not positive"));
+    }
+    let bytes = BigInt::to_vec(value);
+    if bytes.len() > 32 {
+      return Err(invalid("longer than 32 bytes"));
+    }
+    let mut out = [0u8; 32];
+    out[32 - bytes.len()..].copy_from_slice(&bytes);
+    Digest32::from_bytes(&out)
+  }
+
+  pub fn as_bytes(&self) -> &[u8; 32] {
+    &self.0
+  }
+
+  // The only conversion from a digest to the integer the signing protocol
+  // works on; everything that signs or verifies goes through here.
+  pub(crate) fn to_bigint(&self) -> BigInt {
+    BigInt::from(&self.0[..])
+  }
+}
+
+impl fmt::Display for Digest32 {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    self.0.iter().try_for_each(|b| write!(f, "{:02x}", b))
+  }
+}
+
+impl Serialize for Digest32 {
+  fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&self.to_string())
+  }
+}
+
+impl<'de> Deserialize<'de> for Digest32 {
+  fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+    let hex = String::deserialize(deserializer)?;
+    Digest32::from_hex(&hex).map_err(serde::de::Error::custom)
+  }
+}
+
+/// Aggregated secp256k1 public key of a keystore.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct PublicKey(#[cfg_attr(feature = "schemars", schemars(schema_with = "schema::point"))] GE);
+
+impl PublicKey {
+  pub fn from_bytes(bytes: &[u8]) -> Result<Self, CoreErrors> {
+    GE::from_bytes(bytes).map(PublicKey).map_err(|e| {
+      CoreErrors::invalid_data(ValidationKind::Malformed(format!(
+        "Invalid public key bytes ({:?})",
+        e
+      )))
+    })
+  }
+
+  /// SEC1 compressed encoding (33 bytes).
+  pub fn to_bytes(&self) -> Vec<u8> {
+    self.0.get_element().serialize().to_vec()
+  }
+
+  /// SEC1 uncompressed encoding (65 bytes).
+  pub fn to_uncompressed_bytes(&self) -> Vec<u8> {
+    self.0.get_element().serialize_uncompressed().to_vec()
+  }
+
+  pub(crate) fn new(point: GE) -> Self {
+    PublicKey(point)
+  }
+
+  pub(crate) fn as_point(&self) -> &GE {
+    &self.0
+  }
+}
+
+/// ECDSA signature produced by the signing scenario.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(transparent)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct Signature(
+  #[cfg_attr(feature = "schemars", schemars(schema_with = "schema::signature"))] party_i::Signature,
+);
+
+impl Signature {
+  /// Big-endian `r`, left padded to 32 bytes.
+  pub fn r(&self) -> [u8; 32] {
+    scalar_to_bytes(&self.0.r)
+  }
+
+  /// Big-endian `s`, left padded to 32 bytes.
+  pub fn s(&self) -> [u8; 32] {
+    scalar_to_bytes(&self.0.s)
+  }
+
+  /// Compact `r || s` encoding.
+  pub fn to_bytes(&self) -> [u8; 64] {
+    let mut out = [0u8; 64];
+    out[..32].copy_from_slice(&self.r());
+    out[32..].copy_from_slice(&self.s());
+    out
+  }
+
+  pub fn verify(&self, public_key: &PublicKey, digest: &Digest32) -> bool {
+    party_i::verify(&self.0, public_key.as_point(), &digest.to_bigint()).is_ok()
+  }
+
+  pub(crate) fn new(sig: party_i::Signature) -> Self {
+    Signature(sig)
+  }
+
+  pub(crate) fn as_inner(&self) -> &party_i::Signature {
+    &self.0
+  }
+}
+
+/// Schnorr signature `(R, s)` with `s * G = R + e * Y`, where
+/// `e = SHA-256(R || Y || digest)` over compressed points. Produced by the
+/// threshold Schnorr scenario from the same keystores as ECDSA.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct SchnorrSignature {
+  #[cfg_attr(feature = "schemars", schemars(schema_with = "schema::point"))]
+  r: GE,
+  #[cfg_attr(feature = "schemars", schemars(schema_with = "schema::scalar"))]
+  s: FE,
+}
+
+impl SchnorrSignature {
+  /// SEC1 compressed `R` followed by big-endian `s` (65 bytes).
+  pub fn to_bytes(&self) -> Vec<u8> {
+    let mut out = self.r.get_element().serialize().to_vec();
+    out.extend_from_slice(&scalar_to_bytes(&self.s));
+    out
+  }
+
+  pub fn verify(&self, public_key: &PublicKey, digest: &Digest32) -> bool {
+    let g: GE = ECPoint::generator();
+    let e = SchnorrSignature::challenge(&self.r, public_key.as_point(), digest);
+    g * self.s == self.r + *public_key.as_point() * e
+  }
+
+  pub(crate) fn new(r: GE, s: FE) -> Self {
+    SchnorrSignature { r, s }
+  }
+
+  pub(crate) fn challenge(r: &GE, y: &GE, digest: &Digest32) -> FE {
+    let e = HSha256::create_hash(&[
+      &r.bytes_compressed_to_big_int(),
+      &y.bytes_compressed_to_big_int(),
+      &digest.to_bigint(),
+    ]);
+    ECScalar::from(&e)
+  }
+}
+
+/// Big-endian, left-padded to 32 bytes.
+pub(crate) fn scalar_to_bytes(scalar: &FE) -> [u8; 32] {
+  let bytes = BigInt::to_vec(&scalar.to_big_int());
+  let mut out = [0u8; 32];
+  out[32 - bytes.len()..].copy_from_slice(&bytes);
+  out
+}
+
+#[cfg(feature = "interop")]
+mod interop {
+  use super::{Digest, PublicKey, Signature};
+  use curv::{BigInt, GE};
+  use multi_party_ecdsa::protocols::multi_party_ecdsa::gg_2018::party_i;
+
+  impl From<BigInt> for Digest {
+    fn from(value: BigInt) -> Self {
+      Digest(value)
+    }
+  }
+
+  impl From<Digest> for BigInt {
+    fn from(value: Digest) -> Self {
+      value.0
+    }
+  }
+
+  impl From<GE> for PublicKey {
+    fn from(value: GE) -> Self {
+      PublicKey(value)
+    }
+  }
+
+  impl From<PublicKey> for GE {
+    fn from(value: PublicKey) -> Self {
+      value.0
+    }
+  }
+
+  impl From<party_i::Signature> for Signature {
+    fn from(value: party_i::Signature) -> Self {
+      Signature(value)
+    }
+  }
+
+  impl From<Signature> for party_i::Signature {
+    fn from(value: Signature) -> Self {
+      value.0
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::scenarios::run_local_sign;
+
+  // A 2-of-2 (threshold 1) group in the v1 JSON layout, hex encoded; see
+  // tests/fixtures/keystore_v1/README.md.
+  const KEYSTORE_V1_HEX: [&str; 2] = [
+    include_str!("../../tests/fixtures/keystore_v1/party_0.hex"),
+    include_str!("../../tests/fixtures/keystore_v1/party_1.hex"),
+  ];
+
+  fn decode_hex(hex: &str) -> Vec<u8> {
+    let digits: Vec<u8> = hex.bytes().filter(|b| !b.is_ascii_whitespace()).collect();
+    digits
+      .chunks(2)
+      .map(|pair| u8::from_str_radix(std::str::from_utf8(pair).unwrap(), 16).unwrap())
+      .collect()
+  }
+
+  #[test]
+  fn test_keygen_format_migration() {
+    let keystores: Vec<Keystore> = KEYSTORE_V1_HEX
+      .iter()
+      .map(|hex| {
+        let old: OldKeystore = serde_json::from_slice(&decode_hex(hex)).unwrap();
+        Keystore::upgrade_format(old).unwrap()
+      })
+      .collect();
+    for (i, keystore) in keystores.iter().enumerate() {
+      assert_eq!(keystore.party_index(), i);
+      assert_eq!(keystore.threshold(), Threshold::new(1));
+      assert_eq!(keystore.share_count(), 2);
+      assert_eq!(keystore.public_key(), keystores[0].public_key());
+    }
+
+    let digest = Digest32::from_bytes(&[0x5a; 32]).unwrap();
+    let signatures = run_local_sign(&keystores, &[0, 1], &digest).unwrap();
+    for signature in &signatures {
+      assert!(signature.verify(&keystores[0].public_key(), &digest));
+    }
+  }
+
+  #[test]
+  fn upgrade_format_rejects_a_tampered_keystore() {
+    let mut old: OldKeystore = serde_json::from_slice(&decode_hex(KEYSTORE_V1_HEX[0])).unwrap();
+    old.party_index = 1;
+    assert!(Keystore::upgrade_format(old).is_err());
+
+    let mut old: OldKeystore = serde_json::from_slice(&decode_hex(KEYSTORE_V1_HEX[0])).unwrap();
+    old.params.threshold = 0;
+    assert!(Keystore::upgrade_format(old).is_err());
+  }
+
+  #[test]
+  fn digest32_hex_roundtrip() {
+    let digest = Digest32::from_bytes(&[0xab; 32]).unwrap();
+    let hex = digest.to_string();
+    assert_eq!(hex, "ab".repeat(32));
+    assert_eq!(Digest32::from_hex(&hex).unwrap(), digest);
+    assert_eq!(Digest32::from_hex(&hex.to_uppercase()).unwrap(), digest);
+  }
+
+  #[test]
+  fn digest32_rejects_signs_and_non_hex() {
+    let plus = format!("+f{}", "00".repeat(31));
+    assert!(Digest32::from_hex(&plus).is_err());
+    assert!(Digest32::from_hex(&format!("0x{}", "ab".repeat(31))).is_err());
+    assert!(Digest32::from_hex(&"zz".repeat(32)).is_err());
+    assert!(Digest32::from_hex(&"ab".repeat(31)).is_err());
+  }
+
+  #[test]
+  fn digest32_constructors_agree_on_zero() {
+    assert!(Digest32::from_bytes(&[0; 32]).is_err());
+    assert!(Digest32::from_hex(&"00".repeat(32)).is_err());
+    assert!(Digest32::from_bigint(&BigInt::from(0)).is_err());
+  }
+
+  #[test]
+  fn digest32_from_bigint_pads_and_bounds() {
+    let mut expected = [0u8; 32];
+    expected[31] = 1;
+    assert_eq!(
+      Digest32::from_bigint(&BigInt::from(1)).unwrap(),
+      Digest32::from_bytes(&expected).unwrap()
+    );
+    let too_long = BigInt::from(&[1u8; 33][..]);
+    assert!(Digest32::from_bigint(&too_long).is_err());
+  }
+}
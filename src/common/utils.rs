@@ -1,33 +1,390 @@
-use std::iter::repeat;
+use std::panic::{self, AssertUnwindSafe};
 
-use crypto::{
-  aead::{AeadDecryptor, AeadEncryptor},
-  aes::KeySize::KeySize256,
-  aes_gcm::AesGcm,
+use aes_gcm::{
+  aead::{AeadInPlace, KeyInit},
+  Aes256Gcm, Nonce, Tag,
 };
+use crypto::{hkdf, sha2::Sha256};
 
-use crate::common::types::AEAD;
-
-#[allow(dead_code)]
-pub fn aes_encrypt(key: &[u8], plaintext: &[u8]) -> AEAD {
-  let nonce: Vec<u8> = repeat(3).take(12).collect();
-  let aad: [u8; 0] = [];
-  let mut gcm = AesGcm::new(KeySize256, key, &nonce[..], &aad);
-  let mut out: Vec<u8> = repeat(0).take(plaintext.len()).collect();
-  let mut out_tag: Vec<u8> = repeat(0).take(16).collect();
-  gcm.encrypt(&plaintext[..], &mut out[..], &mut out_tag[..]);
-  AEAD {
-    ciphertext: out.to_vec(),
-    tag: out_tag.to_vec(),
-  }
-}
-
-#[allow(dead_code)]
-pub fn aes_decrypt(key: &[u8], aead_pack: AEAD) -> Vec<u8> {
-  let mut out: Vec<u8> = repeat(0).take(aead_pack.ciphertext.len()).collect();
-  let nonce: Vec<u8> = repeat(3).take(12).collect();
-  let aad: [u8; 0] = [];
-  let mut gcm = AesGcm::new(KeySize256, key, &nonce[..], &aad);
-  gcm.decrypt(&aead_pack.ciphertext[..], &mut out, &aead_pack.tag[..]);
+use crate::common::types::{CipherId, SealedData, Signature};
+use crate::errors::{CoreErrors, CryptoKind, ValidationKind};
+#[cfg(feature = "pem")]
+use base64::{engine::general_purpose::STANDARD, Engine};
+use curv::arithmetic::traits::{Converter, Samplable};
+use curv::cryptographic_primitives::secret_sharing::feldman_vss::VerifiableSS;
+#[cfg(feature = "pem")]
+use curv::elliptic::curves::traits::ECPoint;
+use curv::elliptic::curves::traits::ECScalar;
+use curv::{BigInt, FE, GE};
+use subtle::ConstantTimeEq;
+
+/// `len` random bytes from curv's RNG.
+pub(crate) fn random_bytes(len: usize) -> Vec<u8> {
+  let bytes = BigInt::to_vec(&BigInt::sample(len * 8));
+  let mut out = vec![0u8; len - bytes.len()];
+  out.extend_from_slice(&bytes);
   out
 }
+
+/// Seals `plaintext` under `key` with AES-256-GCM and a random nonce. Fails
+/// if `key` is not 32 bytes long.
+pub fn aes_encrypt(key: &[u8], plaintext: &[u8]) -> Result<SealedData, CoreErrors> {
+  let mut nonce = [0u8; 12];
+  nonce.copy_from_slice(&random_bytes(12));
+  aes_encrypt_with_nonce(key, nonce, plaintext)
+}
+
+/// Like `aes_encrypt`, for the legacy formats that fix the nonce. A nonce
+/// must never be used twice under one key.
+pub(crate) fn aes_encrypt_with_nonce(
+  key: &[u8],
+  nonce: [u8; 12],
+  plaintext: &[u8],
+) -> Result<SealedData, CoreErrors> {
+  let mut ciphertext = plaintext.to_vec();
+  let tag = aes256_gcm(key)?
+    .encrypt_in_place_detached(Nonce::from_slice(&nonce), &[], &mut ciphertext)
+    .map_err(|_| {
+      CoreErrors::invalid_data(ValidationKind::Malformed(
+        "AES-GCM plaintext too long".to_string(),
+      ))
+    })?;
+  let mut tag_bytes = [0u8; 16];
+  tag_bytes.copy_from_slice(&tag);
+  Ok(SealedData::V1 {
+    cipher: CipherId::Aes256Gcm,
+    nonce,
+    ciphertext,
+    tag: tag_bytes,
+  })
+}
+
+fn aes256_gcm(key: &[u8]) -> Result<Aes256Gcm, CoreErrors> {
+  Aes256Gcm::new_from_slice(key).map_err(|_| {
+    CoreErrors::invalid_data(ValidationKind::Malformed(format!(
+      "AES-256-GCM key must be 32 bytes, got {}",
+      key.len()
+    )))
+  })
+}
+
+/// Fails if the tag doesn't authenticate the ciphertext under `key`; no
+/// plaintext is released in that case.
+pub fn aes_decrypt(key: &[u8], sealed: SealedData) -> Result<Vec<u8>, CoreErrors> {
+  let SealedData::V1 {
+    cipher: CipherId::Aes256Gcm,
+    nonce,
+    ciphertext,
+    tag,
+  } = sealed;
+  let mut buf = ciphertext;
+  aes256_gcm(key)?
+    .decrypt_in_place_detached(
+      Nonce::from_slice(&nonce),
+      &[],
+      &mut buf,
+      Tag::from_slice(&tag),
+    )
+    .map_err(|_| CoreErrors::ExecutionIssue {
+      kind: CryptoKind::Decryption("AES-GCM tag mismatch".to_string()),
+      round: None,
+      party: None,
+    })?;
+  Ok(buf)
+}
+
+/// Equality of `a` and `b` in time that depends only on their lengths, for
+/// MACs, tags and checksums.
+pub fn constant_time_bytes_eq(a: &[u8], b: &[u8]) -> bool {
+  a.len() == b.len() && bool::from(a.ct_eq(b))
+}
+
+/// HKDF-SHA256 (RFC 5869) extract-then-expand with an empty salt, producing
+/// `len` bytes of key material bound to `info`.
+pub fn hkdf_expand(ikm: &[u8], info: &[u8], len: usize) -> Vec<u8> {
+  let mut prk = [0u8; 32];
+  hkdf::hkdf_extract(Sha256::new(), &[], ikm, &mut prk);
+  let mut okm = vec![0u8; len];
+  hkdf::hkdf_expand(Sha256::new(), &prk, info, &mut okm);
+  okm
+}
+
+/// Reconstructs the shared secret from `(index, share)` pairs by Lagrange
+/// interpolation at zero. Indices are 0-based party indices, the same
+/// convention `VerifiableSS::reconstruct` uses (share `i` is the polynomial
+/// evaluated at `i + 1`).
+pub fn shamir_combine(shares: &[(usize, FE)]) -> Result<FE, CoreErrors> {
+  if shares.len() < 2 {
+    return Err(CoreErrors::invalid_data(ValidationKind::Malformed(
+      format!("At least 2 shares are required, got {}", shares.len()),
+    )));
+  }
+
+  let mut indices: Vec<usize> = shares.iter().map(|(i, _)| *i).collect();
+  indices.sort();
+  indices.dedup();
+  if indices.len() != shares.len() {
+    return Err(CoreErrors::invalid_data(ValidationKind::Malformed(
+      "Duplicate share indices provided".to_string(),
+    )));
+  }
+
+  let points = shares
+    .iter()
+    .map(|(i, _)| ECScalar::from(&BigInt::from(*i as u64 + 1)))
+    .collect::<Vec<FE>>();
+  let values = shares.iter().map(|(_, s)| *s).collect::<Vec<FE>>();
+
+  Ok(VerifiableSS::lagrange_interpolation_at_zero(
+    &points, &values,
+  ))
+}
+
+/// Checks that `vss` is a degree-`threshold` scheme over `share_count`
+/// shares before anything indexes into it, and, with `y_i`, that it shares
+/// the dealer's announced key. Like `verify_vss_share`, the error carries no
+/// round or party; callers attach the dealer.
+pub fn check_vss_shape(
+  vss: &VerifiableSS,
+  threshold: u16,
+  share_count: u16,
+  y_i: Option<&GE>,
+) -> Result<(), CoreErrors> {
+  let invalid = |reason: String| CoreErrors::ExecutionIssue {
+    kind: CryptoKind::InvalidVss(reason),
+    round: None,
+    party: None,
+  };
+  if vss.parameters.threshold != threshold as usize
+    || vss.parameters.share_count != share_count as usize
+  {
+    return Err(invalid(format!(
+      "{}-of-{} parameters, expected {}-of-{}",
+      vss.parameters.threshold, vss.parameters.share_count, threshold, share_count
+    )));
+  }
+  if vss.commitments.len() != threshold as usize + 1 {
+    return Err(invalid(format!(
+      "{} commitments for threshold {}",
+      vss.commitments.len(),
+      threshold
+    )));
+  }
+  if let Some(y_i) = y_i {
+    if vss.commitments[0] != *y_i {
+      return Err(invalid("secret commitment differs from y_i".to_string()));
+    }
+  }
+  Ok(())
+}
+
+/// Checks `share` against the Feldman commitments of `vss` at the 1-based
+/// `party_index`. Some curv versions panic on malformed schemes instead of
+/// returning an error; the panic is caught and reported like a failed check.
+/// The error carries no round or party; callers attach them.
+pub fn verify_vss_share(
+  vss: &VerifiableSS,
+  share: &FE,
+  party_index: usize,
+) -> Result<(), CoreErrors> {
+  let invalid = |reason: String| CoreErrors::ExecutionIssue {
+    kind: CryptoKind::InvalidVss(reason),
+    round: None,
+    party: None,
+  };
+  match panic::catch_unwind(AssertUnwindSafe(|| vss.validate_share(share, party_index))) {
+    Ok(Ok(())) => Ok(()),
+    Ok(Err(e)) => Err(invalid(format!("{:?}", e))),
+    Err(_) => Err(invalid("share validation panicked".to_string())),
+  }
+}
+
+/// Whether `s` lies in the lower half of the curve order, as required by
+/// Bitcoin-style malleability rules.
+pub fn is_low_s(sig: &Signature) -> bool {
+  sig.as_inner().s.to_big_int() <= FE::q() / BigInt::from(2)
+}
+
+/// Replaces a high `s` with `q - s`; the result verifies against the same
+/// key and digest.
+pub fn normalize_signature(sig: Signature) -> Signature {
+  if is_low_s(&sig) {
+    return sig;
+  }
+  let mut inner = sig.as_inner().clone();
+  inner.s = ECScalar::from(&(FE::q() - inner.s.to_big_int()));
+  Signature::new(inner)
+}
+
+/// Lowercase big-endian hex of `n` without a `0x` prefix, padded to an even
+/// length; zero is `"00"`. Only the magnitude is written.
+pub fn bigint_to_hex(n: &BigInt) -> String {
+  let bytes = BigInt::to_vec(n);
+  if bytes.is_empty() {
+    return "00".to_string();
+  }
+  bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Parses hex as written by `bigint_to_hex`. Either case and odd lengths are
+/// accepted; an empty string, a `0x` prefix or any other non-hex character is
+/// rejected.
+pub fn bigint_from_hex(s: &str) -> Result<BigInt, CoreErrors> {
+  if s.is_empty() || !s.bytes().all(|b| b.is_ascii_hexdigit()) {
+    return Err(CoreErrors::invalid_data(ValidationKind::Malformed(
+      format!("Invalid hex integer ({})", s),
+    )));
+  }
+  Ok(BigInt::from_hex(s))
+}
+
+// DER SubjectPublicKeyInfo up to the key bits: id-ecPublicKey with the
+// secp256k1 curve OID, followed by a 66-byte BIT STRING holding the
+// uncompressed point.
+#[cfg(feature = "pem")]
+const SPKI_SECP256K1_PREFIX: [u8; 23] = [
+  0x30, 0x56, 0x30, 0x10, 0x06, 0x07, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01, 0x06, 0x05, 0x2b,
+  0x81, 0x04, 0x00, 0x0a, 0x03, 0x42, 0x00,
+];
+#[cfg(feature = "pem")]
+const PEM_BEGIN: &str = "-----BEGIN PUBLIC KEY-----";
+#[cfg(feature = "pem")]
+const PEM_END: &str = "-----END PUBLIC KEY-----";
+
+/// PEM `PUBLIC KEY` (X.509 SubjectPublicKeyInfo) of `pk`, with the point
+/// uncompressed, as OpenSSL writes it.
+#[cfg(feature = "pem")]
+pub fn public_key_to_pem(pk: &GE) -> String {
+  let mut der = SPKI_SECP256K1_PREFIX.to_vec();
+  der.extend_from_slice(&pk.get_element().serialize_uncompressed());
+  let body = STANDARD.encode(&der);
+
+  let mut pem = String::from(PEM_BEGIN);
+  pem.push('\n');
+  for line in body.as_bytes().chunks(64) {
+    pem.push_str(std::str::from_utf8(line).expect("base64 is ASCII"));
+    pem.push('\n');
+  }
+  pem.push_str(PEM_END);
+  pem.push('\n');
+  pem
+}
+
+/// Inverse of `public_key_to_pem`. Accepts only secp256k1 keys with the
+/// point uncompressed.
+#[cfg(feature = "pem")]
+pub fn public_key_from_pem(pem: &str) -> Result<GE, CoreErrors> {
+  let invalid = |reason: &str| {
+    CoreErrors::invalid_data(ValidationKind::Malformed(format!(
+      "Invalid PEM public key ({})",
+      reason
+    )))
+  };
+
+  let body = pem
+    .trim()
+    .strip_prefix(PEM_BEGIN)
+    .and_then(|rest| rest.strip_suffix(PEM_END))
+    .ok_or_else(|| invalid("missing PUBLIC KEY armor"))?;
+  let body: String = body.split_whitespace().collect();
+  let der = STANDARD.decode(body).map_err(|e| invalid(&e.to_string()))?;
+
+  if der.len() != SPKI_SECP256K1_PREFIX.len() + 65
+    || der[..SPKI_SECP256K1_PREFIX.len()] != SPKI_SECP256K1_PREFIX[..]
+  {
+    return Err(invalid(
+      "not an uncompressed secp256k1 SubjectPublicKeyInfo",
+    ));
+  }
+  GE::from_bytes(&der[SPKI_SECP256K1_PREFIX.len()..])
+    .map_err(|e| invalid(&format!("not on the curve ({:?})", e)))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn hex_bytes(hex: &str) -> Vec<u8> {
+    (0..hex.len())
+      .step_by(2)
+      .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+      .collect()
+  }
+
+  // AES-256 test case 14 of the GCM specification; data sealed before the
+  // move to the aes-gcm crate must keep opening.
+  #[test]
+  fn aes_gcm_known_answer() {
+    let key = [0u8; 32];
+    let sealed = aes_encrypt_with_nonce(&key, [0u8; 12], &[0u8; 16]).unwrap();
+    let SealedData::V1 {
+      ciphertext, tag, ..
+    } = sealed.clone();
+    assert_eq!(ciphertext, hex_bytes("cea7403d4d606b6e074ec5d3baf39d18"));
+    assert_eq!(tag.to_vec(), hex_bytes("d0d1c8a799996bf0265b98b5d48ab919"));
+    assert_eq!(aes_decrypt(&key, sealed).unwrap(), vec![0u8; 16]);
+  }
+
+  #[test]
+  fn aes_decrypt_rejects_tampering() {
+    let key = [7u8; 32];
+    let sealed = aes_encrypt(&key, b"share for party 2").unwrap();
+    assert_eq!(
+      aes_decrypt(&key, sealed.clone()).unwrap(),
+      b"share for party 2"
+    );
+
+    let SealedData::V1 {
+      cipher,
+      nonce,
+      ciphertext,
+      tag,
+    } = sealed.clone();
+    let mut bad_tag = tag;
+    bad_tag[0] ^= 1;
+    let forged = SealedData::V1 {
+      cipher,
+      nonce,
+      ciphertext: ciphertext.clone(),
+      tag: bad_tag,
+    };
+    assert!(aes_decrypt(&key, forged).is_err());
+
+    let mut bad_ciphertext = ciphertext;
+    bad_ciphertext[0] ^= 1;
+    let forged = SealedData::V1 {
+      cipher,
+      nonce,
+      ciphertext: bad_ciphertext,
+      tag,
+    };
+    assert!(matches!(
+      aes_decrypt(&key, forged),
+      Err(CoreErrors::ExecutionIssue {
+        kind: CryptoKind::Decryption(_),
+        ..
+      })
+    ));
+
+    assert!(aes_decrypt(&[8u8; 32], sealed.clone()).is_err());
+    assert!(aes_decrypt(&[7u8; 16], sealed).is_err());
+  }
+
+  #[test]
+  fn aes_encrypt_rejects_a_short_key() {
+    assert!(matches!(
+      aes_encrypt(&[7u8; 16], b"share"),
+      Err(CoreErrors::InvalidData {
+        kind: ValidationKind::Malformed(_),
+        ..
+      })
+    ));
+  }
+
+  #[test]
+  fn constant_time_bytes_eq_compares_contents_and_length() {
+    assert!(constant_time_bytes_eq(b"tag", b"tag"));
+    assert!(!constant_time_bytes_eq(b"tag", b"tah"));
+    assert!(!constant_time_bytes_eq(b"tag", b"tags"));
+  }
+}
@@ -1,13 +1,337 @@
-use derive_more::Display;
+use crate::common::types::{PartyId, RoundId};
+use serde::{Deserialize, Serialize};
+use std::error::Error as StdError;
+use thiserror::Error;
 
-#[derive(Display)]
+// Display strings mirror the pre-structured `CoreErrors(String)` output so
+// log-based alerting keeps matching.
+#[derive(Debug, Error)]
+#[non_exhaustive]
 pub enum CoreErrors {
-  #[display(fmt = "Invalid data exception ({})", _0)]
-  InvalidData(String),
-  #[display(fmt = "Transport issue ({})", _0)]
-  TransportIssue(String),
-  #[display(fmt = "Timeout ({})", _0)]
-  Timeout(String),  
-  #[display(fmt = "Execution issue ({})", _0)]
-  ExecutionIssue(String)
-}
\ No newline at end of file
+  #[error("Invalid data exception ({kind})")]
+  InvalidData {
+    kind: ValidationKind,
+    round: Option<RoundId>,
+    party: Option<PartyId>,
+  },
+  #[error("Transport issue ({kind})")]
+  TransportIssue {
+    kind: TransportKind,
+    round: Option<RoundId>,
+    party: Option<PartyId>,
+    #[source]
+    source: Option<Box<dyn StdError + Send + Sync>>,
+  },
+  #[error("Timeout (Collecting time is over)")]
+  Timeout {
+    round: RoundId,
+    missing: Vec<PartyId>,
+  },
+  #[error("Execution issue ({kind})")]
+  ExecutionIssue {
+    kind: CryptoKind,
+    round: Option<RoundId>,
+    party: Option<PartyId>,
+  },
+  #[error("Request mismatch ({field}: expected {expected}, got {got})")]
+  RequestMismatch {
+    field: RequestField,
+    expected: usize,
+    got: usize,
+  },
+}
+
+#[derive(Debug, Clone, PartialEq, Error)]
+#[non_exhaustive]
+pub enum ValidationKind {
+  #[error("Unexpected incoming message ({0})")]
+  UnexpectedMessage(String),
+  #[error("Unexpected incoming data ({0})")]
+  UnexpectedData(String),
+  #[error("Unexpected empty result")]
+  EmptyResult,
+  #[error("{0}")]
+  Malformed(String),
+  #[error("Unrepresentable fields ({})", .0.join(", "))]
+  Unrepresentable(Vec<String>),
+  #[error("Signing context differs from ours")]
+  ContextMismatch,
+  #[error("Failed to read input ({0})")]
+  Unreadable(String),
+  #[error("Unknown mnemonic word {word:?} at position {position}")]
+  UnknownWord { position: usize, word: String },
+  #[error("Mnemonic checksum mismatch")]
+  BadChecksum,
+  #[error("Wrong passphrase")]
+  WrongPassphrase,
+  #[error("Corrupted data ({0})")]
+  Corrupted(String),
+  #[error("Unsupported format version {0}")]
+  UnsupportedVersion(u16),
+  #[error("Unsupported cipher {0}")]
+  UnsupportedCipher(String),
+  #[error("Unsupported setup: {0}")]
+  UnsupportedSetup(String),
+  #[error("Digest was already signed recently")]
+  DuplicateDigest,
+  #[error("Signing refused ({0})")]
+  SigningRefused(String),
+  #[error("Denied by signing policy ({0})")]
+  PolicyDenied(String),
+  #[error("Rate limit exceeded ({0} signatures per second)")]
+  RateLimitExceeded(u32),
+}
+
+#[derive(Debug, Clone, PartialEq, Error)]
+#[non_exhaustive]
+pub enum TransportKind {
+  #[error("Failed to send {0}")]
+  SendFailed(String),
+  #[error("Incoming message channel is closed")]
+  Disconnected,
+  #[error("Parties {0:?} unreachable")]
+  Unreachable(Vec<u8>),
+  #[error("Message from party {sender} misrouted, intended for party {intended}")]
+  Misrouted { sender: u8, intended: u8 },
+  #[error("Peer aborted the run ({0})")]
+  PeerAborted(String),
+  #[error("Event callback panicked ({0})")]
+  CallbackPanicked(String),
+  #[error("Failed sending result")]
+  ResultUndelivered,
+  #[error("Failed sending quit")]
+  QuitUndelivered,
+}
+
+#[derive(Debug, Clone, PartialEq, Error)]
+#[non_exhaustive]
+pub enum CryptoKind {
+  #[error("Verifying of alpha proofs failed ({0}) (gamma)")]
+  AlphaProofGamma(String),
+  #[error("Verifying of alpha proofs failed ({0}) (w)")]
+  AlphaProofW(String),
+  #[error("proof point not equal to Gamma W")]
+  ProofPointMismatch,
+  #[error("Bad gamma_i decommit ({0})")]
+  BadDecommit(String),
+  #[error("Phase 5 failed ({0})")]
+  Phase5(String),
+  #[error("Incorrect commitment at phase 5 ({0})")]
+  Phase5Commitment(String),
+  #[error("Signature verification failed ({0})")]
+  SignatureVerification(String),
+  #[error("Invalid key at phase 2 ({0})")]
+  InvalidKey(String),
+  #[error("Invalid vss ({0})")]
+  InvalidVss(String),
+  #[error("Incorrect DLog proof ({0})")]
+  DLogProof(String),
+  #[error("Invalid Paillier key proof ({0})")]
+  PaillierKeyProof(String),
+  #[error("Parties disagree on the rotated Paillier keys")]
+  RotationMismatch,
+  #[error("Internal panic ({0})")]
+  Panicked(String),
+  #[error("Nonce reuse detected")]
+  NonceReuse,
+  #[error("Attestation quote does not verify")]
+  Attestation,
+  #[error("Decryption failed ({0})")]
+  Decryption(String),
+}
+
+/// Quantity of a signing request that disagrees with the keystore. For the
+/// index bounds `expected` is the exclusive upper limit.
+#[derive(Debug, Clone, Copy, PartialEq, Error)]
+#[non_exhaustive]
+pub enum RequestField {
+  #[error("signer count")]
+  SignerCount,
+  #[error("participants")]
+  Participants,
+  #[error("threshold")]
+  Threshold,
+  #[error("signer index")]
+  SignerIndex,
+  #[error("signer position")]
+  SignerPosition,
+  #[error("own index")]
+  OwnIndex,
+  #[error("keystore share count")]
+  ShareCount,
+}
+
+/// What a coordinator should do about a failed run.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub enum ErrorClass {
+  /// Nobody is to blame; the same run may succeed when retried.
+  RetryTransient,
+  /// A peer misbehaved; retry with a signer set without `exclude`.
+  RetryDifferentPeers { exclude: Vec<PartyId> },
+  /// Retrying will not help, e.g. a broken keystore or a refused request.
+  Fatal,
+}
+
+impl CoreErrors {
+  pub fn invalid_data(kind: ValidationKind) -> Self {
+    CoreErrors::InvalidData {
+      kind,
+      round: None,
+      party: None,
+    }
+  }
+
+  pub fn transport(kind: TransportKind) -> Self {
+    CoreErrors::TransportIssue {
+      kind,
+      round: None,
+      party: None,
+      source: None,
+    }
+  }
+
+  pub fn mismatch(field: RequestField, expected: usize, got: usize) -> Self {
+    CoreErrors::RequestMismatch {
+      field,
+      expected,
+      got,
+    }
+  }
+
+  pub fn execution(kind: CryptoKind, round: RoundId) -> Self {
+    CoreErrors::ExecutionIssue {
+      kind,
+      round: Some(round),
+      party: None,
+    }
+  }
+
+  /// Attaches the round to errors raised by helpers that don't know it.
+  pub fn in_round(mut self, at: RoundId) -> Self {
+    match &mut self {
+      CoreErrors::InvalidData { round, .. }
+      | CoreErrors::TransportIssue { round, .. }
+      | CoreErrors::ExecutionIssue { round, .. } => {
+        round.get_or_insert(at);
+      }
+      CoreErrors::Timeout { .. } | CoreErrors::RequestMismatch { .. } => {}
+    }
+    self
+  }
+
+  /// Attaches the party responsible for (or targeted by) the failure.
+  pub fn with_party(mut self, id: PartyId) -> Self {
+    match &mut self {
+      CoreErrors::InvalidData { party, .. }
+      | CoreErrors::TransportIssue { party, .. }
+      | CoreErrors::ExecutionIssue { party, .. } => {
+        party.get_or_insert(id);
+      }
+      CoreErrors::Timeout { .. } | CoreErrors::RequestMismatch { .. } => {}
+    }
+    self
+  }
+
+  pub fn round(&self) -> Option<RoundId> {
+    match self {
+      CoreErrors::InvalidData { round, .. }
+      | CoreErrors::TransportIssue { round, .. }
+      | CoreErrors::ExecutionIssue { round, .. } => *round,
+      CoreErrors::Timeout { round, .. } => Some(*round),
+      CoreErrors::RequestMismatch { .. } => None,
+    }
+  }
+
+  pub fn party(&self) -> Option<PartyId> {
+    match self {
+      CoreErrors::InvalidData { party, .. }
+      | CoreErrors::TransportIssue { party, .. }
+      | CoreErrors::ExecutionIssue { party, .. } => *party,
+      CoreErrors::Timeout { .. } | CoreErrors::RequestMismatch { .. } => None,
+    }
+  }
+
+  /// Retry guidance for the coordinator. Data is only ever attributed to a
+  /// party when that party sent it, so invalid data with a party attached
+  /// blames it, whatever was wrong with it; without one it concerns this
+  /// party's own request or keystore.
+  pub fn classification(&self) -> ErrorClass {
+    let blamed = |round: &Option<RoundId>, party: &Option<PartyId>| match (round, party) {
+      (Some(_), Some(party)) => ErrorClass::RetryDifferentPeers {
+        exclude: vec![*party],
+      },
+      _ => ErrorClass::Fatal,
+    };
+    match self {
+      CoreErrors::Timeout { .. } => ErrorClass::RetryTransient,
+      CoreErrors::TransportIssue { kind, .. } => match kind {
+        // A peer refused deliberately, or our own callback is broken.
+        TransportKind::PeerAborted(_) | TransportKind::CallbackPanicked(_) => ErrorClass::Fatal,
+        _ => ErrorClass::RetryTransient,
+      },
+      CoreErrors::ExecutionIssue { kind, round, party } => match kind {
+        CryptoKind::Panicked(_) | CryptoKind::NonceReuse | CryptoKind::RotationMismatch => {
+          ErrorClass::Fatal
+        }
+        _ => blamed(round, party),
+      },
+      CoreErrors::InvalidData { kind, party, .. } => match (kind, party) {
+        (ValidationKind::RateLimitExceeded(_), _) => ErrorClass::RetryTransient,
+        (_, Some(party)) => ErrorClass::RetryDifferentPeers {
+          exclude: vec![*party],
+        },
+        _ => ErrorClass::Fatal,
+      },
+      CoreErrors::RequestMismatch { .. } => ErrorClass::Fatal,
+    }
+  }
+
+  /// The string the error rendered as before the structured redesign.
+  /// Kept while log pipelines migrate; prefer matching on the variants.
+  pub fn as_legacy_string(&self) -> String {
+    match self {
+      CoreErrors::TransportIssue {
+        kind,
+        source: Some(source),
+        ..
+      } if *kind == TransportKind::ResultUndelivered || *kind == TransportKind::QuitUndelivered => {
+        format!("Transport issue ({} {})", kind, source)
+      }
+      _ => self.to_string(),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn invalid_data_from_a_party_blames_it() {
+    for kind in vec![
+      ValidationKind::Malformed("bad share".to_string()),
+      ValidationKind::ContextMismatch,
+      ValidationKind::UnexpectedData("SignRound3".to_string()),
+    ] {
+      let error = CoreErrors::invalid_data(kind.clone())
+        .in_round(RoundId::Sign(1))
+        .with_party(2);
+      assert_eq!(
+        error.classification(),
+        ErrorClass::RetryDifferentPeers { exclude: vec![2] }
+      );
+      assert_eq!(
+        CoreErrors::invalid_data(kind).classification(),
+        ErrorClass::Fatal
+      );
+    }
+  }
+
+  #[test]
+  fn rate_limit_is_transient() {
+    let error =
+      CoreErrors::invalid_data(ValidationKind::RateLimitExceeded(5)).in_round(RoundId::Sign(1));
+    assert_eq!(error.classification(), ErrorClass::RetryTransient);
+  }
+}
@@ -0,0 +1,459 @@
+// Conversion between corelib keystores and bnb-chain/tss-lib's ECDSA
+// `keygen.LocalPartySaveData` JSON. tss-lib embeds `LocalPreParams` and
+// `LocalSecrets`, which Go's encoding/json flattens into the top level.
+
+// Builds and reads keystores through their raw fields.
+#![allow(deprecated)]
+
+use crate::common::types::{Keystore, KeystoreParameters, Threshold};
+use crate::errors::{CoreErrors, ValidationKind};
+use curv::arithmetic::traits::{Converter, Modulo};
+use curv::cryptographic_primitives::secret_sharing::feldman_vss::{
+  ShamirSecretSharing, VerifiableSS,
+};
+use curv::elliptic::curves::traits::{ECPoint, ECScalar};
+use curv::{BigInt, FE, GE};
+use multi_party_ecdsa::protocols::multi_party_ecdsa::gg_2018::party_i::{Keys, SharedKeys};
+use paillier::{DecryptionKey, EncryptionKey};
+use serde::{Deserialize, Serialize};
+use serde_json::value::RawValue;
+use std::panic::{self, AssertUnwindSafe};
+
+const CURVE: &str = "secp256k1";
+
+// Range-proof parameters of GG18/GG20 as implemented by tss-lib. corelib's
+// MtA runs without them, so they are dropped on import and cannot be
+// produced on export.
+const RANGE_PROOF_FIELDS: &[&str] = &[
+  "NTildei", "H1i", "H2i", "Alpha", "Beta", "P", "Q", "NTildej", "H1j", "H2j",
+];
+
+// Go's math/big marshals integers as bare JSON numbers of arbitrary size, so
+// they are carried as raw tokens.
+#[derive(Serialize, Deserialize)]
+struct Int(Box<RawValue>);
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Point {
+  curve: String,
+  coords: [Int; 2],
+}
+
+#[derive(Serialize, Deserialize)]
+struct PaillierSk {
+  #[serde(rename = "N")]
+  n: Int,
+  #[serde(rename = "LambdaN")]
+  lambda_n: Int,
+  #[serde(rename = "PhiN")]
+  phi_n: Int,
+}
+
+#[derive(Serialize, Deserialize)]
+struct PaillierPk {
+  #[serde(rename = "N")]
+  n: Int,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct SaveData {
+  #[serde(rename = "PaillierSK")]
+  paillier_sk: Option<PaillierSk>,
+  #[serde(rename = "NTildei")]
+  n_tilde_i: Option<Int>,
+  h1i: Option<Int>,
+  h2i: Option<Int>,
+  alpha: Option<Int>,
+  beta: Option<Int>,
+  p: Option<Int>,
+  q: Option<Int>,
+  xi: Option<Int>,
+  #[serde(rename = "ShareID")]
+  share_id: Option<Int>,
+  ks: Option<Vec<Int>>,
+  #[serde(rename = "NTildej")]
+  n_tilde_j: Option<Vec<Option<Int>>>,
+  h1j: Option<Vec<Option<Int>>>,
+  h2j: Option<Vec<Option<Int>>>,
+  big_xj: Option<Vec<Point>>,
+  #[serde(rename = "PaillierPKs")]
+  paillier_pks: Option<Vec<PaillierPk>>,
+  #[serde(rename = "ECDSAPub")]
+  ecdsa_pub: Option<Point>,
+}
+
+/// Keystore recovered from tss-lib save data.
+#[derive(Debug, Clone)]
+pub struct Imported {
+  pub keystore: Keystore,
+  /// tss-lib fields corelib has no use for and discarded.
+  pub dropped: Vec<&'static str>,
+  /// Keystore fields tss-lib does not keep; they were rebuilt from public
+  /// data or left empty and must not be relied on beyond signing.
+  pub synthesized: Vec<&'static str>,
+}
+
+/// tss-lib save data rendered from a keystore.
+#[derive(Debug, Clone)]
+pub struct Exported {
+  pub json: String,
+  /// tss-lib fields written as `null` because corelib has no counterpart.
+  /// tss-lib needs fresh pre-parameters for these before the data is usable.
+  pub missing: Vec<&'static str>,
+}
+
+fn malformed(msg: String) -> CoreErrors {
+  CoreErrors::invalid_data(ValidationKind::Malformed(msg))
+}
+
+fn parse_int(value: &Int, field: &str) -> Result<BigInt, CoreErrors> {
+  BigInt::from_str_radix(value.0.get(), 10)
+    .map_err(|e| malformed(format!("{} is not an integer ({:?})", field, e)))
+}
+
+fn make_int(value: &BigInt) -> Int {
+  Int(RawValue::from_string(value.to_str_radix(10)).expect("decimal integer is valid JSON"))
+}
+
+fn parse_point(value: &Point, field: &str) -> Result<GE, CoreErrors> {
+  let mut bytes = vec![4u8];
+  for coord in value.coords.iter() {
+    let coord = BigInt::to_vec(&parse_int(coord, field)?);
+    if coord.len() > 32 {
+      return Err(malformed(format!("{} is not a secp256k1 point", field)));
+    }
+    bytes.extend(vec![0u8; 32 - coord.len()]);
+    bytes.extend(coord);
+  }
+  GE::from_bytes(&bytes).map_err(|e| malformed(format!("{} is not on the curve ({:?})", field, e)))
+}
+
+fn make_point(point: &GE) -> Point {
+  Point {
+    curve: CURVE.to_string(),
+    coords: [
+      make_int(&point.x_coor().expect("affine point")),
+      make_int(&point.y_coor().expect("affine point")),
+    ],
+  }
+}
+
+// Recovers the coefficient commitments of the joint sharing polynomial from
+// its evaluations at 1..=threshold+1 by interpolating in the exponent. A
+// sharing of lower degree than `threshold` has a coefficient at infinity,
+// which curv cannot represent; that is reported instead of panicking.
+fn interpolate_commitments(points: &[GE], threshold: usize) -> Result<Vec<GE>, CoreErrors> {
+  let q = FE::q();
+  let xs: Vec<BigInt> = (1..=threshold + 1)
+    .map(|x| BigInt::from(x as u64))
+    .collect();
+
+  let interpolate = || {
+    let mut commitments: Vec<Option<GE>> = vec![None; threshold + 1];
+    for (j, x_j) in xs.iter().enumerate() {
+      // Coefficients of the Lagrange basis polynomial for x_j.
+      let mut basis = vec![BigInt::from(1)];
+      let mut denominator = BigInt::from(1);
+      for (m, x_m) in xs.iter().enumerate().filter(|(m, _)| *m != j) {
+        let mut next = vec![BigInt::from(0); basis.len() + 1];
+        for (k, c) in basis.iter().enumerate() {
+          next[k + 1] = BigInt::mod_add(&next[k + 1], c, &q);
+          next[k] = BigInt::mod_sub(&next[k], &BigInt::mod_mul(c, x_m, &q), &q);
+        }
+        basis = next;
+        denominator = BigInt::mod_mul(&denominator, &BigInt::mod_sub(x_j, &xs[m], &q), &q);
+      }
+      let scale = BigInt::mod_inv(&denominator, &q);
+
+      for (k, c) in basis.iter().enumerate() {
+        let c = BigInt::mod_mul(c, &scale, &q);
+        if c == BigInt::from(0) {
+          continue;
+        }
+        let c: FE = ECScalar::from(&c);
+        let term = points[j] * c;
+        commitments[k] = Some(match commitments[k] {
+          Some(acc) => acc + term,
+          None => term,
+        });
+      }
+    }
+    commitments.into_iter().collect::<Option<Vec<GE>>>()
+  };
+
+  match panic::catch_unwind(AssertUnwindSafe(interpolate)) {
+    Ok(Some(commitments)) => Ok(commitments),
+    _ => Err(malformed(format!(
+      "BigXj is not a sharing of degree {}",
+      threshold
+    ))),
+  }
+}
+
+// Splits the joint commitments into one scheme per party whose point-wise
+// sum is the joint scheme, which is all signing relies on. Party j's scheme
+// is the joint one weighted by its Lagrange coefficient l_j over all n
+// shares, except that it commits to l_j * X_j: both sum to the joint
+// scheme, as the l_j sum to 1 and interpolate y from the X_j. The split only
+// depends on public data, so every party of the wallet imports the same
+// schemes, and party j's scheme commits to `y_i = g * (l_j * x_j)`.
+fn split_commitments(joint: &VerifiableSS, points: &[GE]) -> Vec<VerifiableSS> {
+  let all: Vec<usize> = (0..points.len()).collect();
+  points
+    .iter()
+    .enumerate()
+    .map(|(j, x_j)| {
+      let l_j = joint.map_share_to_new_params(j, &all);
+      let mut commitments = vec![*x_j * l_j];
+      commitments.extend(joint.commitments[1..].iter().map(|c| *c * l_j));
+      VerifiableSS {
+        parameters: joint.parameters.clone(),
+        commitments,
+      }
+    })
+    .collect()
+}
+
+/// Parses tss-lib save data for a `threshold`-of-n wallet. Shares must be
+/// indexed 1..=n (tss-lib `Ks`), as corelib evaluates shares at those points;
+/// every field that prevents the import is listed in the error.
+pub fn import(json: &str, threshold: u16) -> Result<Imported, CoreErrors> {
+  let data: SaveData =
+    serde_json::from_str(json).map_err(|e| malformed(format!("Invalid save data ({})", e)))?;
+
+  let mut unrepresentable = vec![];
+  let mut require = |present: bool, field: &str| {
+    if !present {
+      unrepresentable.push(format!("{} (missing)", field));
+    }
+  };
+  require(data.paillier_sk.is_some(), "PaillierSK");
+  require(data.xi.is_some(), "Xi");
+  require(data.share_id.is_some(), "ShareID");
+  require(data.ks.is_some(), "Ks");
+  require(data.big_xj.is_some(), "BigXj");
+  require(data.paillier_pks.is_some(), "PaillierPKs");
+  require(data.ecdsa_pub.is_some(), "ECDSAPub");
+
+  let ks = data.ks.as_deref().unwrap_or_default();
+  let share_count = ks.len();
+  for (i, k) in ks.iter().enumerate() {
+    if parse_int(k, "Ks")? != BigInt::from(i as u64 + 1) {
+      unrepresentable.push(format!("Ks[{}] (share index must be {})", i, i + 1));
+    }
+  }
+  let big_xj = data.big_xj.as_deref().unwrap_or_default();
+  if big_xj.len() != share_count {
+    unrepresentable.push(format!("BigXj (expected {} points)", share_count));
+  }
+  for (i, point) in big_xj.iter().enumerate() {
+    if point.curve != CURVE {
+      unrepresentable.push(format!("BigXj[{}].Curve ({})", i, point.curve));
+    }
+  }
+  if let Some(point) = &data.ecdsa_pub {
+    if point.curve != CURVE {
+      unrepresentable.push(format!("ECDSAPub.Curve ({})", point.curve));
+    }
+  }
+  let paillier_pks = data.paillier_pks.as_deref().unwrap_or_default();
+  if paillier_pks.len() != share_count {
+    unrepresentable.push(format!("PaillierPKs (expected {} keys)", share_count));
+  }
+  if threshold as usize >= share_count {
+    unrepresentable.push(format!(
+      "threshold ({} of {} shares)",
+      threshold, share_count
+    ));
+  }
+  if !unrepresentable.is_empty() {
+    return Err(CoreErrors::invalid_data(ValidationKind::Unrepresentable(
+      unrepresentable,
+    )));
+  }
+
+  let threshold = threshold as usize;
+  let share_id = parse_int(data.share_id.as_ref().unwrap(), "ShareID")?;
+  let party_index = (1..=share_count)
+    .position(|k| BigInt::from(k as u64) == share_id)
+    .ok_or_else(|| malformed("ShareID is not one of Ks".to_string()))?;
+
+  let points = big_xj
+    .iter()
+    .enumerate()
+    .map(|(i, p)| parse_point(p, &format!("BigXj[{}]", i)))
+    .collect::<Result<Vec<GE>, CoreErrors>>()?;
+  let y = parse_point(data.ecdsa_pub.as_ref().unwrap(), "ECDSAPub")?;
+
+  let x_i: FE = ECScalar::from(&parse_int(data.xi.as_ref().unwrap(), "Xi")?);
+  if GE::generator() * x_i != points[party_index] {
+    return Err(malformed("Xi does not match BigXj".to_string()));
+  }
+
+  let joint = VerifiableSS {
+    parameters: ShamirSecretSharing {
+      threshold,
+      share_count,
+    },
+    commitments: interpolate_commitments(&points, threshold)?,
+  };
+  let consistent = joint.commitments[0] == y
+    && points
+      .iter()
+      .enumerate()
+      .all(|(i, p)| joint.get_point_commitment(i + 1) == *p);
+  if !consistent {
+    return Err(malformed(format!(
+      "BigXj and ECDSAPub are not a {}-of-{} sharing",
+      threshold, share_count
+    )));
+  }
+
+  let sk = data.paillier_sk.as_ref().unwrap();
+  let n = parse_int(&sk.n, "PaillierSK.N")?;
+  let phi_n = parse_int(&sk.phi_n, "PaillierSK.PhiN")?;
+  // p + q = n - phi(n) + 1 and (p - q)^2 = (p + q)^2 - 4n.
+  let sum = &(&n - &phi_n) + &BigInt::from(1);
+  let diff = (&(&sum * &sum) - &(&n * &BigInt::from(4))).sqrt();
+  let p = (&sum + &diff) / BigInt::from(2);
+  let q = (&sum - &diff) / BigInt::from(2);
+  if &p * &q != n {
+    return Err(malformed("PaillierSK.PhiN does not match N".to_string()));
+  }
+
+  let paillier_key_vec = paillier_pks
+    .iter()
+    .enumerate()
+    .map(|(i, pk)| {
+      let n = parse_int(&pk.n, &format!("PaillierPKs[{}].N", i))?;
+      Ok(EncryptionKey { nn: &n * &n, n })
+    })
+    .collect::<Result<Vec<EncryptionKey>, CoreErrors>>()?;
+  if paillier_key_vec[party_index].n != n {
+    return Err(malformed(
+      "PaillierSK does not match PaillierPKs".to_string(),
+    ));
+  }
+
+  let vss_scheme_vec = split_commitments(&joint, &points);
+  let all: Vec<usize> = (0..share_count).collect();
+  let u_i = joint.map_share_to_new_params(party_index, &all) * x_i;
+  let keystore = Keystore {
+    params: KeystoreParameters::new(Threshold::new(threshold as u16), share_count as u16)?,
+    party_key: Keys {
+      u_i,
+      y_i: vss_scheme_vec[party_index].commitments[0],
+      dk: DecryptionKey { p, q },
+      ek: paillier_key_vec[party_index].clone(),
+      party_index: party_index + 1,
+    },
+    party_shares: vec![],
+    shared_keys: SharedKeys { y, x_i },
+    party_index,
+    vss_scheme_vec,
+    paillier_key_vec,
+    y_sum: y,
+  };
+
+  Ok(Imported {
+    keystore,
+    dropped: RANGE_PROOF_FIELDS.to_vec(),
+    synthesized: vec![
+      "party_key.u_i",
+      "party_key.y_i",
+      "party_shares",
+      "vss_scheme_vec",
+    ],
+  })
+}
+
+/// Renders a keystore as tss-lib save data. corelib does not hold tss-lib's
+/// range-proof parameters, so those are listed in `missing`.
+pub fn export(keystore: &Keystore) -> Result<Exported, CoreErrors> {
+  let share_count = keystore.share_count() as usize;
+  let dk = &keystore.party_key.dk;
+  let one = BigInt::from(1);
+  let (p_1, q_1) = (&dk.p - &one, &dk.q - &one);
+  let phi_n = &p_1 * &q_1;
+  let lambda_n = &phi_n / &p_1.gcd(&q_1);
+
+  let data = SaveData {
+    paillier_sk: Some(PaillierSk {
+      n: make_int(&(&dk.p * &dk.q)),
+      lambda_n: make_int(&lambda_n),
+      phi_n: make_int(&phi_n),
+    }),
+    n_tilde_i: None,
+    h1i: None,
+    h2i: None,
+    alpha: None,
+    beta: None,
+    p: None,
+    q: None,
+    xi: Some(make_int(&keystore.shared_keys.x_i.to_big_int())),
+    share_id: Some(make_int(&BigInt::from(keystore.party_index() as u64 + 1))),
+    ks: Some(
+      (1..=share_count)
+        .map(|k| make_int(&BigInt::from(k as u64)))
+        .collect(),
+    ),
+    n_tilde_j: None,
+    h1j: None,
+    h2j: None,
+    big_xj: Some(
+      Keys::get_commitments_to_xi(&keystore.vss_scheme_vec)
+        .iter()
+        .map(make_point)
+        .collect(),
+    ),
+    paillier_pks: Some(
+      keystore
+        .paillier_key_vec
+        .iter()
+        .map(|ek| PaillierPk { n: make_int(&ek.n) })
+        .collect(),
+    ),
+    ecdsa_pub: Some(make_point(&keystore.y_sum)),
+  };
+
+  let json = serde_json::to_string(&data)
+    .map_err(|e| malformed(format!("Failed to render save data ({})", e)))?;
+  Ok(Exported {
+    json,
+    missing: RANGE_PROOF_FIELDS.to_vec(),
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::testing::fixtures::generate_test_keystore_3_of_5;
+
+  #[test]
+  fn import_is_deterministic_and_consistent() {
+    let keystores = generate_test_keystore_3_of_5();
+    let imported = keystores
+      .iter()
+      .map(|k| import(&export(k).unwrap().json, 2).unwrap().keystore)
+      .collect::<Vec<Keystore>>();
+
+    let fingerprint = imported[0].public().fingerprint();
+    for (original, keystore) in keystores.iter().zip(imported.iter()) {
+      assert_eq!(keystore.public().fingerprint(), fingerprint);
+      assert_eq!(keystore.public().y_sum, original.public().y_sum);
+      Keystore::from_parts(keystore.clone().into_parts()).unwrap();
+    }
+    let again = import(&export(&keystores[0]).unwrap().json, 2).unwrap();
+    assert_eq!(again.keystore.public().fingerprint(), fingerprint);
+  }
+
+  #[test]
+  fn lower_degree_sharing_is_an_error() {
+    let g: GE = ECPoint::generator();
+    let x: FE = ECScalar::new_random();
+    let point = g * x;
+    assert!(interpolate_commitments(&[point, point], 1).is_err());
+  }
+}
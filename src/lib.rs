@@ -1,10 +1,122 @@
+//! Threshold ECDSA (GG18) key generation and signing, one party per call.
+//! Each entry point runs a single party and talks to its peers through a
+//! pair of channels the caller relays over its own transport.
+//!
+//! ```no_run
+//! use corelib::prelude::*;
+//! use std::sync::mpsc::channel;
+//!
+//! # fn main() -> Result<(), CoreErrors> {
+//! // Party 0 of a 2-of-3 key generation; the caller forwards what arrives
+//! // on `outgoing_receiver` and feeds peers' messages to `incoming_sender`.
+//! let (outgoing_sender, outgoing_receiver) = channel();
+//! let (incoming_sender, incoming_receiver) = channel();
+//! # drop((outgoing_receiver, incoming_sender));
+//! safe_keygeneration(3, 1, 0, outgoing_sender, incoming_receiver)?;
+//! # Ok(())
+//! # }
+//! ```
+
 #![feature(associated_type_defaults)]
 
+/// Errors every entry point returns, and how to react to them.
+///
+/// ```
+/// use corelib::errors::ErrorClass;
+/// use corelib::prelude::*;
+/// use std::sync::mpsc::channel;
+///
+/// let (outgoing_sender, _outgoing_receiver) = channel();
+/// let (_incoming_sender, incoming_receiver) = channel();
+/// let err: CoreErrors = safe_keygeneration(3, 0, 0, outgoing_sender, incoming_receiver)
+///   .unwrap_err();
+/// assert_eq!(err.classification(), ErrorClass::Fatal);
+/// ```
 pub mod errors;
+/// Wire messages, keystores and the helpers around them.
+///
+/// ```
+/// use corelib::common::hash::{hash, HashAlg};
+/// use corelib::prelude::*;
+///
+/// # fn main() -> Result<(), CoreErrors> {
+/// let digest: Digest32 = hash(HashAlg::Sha256, b"transaction");
+/// assert_eq!(Digest32::from_hex(&digest.to_string())?, digest);
+/// # Ok(())
+/// # }
+/// ```
 pub mod common;
+/// The protocol runs, plus in-process drivers that run every party at once.
+///
+/// ```no_run
+/// use corelib::prelude::*;
+/// use corelib::scenarios::{run_local_keygen, run_local_sign};
+///
+/// # fn main() -> Result<(), CoreErrors> {
+/// let keystores: Vec<Keystore> = run_local_keygen(3, 1)?;
+/// let digest = Digest32::from_bytes(&[0x42; 32])?;
+/// let signatures = run_local_sign(&keystores, &[0, 2], &digest)?;
+/// assert!(signatures[0].verify(&keystores[0].public_key(), &digest));
+/// # Ok(())
+/// # }
+/// ```
 pub mod scenarios;
+/// Conversions to and from other threshold-signing implementations.
+///
+/// ```no_run
+/// use corelib::interop::tsslib;
+/// use corelib::prelude::*;
+///
+/// # fn main() -> Result<(), CoreErrors> {
+/// # let save_data = String::new();
+/// let imported = tsslib::import(&save_data, 1)?;
+/// let keystore: Keystore = imported.keystore;
+/// let exported = tsslib::export(&keystore)?;
+/// # drop(exported);
+/// # Ok(())
+/// # }
+/// ```
+#[cfg(feature = "tsslib")]
+pub mod interop;
+/// Fixed keystores and misbehaving parties for tests.
+///
+/// ```no_run
+/// use corelib::prelude::*;
+/// use corelib::scenarios::run_local_sign;
+/// use corelib::testing::fixtures::generate_test_keystore_2_of_3;
+///
+/// # fn main() -> Result<(), CoreErrors> {
+/// let keystores: Vec<Keystore> = generate_test_keystore_2_of_3();
+/// let digest = Digest32::from_bytes(&[0x42; 32])?;
+/// run_local_sign(&keystores, &[0, 1], &digest)?;
+/// # Ok(())
+/// # }
+/// ```
+#[cfg(any(test, feature = "test-utils"))]
+pub mod testing;
+
+/// The types and entry points most integrations need.
+///
+/// ```no_run
+/// use corelib::prelude::*;
+/// use std::sync::mpsc::channel;
+///
+/// # fn main() -> Result<(), CoreErrors> {
+/// let (outgoing_sender, outgoing_receiver) = channel();
+/// let (incoming_sender, incoming_receiver) = channel();
+/// # drop((outgoing_receiver, incoming_sender));
+/// safe_keygeneration(3, 1, 0, outgoing_sender, incoming_receiver)?;
+/// # Ok(())
+/// # }
+/// ```
+pub mod prelude;
+
+// The protocol crates, for code that works on the raw keystore parts.
+#[cfg(feature = "interop")]
 pub use curv;
+#[cfg(feature = "interop")]
 pub use curv::GE;
+#[cfg(feature = "interop")]
 pub use multi_party_ecdsa::protocols::multi_party_ecdsa::gg_2018::party_i;
 
 #[cfg(test)]
@@ -0,0 +1,8 @@
+pub use crate::common::messages::{
+  Errors, FromData, IncomingMessages, Message, MessageData, OutgoingMessages, RoundResult,
+};
+pub use crate::common::types::{
+  Digest32, Keystore, KeystoreParameters, SealedData, SignerCount, Threshold, AEAD,
+};
+pub use crate::errors::CoreErrors;
+pub use crate::scenarios::{safe_keygeneration, safe_sign};
@@ -0,0 +1,28 @@
+use curv::arithmetic::traits::Converter;
+use curv::BigInt;
+use multi_party_ecdsa::protocols::multi_party_ecdsa::gg_2018::party_i::KeyGenBroadcastMessage1;
+
+const ATTESTATION_TAG: &[u8] = b"GG2018-keygen-round1-attestation";
+
+/// Hardware attestation for key ceremonies run inside a TEE (SGX, Nitro,
+/// ...). `attest` produces a quote binding `data` to the enclave, `verify`
+/// checks a peer's quote, including whatever measurement policy the
+/// deployment requires.
+pub trait AttestationProvider: Send + Sync {
+  fn attest(&self, data: &[u8]) -> Vec<u8>;
+  fn verify(&self, attestation: &[u8], data: &[u8]) -> bool;
+}
+
+/// What a party's quote covers: its position and its round-1 commitment
+/// and Paillier key, so a quote can't be replayed for another party or
+/// another keygen.
+pub(crate) fn attested_data(party_id: u8, broadcast: &KeyGenBroadcastMessage1) -> Vec<u8> {
+  let mut data = ATTESTATION_TAG.to_vec();
+  data.push(party_id);
+  for value in &[&broadcast.com, &broadcast.e.n] {
+    let bytes = BigInt::to_vec(value);
+    data.extend_from_slice(&(bytes.len() as u32).to_be_bytes());
+    data.extend_from_slice(&bytes);
+  }
+  data
+}
@@ -0,0 +1,101 @@
+use crate::common::types::Digest32;
+use std::collections::{HashSet, VecDeque};
+
+/// Verdict of a `SigningGuard` on a signing request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Decision {
+  Approve,
+  /// The digest was signed recently.
+  Duplicate,
+  /// Refused for a reason of the guard's own.
+  Deny(String),
+}
+
+/// Approves signing requests before this party takes part in them. It is
+/// consulted before anything is sent; a negative decision aborts the run
+/// for every party.
+pub trait SigningGuard {
+  fn check(&mut self, digest: &Digest32, context: Option<&[u8]>, signers: &[usize]) -> Decision;
+}
+
+/// Persistence for `RecentDigests`, so repeats are caught across restarts.
+pub trait DigestStore {
+  /// Previously recorded digests, oldest first.
+  fn load(&mut self) -> Vec<Digest32>;
+  fn record(&mut self, digest: &Digest32);
+}
+
+/// Default guard: refuses a digest that is among the last `capacity` ones it
+/// approved, whatever the context and signers. A digest counts as signed
+/// once approved, so retrying it after a failed run is refused as well.
+pub struct RecentDigests {
+  capacity: usize,
+  order: VecDeque<Digest32>,
+  recent: HashSet<Digest32>,
+  store: Option<Box<dyn DigestStore + Send>>,
+}
+
+impl RecentDigests {
+  pub fn new(capacity: usize) -> Self {
+    RecentDigests {
+      capacity,
+      order: VecDeque::new(),
+      recent: HashSet::new(),
+      store: None,
+    }
+  }
+
+  /// Starts from the digests in `store` and records every approved one
+  /// there.
+  pub fn with_store(capacity: usize, mut store: Box<dyn DigestStore + Send>) -> Self {
+    let mut guard = Self::new(capacity);
+    for digest in store.load() {
+      guard.remember(digest);
+    }
+    guard.store = Some(store);
+    guard
+  }
+
+  fn remember(&mut self, digest: Digest32) {
+    if self.capacity == 0 || !self.recent.insert(digest) {
+      return;
+    }
+    self.order.push_back(digest);
+    if self.order.len() > self.capacity {
+      if let Some(oldest) = self.order.pop_front() {
+        self.recent.remove(&oldest);
+      }
+    }
+  }
+
+  pub fn len(&self) -> usize {
+    self.order.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.order.is_empty()
+  }
+}
+
+impl std::fmt::Debug for RecentDigests {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("RecentDigests")
+      .field("capacity", &self.capacity)
+      .field("len", &self.order.len())
+      .field("persisted", &self.store.is_some())
+      .finish()
+  }
+}
+
+impl SigningGuard for RecentDigests {
+  fn check(&mut self, digest: &Digest32, _context: Option<&[u8]>, _signers: &[usize]) -> Decision {
+    if self.recent.contains(digest) {
+      return Decision::Duplicate;
+    }
+    self.remember(*digest);
+    if let Some(store) = self.store.as_mut() {
+      store.record(digest);
+    }
+    Decision::Approve
+  }
+}
@@ -0,0 +1,179 @@
+use crate::common::messages::*;
+use crate::common::types::{HealthReport, Keystore, RoundId};
+use crate::errors::CoreErrors;
+use crate::scenarios::runner::{catch_panic, finish, halt_on_error, RoundRunner};
+use curv::{
+  arithmetic::traits::Samplable,
+  cryptographic_primitives::hashing::{hash_sha256::HSha256, traits::Hash},
+  elliptic::curves::traits::{ECPoint, ECScalar},
+  BigInt, FE, GE,
+};
+use multi_party_ecdsa::protocols::multi_party_ecdsa::gg_2018::party_i::Keys;
+
+fn share_proof_challenge(
+  request: &HealthCheckChallenge,
+  party: usize,
+  public_share: &GE,
+  commitment: &GE,
+) -> FE {
+  let e = HSha256::create_hash(&[
+    &BigInt::from(&request.session_id[..]),
+    &request.challenge,
+    &BigInt::from(party as u64),
+    &public_share.bytes_compressed_to_big_int(),
+    &commitment.bytes_compressed_to_big_int(),
+  ]);
+  ECScalar::from(&e)
+}
+
+// Schnorr proof of knowledge of `x_i` for the public share `X_i = x_i * G`,
+// bound to the challenge and session so it can't be replayed.
+#[allow(deprecated)]
+fn prove_share(keystore: &Keystore, request: &HealthCheckChallenge) -> HealthCheckResponse {
+  let g: GE = ECPoint::generator();
+  let x_i = keystore.shared_keys.x_i;
+  let r: FE = ECScalar::new_random();
+  let commitment = g * r;
+  let e = share_proof_challenge(request, keystore.party_index, &(g * x_i), &commitment);
+  HealthCheckResponse {
+    commitment,
+    response: r + e * x_i,
+  }
+}
+
+fn verify_share_proof(
+  request: &HealthCheckChallenge,
+  party: usize,
+  public_share: &GE,
+  proof: &HealthCheckResponse,
+) -> bool {
+  let g: GE = ECPoint::generator();
+  let e = share_proof_challenge(request, party, public_share, &proof.commitment);
+  g * proof.response == proof.commitment + *public_share * e
+}
+
+pub fn health_check(
+  keystore: &Keystore,
+  session_id: &[u8],
+  outgoing_sender: OutgoingChannel,
+  incoming_receiver: IncomingChannel,
+) {
+  let result = safe_health_check(
+    keystore,
+    session_id,
+    outgoing_sender.clone(),
+    incoming_receiver,
+  )
+  .and_then(|report| {
+    finish(
+      &outgoing_sender,
+      Message::Complete(RoundResult::HealthCheck { report }),
+    )
+  });
+  halt_on_error(&outgoing_sender, result);
+}
+
+/// Checks that every party is online and still holds a share consistent
+/// with the public VSS commitments, without signing anything.
+///
+/// The initiator broadcasts a fresh challenge; each party answers with a
+/// proof of knowledge of its share bound to the challenge and `session_id`
+/// (see `health_check_respond`). One round trip, nothing secret is revealed,
+/// so it can be repeated as often as needed.
+pub fn safe_health_check(
+  keystore: &Keystore,
+  session_id: &[u8],
+  outgoing_sender: OutgoingChannel,
+  incoming_receiver: IncomingChannel,
+) -> Result<HealthReport, CoreErrors> {
+  let sink = outgoing_sender.clone();
+  catch_panic("health_check", &sink, || {
+    health_check_rounds(keystore, session_id, outgoing_sender, incoming_receiver)
+  })
+}
+
+#[allow(deprecated)]
+fn health_check_rounds(
+  keystore: &Keystore,
+  session_id: &[u8],
+  outgoing_sender: OutgoingChannel,
+  incoming_receiver: IncomingChannel,
+) -> Result<HealthReport, CoreErrors> {
+  let participants = keystore.params.share_count as u8;
+  let party_id = keystore.party_index as u8;
+  let runner = RoundRunner::new(party_id, participants, &outgoing_sender, incoming_receiver);
+  let public_shares = Keys::get_commitments_to_xi(&keystore.vss_scheme_vec);
+
+  let request = HealthCheckChallenge {
+    session_id: session_id.to_vec(),
+    challenge: BigInt::sample(256),
+  };
+
+  runner.log("Broadcasting health check challenge")?;
+  // Parties we cannot reach simply end up silent in the report.
+  runner
+    .broadcast(&MessageData::HealthCheckChallenge(request.clone()))
+    .require_any(RoundId::HealthCheck(1))?;
+
+  let mut responses: Vec<Option<HealthCheckResponse>> = vec![None; participants as usize];
+  responses[party_id as usize] = Some(prove_share(keystore, &request));
+
+  runner.log("Collecting health check responses")?;
+  let (responses, _) = runner.collect_available(responses, RoundId::HealthCheck(2))?;
+
+  let mut report = HealthReport::default();
+  for (i, response) in responses.iter().enumerate() {
+    match (response, public_shares.get(i)) {
+      (None, _) => report.silent.push(i as u8),
+      (Some(proof), Some(public_share)) if verify_share_proof(&request, i, public_share, proof) => {
+        report.healthy.push(i as u8)
+      }
+      (Some(_), _) => report.invalid.push(i as u8),
+    }
+  }
+
+  Ok(report)
+}
+
+pub fn health_check_respond(
+  keystore: &Keystore,
+  outgoing_sender: OutgoingChannel,
+  incoming_receiver: IncomingChannel,
+) {
+  let result = safe_health_check_respond(keystore, outgoing_sender.clone(), incoming_receiver);
+  halt_on_error(&outgoing_sender, result);
+}
+
+/// Answers a single `safe_health_check` challenge from whichever party sent it.
+pub fn safe_health_check_respond(
+  keystore: &Keystore,
+  outgoing_sender: OutgoingChannel,
+  incoming_receiver: IncomingChannel,
+) -> Result<(), CoreErrors> {
+  let sink = outgoing_sender.clone();
+  catch_panic("health_check", &sink, || {
+    health_check_respond_rounds(keystore, outgoing_sender, incoming_receiver)
+  })
+}
+
+#[allow(deprecated)]
+fn health_check_respond_rounds(
+  keystore: &Keystore,
+  outgoing_sender: OutgoingChannel,
+  incoming_receiver: IncomingChannel,
+) -> Result<(), CoreErrors> {
+  let participants = keystore.params.share_count as u8;
+  let party_id = keystore.party_index as u8;
+  let runner = RoundRunner::new(party_id, participants, &outgoing_sender, incoming_receiver);
+
+  runner.log("Waiting for health check challenge")?;
+  let (initiator, request) = runner.receive_one::<HealthCheckChallenge>(RoundId::HealthCheck(1))?;
+
+  runner.log("Sending health check response")?;
+  runner.send(
+    initiator,
+    &MessageData::HealthCheckResponse(prove_share(keystore, &request)),
+  )?;
+
+  runner.quit()
+}
@@ -0,0 +1,501 @@
+use crate::common::channel::{ChannelSink, ProtocolReceiver, ProtocolSender};
+use crate::common::config::{ProtocolConfig, ProtocolVersion};
+use crate::common::events::EventSink;
+use crate::common::hash::{hash, HashAlg};
+use crate::common::messages::*;
+use crate::common::types::{
+  scalar_to_bytes, CeremonyReport, Digest32, Keystore, KeystoreParameters, RoundId, SealedData,
+  Threshold, AEAD, LEGACY_NONCE,
+};
+use crate::common::utils::{
+  aes_decrypt, aes_encrypt, aes_encrypt_with_nonce, bigint_to_hex, check_vss_shape, hkdf_expand,
+  verify_vss_share,
+};
+use crate::errors::{CoreErrors, CryptoKind, RequestField, ValidationKind};
+use crate::scenarios::attestation::{attested_data, AttestationProvider};
+use crate::scenarios::runner::{catch_panic, halt_on_error, RoundRunner};
+use curv::{
+  arithmetic::traits::Converter,
+  elliptic::curves::traits::{ECPoint, ECScalar},
+  BigInt, FE, GE,
+};
+use multi_party_ecdsa::protocols::multi_party_ecdsa::gg_2018::party_i::{
+  KeyGenBroadcastMessage1, Keys, Parameters,
+};
+use paillier::EncryptionKey;
+use std::convert::TryFrom;
+
+const ROUND3_KEY_INFO: &[u8] = b"GG2018-keygen-round3-aes-key";
+
+// AES key for the round-3 share sent from `from` to `to`, derived from the
+// pairwise DH x-coordinate. From V2 on each direction gets its own key, as
+// both use the same fixed nonce.
+fn round3_key(version: ProtocolVersion, shared_x: &BigInt, from: u8, to: u8) -> Vec<u8> {
+  if version == ProtocolVersion::V1 {
+    return BigInt::to_vec(shared_x);
+  }
+  let mut info = ROUND3_KEY_INFO.to_vec();
+  info.extend_from_slice(&[from, to]);
+  hkdf_expand(&BigInt::to_vec(shared_x), &info, 32)
+}
+
+// Plaintext of a round-3 share: minimal big-endian before V3, which leaks the
+// share's magnitude through the ciphertext length; exactly 32 bytes from V3.
+fn encode_share(version: ProtocolVersion, share: &FE) -> Vec<u8> {
+  if version < ProtocolVersion::V3 {
+    return BigInt::to_vec(&share.to_big_int());
+  }
+  scalar_to_bytes(share).to_vec()
+}
+
+fn decode_share(version: ProtocolVersion, plaintext: &[u8]) -> Result<FE, CoreErrors> {
+  if version >= ProtocolVersion::V3 && plaintext.len() != 32 {
+    return Err(CoreErrors::invalid_data(ValidationKind::Malformed(
+      format!("round 3 share of {} bytes", plaintext.len()),
+    )));
+  }
+  Ok(ECScalar::from(&BigInt::from(plaintext)))
+}
+
+// Refuses what `KeystoreParameters::new` refuses, and a party outside the run,
+// before anything is sent.
+fn check_keygen_request(participants: u8, threshold: u8, party_id: u8) -> Result<(), CoreErrors> {
+  KeystoreParameters::new(Threshold::new(threshold as u16), participants as u16)?;
+  if party_id >= participants {
+    return Err(CoreErrors::mismatch(
+      RequestField::OwnIndex,
+      participants as usize,
+      party_id as usize,
+    ));
+  }
+  Ok(())
+}
+
+pub fn keygeneration(
+  participants: u8,
+  threshold: u8,
+  party_id: u8,
+  outgoing_sender: OutgoingChannel,
+  incoming_receiver: IncomingChannel,
+) {
+  let result = safe_keygeneration(
+    participants,
+    threshold,
+    party_id,
+    outgoing_sender.clone(),
+    incoming_receiver,
+  );
+  halt_on_error(&outgoing_sender, result);
+}
+
+pub fn keygeneration_with_config(
+  participants: u8,
+  threshold: u8,
+  party_id: u8,
+  config: &ProtocolConfig,
+  outgoing_sender: OutgoingChannel,
+  incoming_receiver: IncomingChannel,
+) {
+  let result = safe_keygeneration_with_config(
+    participants,
+    threshold,
+    party_id,
+    config,
+    outgoing_sender.clone(),
+    incoming_receiver,
+  );
+  halt_on_error(&outgoing_sender, result);
+}
+
+pub fn safe_keygeneration(
+  participants: u8,
+  threshold: u8,
+  party_id: u8,
+  outgoing_sender: OutgoingChannel,
+  incoming_receiver: IncomingChannel,
+) -> Result<(), CoreErrors> {
+  safe_keygeneration_with_config(
+    participants,
+    threshold,
+    party_id,
+    &ProtocolConfig::default(),
+    outgoing_sender,
+    incoming_receiver,
+  )
+}
+
+pub fn safe_keygeneration_with_config(
+  participants: u8,
+  threshold: u8,
+  party_id: u8,
+  config: &ProtocolConfig,
+  outgoing_sender: OutgoingChannel,
+  incoming_receiver: IncomingChannel,
+) -> Result<(), CoreErrors> {
+  safe_keygeneration_with_sink(
+    participants,
+    threshold,
+    party_id,
+    config,
+    &outgoing_sender,
+    incoming_receiver,
+  )
+}
+
+pub fn keygeneration_with_sink(
+  participants: u8,
+  threshold: u8,
+  party_id: u8,
+  config: &ProtocolConfig,
+  sink: &dyn EventSink,
+  incoming_receiver: IncomingChannel,
+) {
+  let result = safe_keygeneration_with_sink(
+    participants,
+    threshold,
+    party_id,
+    config,
+    sink,
+    incoming_receiver,
+  );
+  halt_on_error(sink, result);
+}
+
+/// Like `safe_keygeneration_with_config`, delivering everything the run
+/// emits to `sink` instead of a channel.
+pub fn safe_keygeneration_with_sink(
+  participants: u8,
+  threshold: u8,
+  party_id: u8,
+  config: &ProtocolConfig,
+  sink: &dyn EventSink,
+  incoming_receiver: IncomingChannel,
+) -> Result<(), CoreErrors> {
+  catch_panic("keygen", sink, || {
+    keygen_rounds(
+      participants,
+      threshold,
+      party_id,
+      config,
+      None,
+      sink,
+      incoming_receiver,
+    )
+  })
+}
+
+pub fn keygeneration_with_channels<S: ProtocolSender + Clone, R: ProtocolReceiver>(
+  participants: u8,
+  threshold: u8,
+  party_id: u8,
+  config: &ProtocolConfig,
+  outgoing_sender: S,
+  incoming_receiver: R,
+) {
+  let result = safe_keygeneration_with_channels(
+    participants,
+    threshold,
+    party_id,
+    config,
+    outgoing_sender.clone(),
+    incoming_receiver,
+  );
+  halt_on_error(&ChannelSink(outgoing_sender), result);
+}
+
+/// Like `safe_keygeneration_with_config`, over any channel implementation,
+/// e.g. crossbeam or tokio channels with the matching feature.
+pub fn safe_keygeneration_with_channels<S: ProtocolSender, R: ProtocolReceiver>(
+  participants: u8,
+  threshold: u8,
+  party_id: u8,
+  config: &ProtocolConfig,
+  outgoing_sender: S,
+  incoming_receiver: R,
+) -> Result<(), CoreErrors> {
+  let sink = ChannelSink(outgoing_sender);
+  catch_panic("keygen", &sink, || {
+    keygen_rounds(
+      participants,
+      threshold,
+      party_id,
+      config,
+      None,
+      &sink,
+      incoming_receiver,
+    )
+  })
+}
+
+pub fn keygeneration_with_attestation(
+  participants: u8,
+  threshold: u8,
+  party_id: u8,
+  config: &ProtocolConfig,
+  attestation: &dyn AttestationProvider,
+  outgoing_sender: OutgoingChannel,
+  incoming_receiver: IncomingChannel,
+) {
+  let result = safe_keygeneration_with_attestation(
+    participants,
+    threshold,
+    party_id,
+    config,
+    attestation,
+    outgoing_sender.clone(),
+    incoming_receiver,
+  );
+  halt_on_error(&outgoing_sender, result);
+}
+
+/// Like `safe_keygeneration_with_config`, for ceremonies inside a TEE: the
+/// round-1 broadcast carries an attestation quote over this party's
+/// commitment and Paillier key, and every peer's quote has to verify
+/// before the run goes on. All parties have to run the attested variant.
+pub fn safe_keygeneration_with_attestation(
+  participants: u8,
+  threshold: u8,
+  party_id: u8,
+  config: &ProtocolConfig,
+  attestation: &dyn AttestationProvider,
+  outgoing_sender: OutgoingChannel,
+  incoming_receiver: IncomingChannel,
+) -> Result<(), CoreErrors> {
+  catch_panic("keygen", &outgoing_sender, || {
+    keygen_rounds(
+      participants,
+      threshold,
+      party_id,
+      config,
+      Some(attestation),
+      &outgoing_sender,
+      incoming_receiver,
+    )
+  })
+}
+
+#[allow(deprecated)]
+fn keygen_rounds<R: ProtocolReceiver>(
+  participants: u8,
+  threshold: u8,
+  party_id: u8,
+  config: &ProtocolConfig,
+  attestation: Option<&dyn AttestationProvider>,
+  sink: &dyn EventSink,
+  incoming_receiver: R,
+) -> Result<(), CoreErrors> {
+  check_keygen_request(participants, threshold, party_id)?;
+  let version = config.protocol_version;
+  let runner =
+    RoundRunner::new(party_id, participants, sink, incoming_receiver).with_config(config);
+  let parties: u16 = participants as u16;
+  let threshold: u16 = threshold as u16;
+
+  let params = Parameters {
+    threshold: threshold,
+    share_count: parties,
+  };
+
+  let party_num_int = (party_id + 1) as u16;
+  let party_keys = Keys::create(party_num_int as usize);
+  let (bc_i, decom_i) = party_keys.phase1_broadcast_phase3_proof_of_correct_key();
+
+  let bc1_vec = match attestation {
+    None => runner.exchange(RoundId::KeyGen(1), bc_i, MessageData::KeyGenRound1)?,
+    Some(provider) => {
+      let quote = provider.attest(&attested_data(party_id, &bc_i));
+      let attested = AttestedBroadcast {
+        broadcast: bc_i,
+        quote,
+      };
+      let round_1 = runner.exchange(
+        RoundId::KeyGen(1),
+        attested,
+        MessageData::KeyGenRound1Attested,
+      )?;
+      for (i, m) in round_1.iter().enumerate() {
+        if i != party_id as usize
+          && !provider.verify(&m.quote, &attested_data(i as u8, &m.broadcast))
+        {
+          return Err(
+            CoreErrors::execution(CryptoKind::Attestation, RoundId::KeyGen(1)).with_party(i as u8),
+          );
+        }
+      }
+      round_1.into_iter().map(|m| m.broadcast).collect()
+    }
+  };
+  let decom_vec = runner.exchange(RoundId::KeyGen(2), decom_i, MessageData::KeyGenRound2)?;
+
+  let point_vec: Vec<GE> = decom_vec.iter().map(|d| d.y_i).collect();
+  let enc_keys: Vec<BigInt> = decom_vec
+    .iter()
+    .enumerate()
+    .filter(|(k, _)| *k != party_id as usize)
+    .map(|(k, d)| {
+      (d.y_i * party_keys.u_i).x_coor().ok_or_else(|| {
+        CoreErrors::execution(
+          CryptoKind::InvalidKey("shared key is the point at infinity".to_string()),
+          RoundId::KeyGen(2),
+        )
+        .with_party(k as u8)
+      })
+    })
+    .collect::<Result<Vec<BigInt>, CoreErrors>>()?;
+
+  let (head, tail) = point_vec.split_at(1);
+  let y_sum = tail.iter().fold(head[0], |acc, x| acc + x);
+
+  let (vss_scheme, secret_shares, _index) = party_keys
+    .phase1_verify_com_phase3_verify_correct_key_phase2_distribute(&params, &decom_vec, &bc1_vec)
+    .map_err(|e| {
+      CoreErrors::execution(
+        CryptoKind::InvalidKey(format!("{:?}", e)),
+        RoundId::KeyGen(2),
+      )
+    })?;
+
+  let mut j = 0;
+  for (k, i) in (1..=parties).enumerate() {
+    if i != party_num_int {
+      // prepare encrypted ss for party i:
+      let key_i = round3_key(version, &enc_keys[j], party_id, k as u8);
+      let plaintext = encode_share(version, &secret_shares[k]);
+      let data = if version >= ProtocolVersion::V5 {
+        MessageData::KeyGenRound3Sealed(aes_encrypt(&key_i, &plaintext)?)
+      } else {
+        MessageData::KeyGenRound3(
+          aes_encrypt_with_nonce(&key_i, LEGACY_NONCE, &plaintext)?.into_legacy()?,
+        )
+      };
+      runner.log(format!("Sending round 3 to {}", k))?;
+      runner.send(k as u8, &data)?;
+
+      j += 1;
+    }
+  }
+
+  runner.log("Collecting round 3")?;
+  // Legacy packs that don't convert fail below, blamed on their sender.
+  let encrypted: Vec<Result<SealedData, CoreErrors>> = if version >= ProtocolVersion::V5 {
+    let sealed = runner.collect_others::<SealedData>(RoundId::KeyGen(3))?;
+    sealed.into_iter().map(Ok).collect()
+  } else {
+    let legacy = runner.collect_others::<AEAD>(RoundId::KeyGen(3))?;
+    legacy.into_iter().map(SealedData::try_from).collect()
+  };
+  let mut encrypted = encrypted.into_iter();
+
+  let mut j = 0;
+  let mut party_shares: Vec<FE> = Vec::new();
+  for i in 1..=parties {
+    if i == party_num_int {
+      party_shares.push(secret_shares[(i - 1) as usize]);
+    } else {
+      let key_i = round3_key(version, &enc_keys[j], (i - 1) as u8, party_id);
+      let out_fe = encrypted
+        .next()
+        .unwrap_or_else(|| Err(CoreErrors::invalid_data(ValidationKind::EmptyResult)))
+        .and_then(|sealed| aes_decrypt(&key_i, sealed))
+        .and_then(|out| decode_share(version, &out))
+        .map_err(|e| e.in_round(RoundId::KeyGen(3)).with_party((i - 1) as u8))?;
+      party_shares.push(out_fe);
+
+      j += 1;
+    }
+  }
+
+  let vss_scheme_vec =
+    runner.exchange(RoundId::KeyGen(4), vss_scheme, MessageData::KeyGenRound4)?;
+
+  for (i, vss) in vss_scheme_vec.iter().enumerate() {
+    check_vss_shape(vss, threshold, parties, Some(&point_vec[i]))
+      .map_err(|e| e.in_round(RoundId::KeyGen(4)).with_party(i as u8))?;
+  }
+
+  for (i, (vss, share)) in vss_scheme_vec.iter().zip(party_shares.iter()).enumerate() {
+    verify_vss_share(vss, share, party_num_int as usize)
+      .map_err(|e| e.in_round(RoundId::KeyGen(4)).with_party(i as u8))?;
+  }
+
+  let (shared_keys, dlog_proof) = party_keys
+    .phase2_verify_vss_construct_keypair_phase3_pok_dlog(
+      &params,
+      &point_vec,
+      &party_shares,
+      &vss_scheme_vec,
+      party_num_int as usize,
+    )
+    .map_err(|e| {
+      CoreErrors::execution(
+        CryptoKind::InvalidVss(format!("{:?}", e)),
+        RoundId::KeyGen(4),
+      )
+    })?;
+
+  let dlog_proof_vec =
+    runner.exchange(RoundId::KeyGen(5), dlog_proof, MessageData::KeyGenRound5)?;
+
+  Keys::verify_dlog_proofs(&params, &dlog_proof_vec, &point_vec).map_err(|e| {
+    CoreErrors::execution(
+      CryptoKind::DLogProof(format!("{:?}", e)),
+      RoundId::KeyGen(5),
+    )
+  })?;
+
+  let paillier_key_vec = (0..parties)
+    .map(|i| bc1_vec[i as usize].e.clone())
+    .collect::<Vec<EncryptionKey>>();
+
+  let keystore = Keystore {
+    params: KeystoreParameters {
+      threshold: Threshold::new(params.threshold as u16),
+      share_count: params.share_count as u16,
+    },
+    party_key: party_keys,
+    party_shares,
+    shared_keys,
+    party_index: party_id as usize,
+    vss_scheme_vec,
+    paillier_key_vec,
+    y_sum,
+  };
+  let report = ceremony_report(&keystore, &bc1_vec, version);
+  runner.finish(Message::make_complete_keygen_with_report(&keystore, report))
+}
+
+// Checks a run has passed by the time it completes, in the order they ran.
+const CEREMONY_CHECKS: [&str; 5] = [
+  "commitments",
+  "paillier_key_proofs",
+  "vss_shapes",
+  "vss_shares",
+  "dlog_proofs",
+];
+
+#[allow(deprecated)]
+fn ceremony_report(
+  keystore: &Keystore,
+  bc1_vec: &[KeyGenBroadcastMessage1],
+  version: ProtocolVersion,
+) -> CeremonyReport {
+  let commitment_hashes: Vec<Digest32> = bc1_vec
+    .iter()
+    .map(|bc| hash(HashAlg::Sha256, &BigInt::to_vec(&bc.com)))
+    .collect();
+  let ids: Vec<u8> = commitment_hashes
+    .iter()
+    .flat_map(|h| h.as_bytes().to_vec())
+    .collect();
+
+  CeremonyReport {
+    ceremony_id: hash(HashAlg::Sha256, &ids),
+    params: keystore.params.clone(),
+    parties: (0..keystore.share_count() as u8).collect(),
+    public_key: keystore.public_key(),
+    fingerprint: bigint_to_hex(&keystore.public().fingerprint()),
+    commitment_hashes,
+    protocol_version: version,
+    checks: CEREMONY_CHECKS.iter().map(|c| c.to_string()).collect(),
+  }
+}
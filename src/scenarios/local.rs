@@ -0,0 +1,175 @@
+use crate::common::messages::*;
+use crate::common::types::{Digest32, Keystore, Signature};
+use crate::errors::{CoreErrors, ValidationKind};
+use crate::scenarios::{safe_keygeneration, safe_sign};
+use std::sync::mpsc::channel;
+use std::sync::Arc;
+use std::thread;
+
+// Runs one protocol instance per party on its own thread and routes their
+// messages in-process. `observe` sees every message, with its sender and
+// target, before it is delivered. Parties that ended without reporting a
+// result, like health check responders, are `None`.
+pub(crate) fn run_parties<F, O>(
+  participants: u8,
+  party: F,
+  mut observe: O,
+) -> Result<Vec<Option<RoundResult>>, CoreErrors>
+where
+  F: Fn(u8, OutgoingChannel, IncomingChannel) -> Result<(), CoreErrors> + Send + Sync + 'static,
+  O: FnMut(u8, u8, &MessageData),
+{
+  let party = Arc::new(party);
+  let (routed_sender, routed_receiver) = channel::<(u8, Message)>();
+  let mut incoming_senders = vec![];
+  let mut handles = vec![];
+
+  for party_id in 0..participants {
+    let (outgoing_sender, outgoing_receiver) = channel::<Message>();
+    let (incoming_sender, incoming_receiver) = channel::<Message>();
+    incoming_senders.push(incoming_sender);
+
+    let routed_sender = routed_sender.clone();
+    thread::spawn(move || {
+      for msg in outgoing_receiver {
+        if routed_sender.send((party_id, msg)).is_err() {
+          return;
+        }
+      }
+    });
+
+    let party = party.clone();
+    handles.push(thread::spawn(move || {
+      party(party_id, outgoing_sender, incoming_receiver)
+    }));
+  }
+  drop(routed_sender);
+
+  let mut results: Vec<Option<RoundResult>> = vec![None; participants as usize];
+  for (party_id, msg) in routed_receiver {
+    if let Message::Complete(result) = msg {
+      results[party_id as usize] = Some(result);
+      continue;
+    }
+    if let Message::Send {
+      sender,
+      target,
+      data,
+      ..
+    } = &msg
+    {
+      observe(*sender, *target, data);
+      if let Some(incoming_sender) = incoming_senders.get(*target as usize) {
+        // The target may already have halted; its own result reports why.
+        let _ = incoming_sender.send(msg);
+      }
+    }
+  }
+
+  for handle in handles {
+    handle.join().expect("local party panicked")?;
+  }
+  Ok(results)
+}
+
+pub(crate) fn run_keygen_observed<O>(
+  participants: u8,
+  threshold: u8,
+  observe: O,
+) -> Result<Vec<Keystore>, CoreErrors>
+where
+  O: FnMut(u8, u8, &MessageData),
+{
+  let results = run_parties(
+    participants,
+    move |party_id, outgoing_sender, incoming_receiver| {
+      safe_keygeneration(
+        participants,
+        threshold,
+        party_id,
+        outgoing_sender,
+        incoming_receiver,
+      )
+    },
+    observe,
+  )?;
+
+  results
+    .iter()
+    .map(|r| {
+      r.as_ref()
+        .and_then(|r| r.as_keystore())
+        .cloned()
+        .ok_or(CoreErrors::invalid_data(ValidationKind::EmptyResult))
+    })
+    .collect()
+}
+
+/// Runs a full key generation for every party inside this process and
+/// returns the keystores ordered by party index.
+pub fn run_local_keygen(participants: u8, threshold: u8) -> Result<Vec<Keystore>, CoreErrors> {
+  run_keygen_observed(participants, threshold, |_, _, _| {})
+}
+
+pub(crate) fn run_sign_observed<O>(
+  keystores: &[Keystore],
+  signers: &[usize],
+  digest: &Digest32,
+  observe: O,
+) -> Result<Vec<Signature>, CoreErrors>
+where
+  O: FnMut(u8, u8, &MessageData),
+{
+  let signer_keystores = signers
+    .iter()
+    .map(|i| {
+      keystores.get(*i).cloned().ok_or_else(|| {
+        CoreErrors::invalid_data(ValidationKind::Malformed(format!(
+          "No keystore for signer {}",
+          i
+        )))
+      })
+    })
+    .collect::<Result<Vec<Keystore>, CoreErrors>>()?;
+  let participants = signers.len() as u8;
+  let signers = signers.to_vec();
+  let digest = *digest;
+
+  let results = run_parties(
+    participants,
+    move |party_id, outgoing_sender, incoming_receiver| {
+      let keystore = &signer_keystores[party_id as usize];
+      safe_sign(
+        participants,
+        keystore.threshold().get() as u8,
+        party_id,
+        keystore,
+        &digest,
+        &signers,
+        outgoing_sender,
+        incoming_receiver,
+      )
+    },
+    observe,
+  )?;
+
+  results
+    .iter()
+    .map(|r| {
+      r.as_ref()
+        .and_then(|r| r.as_signature())
+        .cloned()
+        .ok_or(CoreErrors::invalid_data(ValidationKind::EmptyResult))
+    })
+    .collect()
+}
+
+/// Signs `digest` in-process with the keystores of `signers` (indices into
+/// `keystores`) and returns each signer's signature in signer order.
+pub fn run_local_sign(
+  keystores: &[Keystore],
+  signers: &[usize],
+  digest: &Digest32,
+) -> Result<Vec<Signature>, CoreErrors> {
+  run_sign_observed(keystores, signers, digest, |_, _, _| {})
+}
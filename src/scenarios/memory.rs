@@ -0,0 +1,112 @@
+use crate::common::types::RoundId;
+use crate::errors::CoreErrors;
+use crate::scenarios::local::run_keygen_observed;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+const SAMPLE_INTERVAL_MS: u64 = 5;
+
+/// Resident memory observed while running a local key generation.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryProfile {
+  pub peak_rss_bytes: usize,
+  pub final_rss_bytes: usize,
+  /// RSS change over each round, in round order. Round 1 includes the
+  /// Paillier key creation that precedes its first message.
+  pub per_round_delta: Vec<isize>,
+}
+
+#[cfg(target_os = "linux")]
+fn rss_bytes() -> Option<usize> {
+  let status = std::fs::read_to_string("/proc/self/status").ok()?;
+  let line = status.lines().find(|l| l.starts_with("VmRSS:"))?;
+  let kb: usize = line.split_whitespace().nth(1)?.parse().ok()?;
+  Some(kb * 1024)
+}
+
+#[cfg(target_os = "macos")]
+fn rss_bytes() -> Option<usize> {
+  let mut info: libc::mach_task_basic_info = unsafe { std::mem::zeroed() };
+  let mut count = libc::MACH_TASK_BASIC_INFO_COUNT;
+  let status = unsafe {
+    libc::task_info(
+      libc::mach_task_self(),
+      libc::MACH_TASK_BASIC_INFO,
+      &mut info as *mut _ as libc::task_info_t,
+      &mut count,
+    )
+  };
+  if status != libc::KERN_SUCCESS {
+    return None;
+  }
+  Some(info.resident_size as usize)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn rss_bytes() -> Option<usize> {
+  None
+}
+
+/// Runs a `threshold`-of-`participants` key generation in-process and
+/// reports how resident memory evolved. Figures are zero on platforms
+/// without an RSS source.
+///
+/// A failed key generation is returned as its error once the sampler has
+/// been stopped.
+pub fn keygen_memory_profile(participants: u8, threshold: u8) -> Result<MemoryProfile, CoreErrors> {
+  let sample = || rss_bytes().unwrap_or(0);
+
+  let running = Arc::new(AtomicBool::new(true));
+  let peak = Arc::new(AtomicUsize::new(sample()));
+  let sampler = {
+    let running = running.clone();
+    let peak = peak.clone();
+    thread::spawn(move || {
+      while running.load(Ordering::Relaxed) {
+        peak.fetch_max(sample(), Ordering::Relaxed);
+        thread::sleep(Duration::from_millis(SAMPLE_INTERVAL_MS));
+      }
+    })
+  };
+
+  let mut boundaries = vec![sample()];
+  let mut current: Option<RoundId> = None;
+  let keygen = run_keygen_observed(participants, threshold, |_, _, data| {
+    let round = data.round();
+    if round.is_some() && round != current {
+      if current.is_some() {
+        boundaries.push(sample());
+      }
+      current = round;
+    }
+  });
+
+  running.store(false, Ordering::Relaxed);
+  let _ = sampler.join();
+  keygen?;
+
+  let final_rss_bytes = sample();
+  boundaries.push(final_rss_bytes);
+  let per_round_delta = boundaries
+    .windows(2)
+    .map(|w| w[1] as isize - w[0] as isize)
+    .collect();
+
+  Ok(MemoryProfile {
+    peak_rss_bytes: peak.load(Ordering::Relaxed).max(final_rss_bytes),
+    final_rss_bytes,
+    per_round_delta,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn failed_keygen_is_returned_as_error() {
+    assert!(keygen_memory_profile(3, 3).is_err());
+  }
+}
@@ -0,0 +1,160 @@
+use crate::common::messages::*;
+use crate::errors::{CoreErrors, ValidationKind};
+use std::collections::HashMap;
+use std::sync::mpsc::{channel, Sender, TryRecvError};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+const ROUTE_POLL_MS: u64 = 10;
+
+/// Plays several party ids of one run in this process, e.g. two of the five
+/// shares of a keystore held by one host, which amounts to weighted signing.
+/// Messages between the local ids are handed over in memory and never reach
+/// the transport; the outside world sees a single pair of channels carrying
+/// only the traffic to and from the other hosts.
+#[derive(Debug, Clone)]
+pub struct MultiPartyRunner {
+  local_ids: Vec<u8>,
+}
+
+impl MultiPartyRunner {
+  /// `local_ids` are protocol party ids: keygen indices, or signer positions
+  /// for signing.
+  pub fn new(local_ids: Vec<u8>) -> Self {
+    MultiPartyRunner { local_ids }
+  }
+
+  pub fn local_ids(&self) -> &[u8] {
+    &self.local_ids
+  }
+
+  /// Runs `party` once per local id, each on its own thread, like a channel
+  /// entry point (`safe_keygeneration`, `safe_sign`, ...) would be run for a
+  /// single id. Traffic for other ids goes out through `outgoing_sender`, and
+  /// messages read from `incoming_receiver` are delivered by their target.
+  /// Logs and metrics of every local party are passed on; one `SendsIssued`
+  /// and `Quit` follow once all of them are done.
+  ///
+  /// Returns each local party's result in `local_ids` order, or the first
+  /// failure among them.
+  pub fn run<F>(
+    &self,
+    party: F,
+    outgoing_sender: OutgoingChannel,
+    incoming_receiver: IncomingChannel,
+  ) -> Result<Vec<(u8, RoundResult)>, CoreErrors>
+  where
+    F: Fn(u8, OutgoingChannel, IncomingChannel) -> Result<(), CoreErrors> + Send + Sync + 'static,
+  {
+    let party = Arc::new(party);
+    let (routed_sender, routed_receiver) = channel::<(u8, Message)>();
+    let mut local: HashMap<u8, Sender<Message>> = HashMap::new();
+    let mut handles = vec![];
+
+    for &party_id in &self.local_ids {
+      let (party_outgoing, party_outgoing_receiver) = channel::<Message>();
+      let (party_incoming, party_incoming_receiver) = channel::<Message>();
+      local.insert(party_id, party_incoming);
+
+      let routed_sender = routed_sender.clone();
+      thread::spawn(move || {
+        for msg in party_outgoing_receiver {
+          if routed_sender.send((party_id, msg)).is_err() {
+            return;
+          }
+        }
+      });
+
+      let party = party.clone();
+      handles.push((
+        party_id,
+        thread::spawn(move || party(party_id, party_outgoing, party_incoming_receiver)),
+      ));
+    }
+    drop(routed_sender);
+
+    let mut results: HashMap<u8, RoundResult> = HashMap::new();
+    let mut incoming_open = true;
+    loop {
+      let mut idle = true;
+
+      match routed_receiver.try_recv() {
+        Ok((party_id, msg)) => {
+          idle = false;
+          match msg {
+            Message::Send { target, .. } => match local.get(&target) {
+              // The target may already have halted; its own result reports
+              // why.
+              Some(incoming) => {
+                let _ = incoming.send(msg);
+              }
+              None => {
+                let _ = outgoing_sender.send(msg);
+              }
+            },
+            Message::Complete(result) => {
+              results.insert(party_id, result);
+            }
+            Message::Quit | Message::SendsIssued => {}
+            other => {
+              let _ = outgoing_sender.send(other);
+            }
+          }
+        }
+        Err(TryRecvError::Disconnected) => break,
+        Err(TryRecvError::Empty) => {}
+      }
+
+      if incoming_open {
+        match incoming_receiver.try_recv() {
+          Ok(msg) => {
+            idle = false;
+            match msg.relay_target() {
+              RelayDecision::To(target) => match local.get(&target) {
+                Some(incoming) => {
+                  let _ = incoming.send(msg);
+                }
+                None => {
+                  let _ = outgoing_sender.send(Message::Log(format!(
+                    "Dropped message for party {}, not played here",
+                    target
+                  )));
+                }
+              },
+              _ => {
+                let _ = outgoing_sender.send(Message::Log(format!("Dropped {}", msg)));
+              }
+            }
+          }
+          Err(TryRecvError::Disconnected) => incoming_open = false,
+          Err(TryRecvError::Empty) => {}
+        }
+      }
+
+      if idle {
+        thread::sleep(Duration::from_millis(ROUTE_POLL_MS));
+      }
+    }
+
+    for (party_id, handle) in handles {
+      handle
+        .join()
+        .expect("local party panicked")
+        .map_err(|e| e.with_party(party_id))?;
+    }
+    let _ = outgoing_sender.send(Message::SendsIssued);
+    let _ = outgoing_sender.send(Message::Quit);
+
+    self
+      .local_ids
+      .iter()
+      .map(|id| {
+        results
+          .remove(id)
+          .map(|r| (*id, r))
+          .ok_or_else(|| CoreErrors::invalid_data(ValidationKind::EmptyResult).with_party(*id))
+      })
+      .collect()
+  }
+}
@@ -0,0 +1,228 @@
+use crate::common::messages::*;
+use crate::common::types::{Digest32, Keystore, RoundId, SchnorrSignature};
+use crate::errors::{CoreErrors, CryptoKind};
+use crate::scenarios::runner::{catch_panic, halt_on_error, RoundRunner};
+use crate::scenarios::sign::check_sign_request;
+use curv::{
+  cryptographic_primitives::hashing::{hash_sha256::HSha256, traits::Hash},
+  elliptic::curves::traits::{ECPoint, ECScalar},
+  BigInt, FE, GE,
+};
+use multi_party_ecdsa::protocols::multi_party_ecdsa::gg_2018::party_i::Keys;
+
+// FROST-style binding factor `rho_i = H(i, m, {(j, D_j, E_j)})` for the
+// signer with keystore index `index`. It covers every signer's commitments,
+// so changing any one of them changes every signer's factor and nobody can
+// steer the final `R`. Indices are hashed 1-based, like the VSS points.
+fn binding_factor(
+  index: usize,
+  digest: &Digest32,
+  signers: &[usize],
+  nonces: &[SchnorrNonces],
+) -> FE {
+  let mut input = vec![BigInt::from(index as u64 + 1), digest.to_bigint()];
+  for (j, nonce) in signers.iter().zip(nonces) {
+    input.push(BigInt::from(*j as u64 + 1));
+    input.push(nonce.d.bytes_compressed_to_big_int());
+    input.push(nonce.e.bytes_compressed_to_big_int());
+  }
+  let refs: Vec<&BigInt> = input.iter().collect();
+  ECScalar::from(&HSha256::create_hash(&refs))
+}
+
+fn sum(points: &[GE]) -> GE {
+  let (head, tail) = points.split_at(1);
+  tail.iter().fold(head[0], |acc, p| acc + p)
+}
+
+pub fn sign_with_threshold_schnorr(
+  participants: u8,
+  threshold: u8,
+  party_num_id: u8,
+  keystore: &Keystore,
+  digest: &Digest32,
+  signers_vec: &Vec<usize>,
+  outgoing_sender: OutgoingChannel,
+  incoming_receiver: IncomingChannel,
+) {
+  let result = safe_sign_with_threshold_schnorr(
+    participants,
+    threshold,
+    party_num_id,
+    keystore,
+    digest,
+    signers_vec,
+    outgoing_sender.clone(),
+    incoming_receiver,
+  );
+  halt_on_error(&outgoing_sender, result);
+}
+
+/// Schnorr signature over the keystore's shared key in two rounds, using
+/// MuSig2's two-nonce signing. The key is already a single Feldman-shared
+/// secret, so there is no MuSig2 key aggregation: each of the `threshold + 1`
+/// signers weights its share `x_i` by its Lagrange coefficient instead.
+///
+/// 1. every signer broadcasts nonce points `D_i = d_i * G`, `E_i = e_i * G`;
+/// 2. with `rho_i = H(i, m, {(j, D_j, E_j)})`, `R = sum(D_j + rho_j * E_j)`
+///    and `c = H(R, Y, m)`, every signer broadcasts
+///    `s_i = d_i + rho_i * e_i + c * l_i * x_i`.
+///
+/// Each `s_i` is checked against the signer's public share, so a bad partial
+/// signature aborts naming its sender. Nonces are fresh per run and never
+/// leave this function.
+pub fn safe_sign_with_threshold_schnorr(
+  participants: u8,
+  threshold: u8,
+  party_num_id: u8,
+  keystore: &Keystore,
+  digest: &Digest32,
+  signers_vec: &Vec<usize>,
+  outgoing_sender: OutgoingChannel,
+  incoming_receiver: IncomingChannel,
+) -> Result<(), CoreErrors> {
+  let sink = outgoing_sender.clone();
+  catch_panic("schnorr_sign", &sink, || {
+    schnorr_sign_rounds(
+      participants,
+      threshold,
+      party_num_id,
+      keystore,
+      digest,
+      signers_vec,
+      outgoing_sender,
+      incoming_receiver,
+    )
+  })
+}
+
+#[allow(deprecated)]
+fn schnorr_sign_rounds(
+  participants: u8,
+  threshold: u8,
+  party_num_id: u8,
+  keystore: &Keystore,
+  digest: &Digest32,
+  signers_vec: &Vec<usize>,
+  outgoing_sender: OutgoingChannel,
+  incoming_receiver: IncomingChannel,
+) -> Result<(), CoreErrors> {
+  check_sign_request(participants, threshold, party_num_id, keystore, signers_vec)?;
+  let runner = RoundRunner::new(
+    party_num_id,
+    participants,
+    &outgoing_sender,
+    incoming_receiver,
+  );
+
+  runner.log("Start threshold Schnorr signing")?;
+
+  let g: GE = ECPoint::generator();
+  let y = *keystore.public_key().as_point();
+  let public_shares = Keys::get_commitments_to_xi(&keystore.vss_scheme_vec);
+  let lagrange = |i: usize| {
+    keystore.vss_scheme_vec[signers_vec[i]].map_share_to_new_params(signers_vec[i], signers_vec)
+  };
+
+  let d_i: FE = ECScalar::new_random();
+  let e_i: FE = ECScalar::new_random();
+  let nonces = SchnorrNonces {
+    d: g * d_i,
+    e: g * e_i,
+  };
+  let nonces_vec = runner.exchange(
+    RoundId::SchnorrSign(1),
+    nonces,
+    MessageData::SchnorrSignRound1,
+  )?;
+
+  let rho: Vec<FE> = signers_vec
+    .iter()
+    .map(|&j| binding_factor(j, digest, signers_vec, &nonces_vec))
+    .collect();
+  let r = sum(
+    &nonces_vec
+      .iter()
+      .zip(&rho)
+      .map(|(n, rho_j)| n.d + &(n.e * *rho_j))
+      .collect::<Vec<GE>>(),
+  );
+  let c = SchnorrSignature::challenge(&r, &y, digest);
+
+  let own = party_num_id as usize;
+  let s_i = d_i + rho[own] * e_i + c * lagrange(own) * keystore.shared_keys.x_i;
+  let partials = runner.exchange(
+    RoundId::SchnorrSign(2),
+    SchnorrPartial { s: s_i },
+    MessageData::SchnorrSignRound2,
+  )?;
+
+  for (i, partial) in partials.iter().enumerate() {
+    let nonce = &nonces_vec[i];
+    let expected =
+      nonce.d + &(nonce.e * rho[i]) + &(public_shares[signers_vec[i]] * (c * lagrange(i)));
+    if g * partial.s != expected {
+      return Err(
+        CoreErrors::execution(
+          CryptoKind::SignatureVerification("invalid partial Schnorr signature".to_string()),
+          RoundId::SchnorrSign(2),
+        )
+        .with_party(i as u8),
+      );
+    }
+  }
+
+  let (head, tail) = partials.split_at(1);
+  let s = tail.iter().fold(head[0].s, |acc, p| acc + p.s);
+  let sig = SchnorrSignature::new(r, s);
+  if !sig.verify(&keystore.public_key(), digest) {
+    return Err(CoreErrors::execution(
+      CryptoKind::SignatureVerification("aggregate Schnorr signature".to_string()),
+      RoundId::SchnorrSign(2),
+    ));
+  }
+
+  runner.finish(Message::make_complete_schnorr_signature(sig))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn any_changed_commitment_changes_every_binding_factor() {
+    let g: GE = ECPoint::generator();
+    let digest = Digest32::from_bytes(&[0x42; 32]).unwrap();
+    let signers = vec![0, 2, 3];
+    let nonces: Vec<SchnorrNonces> = signers
+      .iter()
+      .map(|_| SchnorrNonces {
+        d: g * FE::new_random(),
+        e: g * FE::new_random(),
+      })
+      .collect();
+    let factors = |nonces: &[SchnorrNonces]| {
+      signers
+        .iter()
+        .map(|&j| binding_factor(j, &digest, &signers, nonces))
+        .collect::<Vec<FE>>()
+    };
+    let original = factors(&nonces);
+
+    for k in 0..nonces.len() {
+      for &replace_d in &[true, false] {
+        let mut tampered = nonces.clone();
+        let fresh = g * FE::new_random();
+        if replace_d {
+          tampered[k].d = fresh;
+        } else {
+          tampered[k].e = fresh;
+        }
+        let changed = factors(&tampered);
+        for i in 0..signers.len() {
+          assert_ne!(changed[i], original[i], "signer {} after changing {}", i, k);
+        }
+      }
+    }
+  }
+}
@@ -0,0 +1,47 @@
+use crate::common::types::RoundId;
+use crate::errors::{CoreErrors, CryptoKind};
+use crypto::{digest::Digest, sha2::Sha256};
+use curv::{arithmetic::traits::Converter, elliptic::curves::traits::ECScalar, BigInt, FE};
+use std::collections::HashSet;
+
+/// Signing nonces `k_i` this party has used, to catch a reuse (from an RNG
+/// failure or a bug) before it leaks the key share. Only SHA-256 hashes of
+/// the nonces are kept, so the registry itself holds nothing secret.
+///
+/// Share one registry between all signing runs of a keystore, e.g. behind a
+/// `Mutex`, and hand it to `SignHooks::with_nonce_registry`. It only covers
+/// the runs it has seen, so it is a paranoid extra check, not a guarantee.
+#[derive(Debug, Default)]
+pub struct NonceRegistry {
+  used_nonces: HashSet<[u8; 32]>,
+}
+
+impl NonceRegistry {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Records `nonce`, failing if it was recorded before.
+  pub fn check_and_register(&mut self, nonce: &FE) -> Result<(), CoreErrors> {
+    let mut hasher = Sha256::new();
+    hasher.input(&BigInt::to_vec(&nonce.to_big_int()));
+    let mut hash = [0u8; 32];
+    hasher.result(&mut hash);
+
+    if !self.used_nonces.insert(hash) {
+      return Err(CoreErrors::execution(
+        CryptoKind::NonceReuse,
+        RoundId::Sign(1),
+      ));
+    }
+    Ok(())
+  }
+
+  pub fn len(&self) -> usize {
+    self.used_nonces.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.used_nonces.is_empty()
+  }
+}
@@ -0,0 +1,113 @@
+use crate::common::types::RoundId;
+use crate::errors::{CoreErrors, ValidationKind};
+use std::collections::BTreeMap;
+use std::iter::FromIterator;
+
+/// Values of one signing phase keyed by signer position, i.e. the index into
+/// `signers_vec`, which is also the party id on the wire. Replaces vectors
+/// kept aligned by hand; iteration is always in position order, which is the
+/// order gg_2018 expects its vectors in.
+#[derive(Debug, Clone)]
+pub(crate) struct PerSigner<T> {
+  values: BTreeMap<usize, T>,
+}
+
+impl<T> PerSigner<T> {
+  /// Everyone's value, own included, as returned by `RoundRunner::exchange`.
+  pub(crate) fn from_all(values: Vec<T>) -> Self {
+    values.into_iter().enumerate().collect()
+  }
+
+  /// The other signers' values in position order, as returned by
+  /// `RoundRunner::collect_others`.
+  pub(crate) fn from_others(values: Vec<T>, own: usize) -> Self {
+    values
+      .into_iter()
+      .enumerate()
+      .map(|(k, v)| (if k < own { k } else { k + 1 }, v))
+      .collect()
+  }
+
+  pub(crate) fn get(&self, position: usize) -> Option<&T> {
+    self.values.get(&position)
+  }
+
+  /// Like `get`, but a missing position is an error of `round` instead of a
+  /// panic, for lookups keyed by data a peer sent.
+  pub(crate) fn require(&self, position: usize, round: RoundId) -> Result<&T, CoreErrors> {
+    self.get(position).ok_or_else(|| {
+      CoreErrors::invalid_data(ValidationKind::Malformed(format!(
+        "No value for signer {}",
+        position
+      )))
+      .in_round(round)
+    })
+  }
+
+  pub(crate) fn all(&self) -> impl Iterator<Item = (usize, &T)> {
+    self.values.iter().map(|(i, v)| (*i, v))
+  }
+
+  pub(crate) fn others(&self, own: usize) -> impl Iterator<Item = (usize, &T)> {
+    self.all().filter(move |(i, _)| *i != own)
+  }
+
+  pub(crate) fn map<U, F: FnMut(&T) -> U>(&self, mut f: F) -> PerSigner<U> {
+    self.all().map(|(i, v)| (i, f(v))).collect()
+  }
+
+  /// Fails unless exactly `expected` positions hold a value; the phase
+  /// boundary check between rounds.
+  pub(crate) fn expect_keys(&self, expected: &[usize], round: RoundId) -> Result<(), CoreErrors> {
+    if self.values.keys().eq(expected.iter()) {
+      return Ok(());
+    }
+    Err(
+      CoreErrors::invalid_data(ValidationKind::Malformed(format!(
+        "Values for signers {:?}, expected {:?}",
+        self.values.keys().collect::<Vec<_>>(),
+        expected
+      )))
+      .in_round(round),
+    )
+  }
+}
+
+impl<T: Clone> PerSigner<T> {
+  /// Every value in position order.
+  pub(crate) fn values(&self) -> Vec<T> {
+    self.values.values().cloned().collect()
+  }
+
+  /// The other signers' values in position order.
+  pub(crate) fn others_values(&self, own: usize) -> Vec<T> {
+    self.others(own).map(|(_, v)| v.clone()).collect()
+  }
+}
+
+impl<T> FromIterator<(usize, T)> for PerSigner<T> {
+  fn from_iter<I: IntoIterator<Item = (usize, T)>>(iter: I) -> Self {
+    PerSigner {
+      values: iter.into_iter().collect(),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn require_reports_a_missing_signer_in_its_round() {
+    let values = PerSigner::from_others(vec!["a", "c"], 1);
+    assert_eq!(values.require(2, RoundId::Sign(1)).unwrap(), &"c");
+    assert!(matches!(
+      values.require(1, RoundId::Sign(4)),
+      Err(CoreErrors::InvalidData {
+        kind: ValidationKind::Malformed(_),
+        round: Some(RoundId::Sign(4)),
+        ..
+      })
+    ));
+  }
+}
@@ -0,0 +1,44 @@
+use crate::common::types::Digest32;
+use std::collections::BTreeMap;
+
+/// What a signing party is asked to take part in.
+#[derive(Debug, Clone, Copy)]
+pub struct SigningRequest<'a> {
+  pub digest: &'a Digest32,
+  pub context: Option<&'a [u8]>,
+  pub signers: &'a [usize],
+  pub session_id: Option<&'a [u8]>,
+  /// Whatever the caller passed along, e.g. the amount and destination the
+  /// digest commits to.
+  pub metadata: &'a BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PolicyDecision {
+  /// Take part; `annotations` are logged with the run and recorded in
+  /// `RoundResult::Sign`.
+  Approve {
+    annotations: Vec<String>,
+  },
+  Deny {
+    reason: String,
+  },
+}
+
+/// Business rules that gate participation in a signing run, evaluated before
+/// round 1. A panic inside `evaluate` fails the run like any other.
+pub trait SigningPolicy {
+  fn evaluate(&self, request: &SigningRequest) -> PolicyDecision;
+}
+
+/// Policy that approves every request.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ApproveAll;
+
+impl SigningPolicy for ApproveAll {
+  fn evaluate(&self, _request: &SigningRequest) -> PolicyDecision {
+    PolicyDecision::Approve {
+      annotations: vec![],
+    }
+  }
+}
@@ -0,0 +1,205 @@
+use crate::common::messages::MessageData;
+use crate::common::types::{Digest32, Keystore, RoundId, Signature};
+use crate::errors::CoreErrors;
+use crate::scenarios::local::{run_keygen_observed, run_sign_observed};
+use serde::Serialize;
+use std::io;
+use std::time::Instant;
+
+/// One round as seen by one party of a local simulation.
+#[derive(Debug, Clone, Serialize)]
+pub struct RoundTiming {
+  pub round: RoundId,
+  /// Time from the party's previous round's last message (or the start of
+  /// the run) to its last message of this round. The final round runs to
+  /// the end of the simulation.
+  pub wall_ms: f64,
+  pub messages: usize,
+  /// JSON-encoded size of the messages sent.
+  pub bytes: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PartyReport {
+  pub party: u8,
+  pub rounds: Vec<RoundTiming>,
+}
+
+/// One round across all parties.
+#[derive(Debug, Clone, Serialize)]
+pub struct RoundSummary {
+  pub round: RoundId,
+  pub p50_ms: f64,
+  pub p90_ms: f64,
+  pub max_ms: f64,
+  pub messages: usize,
+  pub bytes: usize,
+}
+
+/// Where a local simulation spent its time, per party and per round.
+#[derive(Debug, Clone, Serialize)]
+pub struct SimulationReport {
+  pub total_ms: f64,
+  pub parties: Vec<PartyReport>,
+  pub rounds: Vec<RoundSummary>,
+}
+
+struct ByteCounter(usize);
+
+impl io::Write for ByteCounter {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    self.0 += buf.len();
+    Ok(buf.len())
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    Ok(())
+  }
+}
+
+fn encoded_len(data: &MessageData) -> usize {
+  let mut counter = ByteCounter(0);
+  match serde_json::to_writer(&mut counter, data) {
+    Ok(()) => counter.0,
+    Err(_) => 0,
+  }
+}
+
+fn millis(from: Instant, to: Instant) -> f64 {
+  to.duration_since(from).as_secs_f64() * 1000.0
+}
+
+// Nearest-rank percentile of an ascending slice.
+fn percentile(sorted: &[f64], p: f64) -> f64 {
+  if sorted.is_empty() {
+    return 0.0;
+  }
+  let rank = ((p / 100.0) * sorted.len() as f64).ceil() as usize;
+  sorted[rank.max(1) - 1]
+}
+
+struct Recorder {
+  start: Instant,
+  // Per party, the rounds in the order it sent them: (round, last send,
+  // messages, bytes).
+  sends: Vec<Vec<(RoundId, Instant, usize, usize)>>,
+}
+
+impl Recorder {
+  fn new(participants: u8) -> Self {
+    Recorder {
+      start: Instant::now(),
+      sends: vec![vec![]; participants as usize],
+    }
+  }
+
+  fn observe(&mut self, sender: u8, data: &MessageData) {
+    let round = match data.round() {
+      Some(round) => round,
+      None => return,
+    };
+    let bytes = encoded_len(data);
+    let now = Instant::now();
+    let rounds = &mut self.sends[sender as usize];
+    match rounds.last_mut() {
+      Some(last) if last.0 == round => {
+        last.1 = now;
+        last.2 += 1;
+        last.3 += bytes;
+      }
+      _ => rounds.push((round, now, 1, bytes)),
+    }
+  }
+
+  fn finish(self) -> SimulationReport {
+    let end = Instant::now();
+    let start = self.start;
+
+    let parties: Vec<PartyReport> = self
+      .sends
+      .into_iter()
+      .enumerate()
+      .map(|(party, sends)| {
+        let count = sends.len();
+        let mut previous = start;
+        let rounds = sends
+          .into_iter()
+          .enumerate()
+          .map(|(i, (round, last, messages, bytes))| {
+            let until = if i + 1 == count { end } else { last };
+            let timing = RoundTiming {
+              round,
+              wall_ms: millis(previous, until),
+              messages,
+              bytes,
+            };
+            previous = last;
+            timing
+          })
+          .collect();
+        PartyReport {
+          party: party as u8,
+          rounds,
+        }
+      })
+      .collect();
+
+    let mut round_ids: Vec<RoundId> = parties
+      .iter()
+      .flat_map(|p| p.rounds.iter().map(|r| r.round))
+      .collect();
+    round_ids.sort();
+    round_ids.dedup();
+
+    let rounds = round_ids
+      .into_iter()
+      .map(|round| {
+        let timings: Vec<&RoundTiming> = parties
+          .iter()
+          .flat_map(|p| p.rounds.iter().filter(|r| r.round == round))
+          .collect();
+        let mut wall: Vec<f64> = timings.iter().map(|t| t.wall_ms).collect();
+        wall.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+        RoundSummary {
+          round,
+          p50_ms: percentile(&wall, 50.0),
+          p90_ms: percentile(&wall, 90.0),
+          max_ms: wall.last().copied().unwrap_or(0.0),
+          messages: timings.iter().map(|t| t.messages).sum(),
+          bytes: timings.iter().map(|t| t.bytes).sum(),
+        }
+      })
+      .collect();
+
+    SimulationReport {
+      total_ms: millis(start, end),
+      parties,
+      rounds,
+    }
+  }
+}
+
+/// `run_local_keygen` with a timing report of the run.
+pub fn run_local_keygen_with_report(
+  participants: u8,
+  threshold: u8,
+) -> Result<(Vec<Keystore>, SimulationReport), CoreErrors> {
+  let mut recorder = Recorder::new(participants);
+  let keystores = run_keygen_observed(participants, threshold, |sender, _, data| {
+    recorder.observe(sender, data)
+  })?;
+  Ok((keystores, recorder.finish()))
+}
+
+/// `run_local_sign` with a timing report of the run.
+pub fn run_local_sign_with_report(
+  keystores: &[Keystore],
+  signers: &[usize],
+  digest: &Digest32,
+) -> Result<(Vec<Signature>, SimulationReport), CoreErrors> {
+  let mut recorder = Recorder::new(signers.len() as u8);
+  let signatures = run_sign_observed(keystores, signers, digest, |sender, _, data| {
+    recorder.observe(sender, data)
+  })?;
+  Ok((signatures, recorder.finish()))
+}
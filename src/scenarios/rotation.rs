@@ -0,0 +1,137 @@
+use crate::common::messages::*;
+use crate::common::types::{Keystore, RoundId};
+use crate::errors::{CoreErrors, CryptoKind};
+use crate::scenarios::runner::{catch_panic, halt_on_error, RoundRunner};
+use curv::cryptographic_primitives::hashing::{hash_sha256::HSha256, traits::Hash};
+use curv::BigInt;
+use paillier::{DecryptionKey, EncryptionKey, KeyGeneration, Paillier};
+use zk_paillier::zkproofs::NICorrectKeyProof;
+
+pub fn rotate_paillier(
+  keystore: &Keystore,
+  fresh_keys: Option<(EncryptionKey, DecryptionKey)>,
+  outgoing_sender: OutgoingChannel,
+  incoming_receiver: IncomingChannel,
+) {
+  let result = safe_rotate_paillier(
+    keystore,
+    fresh_keys,
+    outgoing_sender.clone(),
+    incoming_receiver,
+  );
+  halt_on_error(&outgoing_sender, result);
+}
+
+/// Replaces every party's Paillier keypair while keeping the ECDSA shares.
+///
+/// All parties of the keystore take part. Round 1 exchanges the new
+/// encryption keys with proofs of correct construction, round 2 confirms that
+/// everyone accepted the same key vector, and round 3 commits to it: a party
+/// only sends its commit once every confirmation matched, and only emits the
+/// rotated keystore in `Complete` once it holds everyone's commit.
+///
+/// The switch is two-phase. Until `Complete`, the old keystore is the one to
+/// use; after it, the rotated one. Should a run end with some parties
+/// switched and others not (a commit lost on the way), signing between the
+/// two groups fails in round 1 with `RotationMismatch` rather than somewhere
+/// in the MtA. Running the rotation again from whichever keystore each party
+/// holds brings the group back in step, as it does not depend on the
+/// Paillier keys it replaces.
+pub fn safe_rotate_paillier(
+  keystore: &Keystore,
+  fresh_keys: Option<(EncryptionKey, DecryptionKey)>,
+  outgoing_sender: OutgoingChannel,
+  incoming_receiver: IncomingChannel,
+) -> Result<(), CoreErrors> {
+  let sink = outgoing_sender.clone();
+  catch_panic("paillier_rotation", &sink, || {
+    rotation_rounds(keystore, fresh_keys, outgoing_sender, incoming_receiver)
+  })
+}
+
+#[allow(deprecated)]
+fn rotation_rounds(
+  keystore: &Keystore,
+  fresh_keys: Option<(EncryptionKey, DecryptionKey)>,
+  outgoing_sender: OutgoingChannel,
+  incoming_receiver: IncomingChannel,
+) -> Result<(), CoreErrors> {
+  let participants = keystore.params.share_count as u8;
+  let party_id = keystore.party_index as u8;
+  let runner = RoundRunner::new(party_id, participants, &outgoing_sender, incoming_receiver);
+
+  let (ek, dk) = fresh_keys.unwrap_or_else(|| Paillier::keypair().keys());
+  let data = PaillierRotationData {
+    ek: ek.clone(),
+    proof: NICorrectKeyProof::proof(&dk),
+  };
+
+  let rotation_vec = runner.exchange(
+    RoundId::PaillierRotation(1),
+    data,
+    MessageData::PaillierRotationRound1,
+  )?;
+
+  for (i, rotation) in rotation_vec.iter().enumerate() {
+    if i != party_id as usize {
+      rotation.proof.verify(&rotation.ek).map_err(|e| {
+        CoreErrors::execution(
+          CryptoKind::PaillierKeyProof(format!("{:?}", e)),
+          RoundId::PaillierRotation(1),
+        )
+        .with_party(i as u8)
+      })?;
+    }
+  }
+
+  let paillier_key_vec = rotation_vec
+    .into_iter()
+    .map(|r| r.ek)
+    .collect::<Vec<EncryptionKey>>();
+  let keys_hash = paillier_keys_hash(&paillier_key_vec);
+  let confirm = PaillierRotationConfirm {
+    keys_hash: keys_hash.clone(),
+  };
+
+  let confirm_vec = runner.exchange(
+    RoundId::PaillierRotation(2),
+    confirm.clone(),
+    MessageData::PaillierRotationRound2,
+  )?;
+
+  if let Some(i) = confirm_vec.iter().position(|c| *c != confirm) {
+    return Err(
+      CoreErrors::execution(CryptoKind::RotationMismatch, RoundId::PaillierRotation(2))
+        .with_party(i as u8),
+    );
+  }
+
+  let commit = PaillierRotationCommit { keys_hash };
+  let commit_vec = runner.exchange(
+    RoundId::PaillierRotation(3),
+    commit.clone(),
+    MessageData::PaillierRotationRound3,
+  )?;
+
+  if let Some(i) = commit_vec.iter().position(|c| *c != commit) {
+    return Err(
+      CoreErrors::execution(CryptoKind::RotationMismatch, RoundId::PaillierRotation(3))
+        .with_party(i as u8),
+    );
+  }
+
+  let mut rotated = keystore.clone();
+  rotated.party_key.ek = ek;
+  rotated.party_key.dk = dk;
+  rotated.paillier_key_vec = paillier_key_vec;
+
+  runner.finish(Message::Complete(RoundResult::PaillierRotation {
+    private_key: rotated,
+  }))
+}
+
+// What parties compare to agree on a Paillier key vector, in the rotation
+// and at the start of every signing run.
+pub(crate) fn paillier_keys_hash(paillier_key_vec: &[EncryptionKey]) -> BigInt {
+  HSha256::create_hash(&paillier_key_vec.iter().map(|ek| &ek.n).collect::<Vec<_>>())
+}
@@ -0,0 +1,774 @@
+use crate::common::channel::ProtocolReceiver;
+use crate::common::config::ProtocolConfig;
+#[cfg(feature = "debug-dump")]
+use crate::common::dump::RoundDumper;
+use crate::common::events::{emit, EventSink, SinkClosed};
+use crate::common::messages::*;
+use crate::common::types::RoundId;
+use crate::errors::{CoreErrors, CryptoKind, TransportKind, ValidationKind};
+use crate::scenarios::telemetry;
+use std::any::Any;
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::fmt::Debug;
+use std::mem::discriminant;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{RecvTimeoutError, SendError};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+const COLLECT_TIMEOUT: Duration = Duration::from_millis(3000);
+// Longest single wait on the incoming channel; bounds how late the
+// deadline is noticed when nothing arrives.
+const COLLECT_POLL: Duration = Duration::from_millis(100);
+const FLUSH_TIMEOUT: Duration = Duration::from_millis(2000);
+
+fn panic_reason(payload: Box<dyn Any + Send>) -> String {
+  payload
+    .downcast_ref::<&str>()
+    .map(|s| s.to_string())
+    .or_else(|| payload.downcast_ref::<String>().cloned())
+    .unwrap_or_else(|| "no message".to_string())
+}
+
+// Hands `msg` to the sink, failing with `closed` if the sink is gone. A
+// panicking callback is caught here instead of unwinding through the rounds.
+fn deliver(sink: &dyn EventSink, msg: Message, closed: TransportKind) -> Result<(), TransportKind> {
+  match panic::catch_unwind(AssertUnwindSafe(|| emit(sink, msg))) {
+    Ok(Ok(())) => Ok(()),
+    Ok(Err(SinkClosed)) => Err(closed),
+    Err(payload) => Err(TransportKind::CallbackPanicked(panic_reason(payload))),
+  }
+}
+
+fn send(
+  sink: &dyn EventSink,
+  target: u8,
+  party_id: u8,
+  id_offset: u8,
+  data: &Arc<MessageData>,
+) -> Result<(), CoreErrors> {
+  let msg = Message::make_send_shared(party_id + id_offset, target + id_offset, data.clone());
+  let error_msg = format!("{}", msg);
+  deliver(sink, msg, TransportKind::SendFailed(error_msg)).map_err(|kind| {
+    let error = CoreErrors::transport(kind).with_party(target);
+    match data.round() {
+      Some(round) => error.in_round(round),
+      None => error,
+    }
+  })
+}
+
+fn log(sink: &dyn EventSink, msg: String) -> Result<(), CoreErrors> {
+  let msg = Message::Log(msg);
+  let error_msg = format!("{}", msg);
+  deliver(sink, msg, TransportKind::SendFailed(error_msg)).map_err(CoreErrors::transport)
+}
+
+fn complete(sink: &dyn EventSink, msg: Message, kind: TransportKind) -> Result<(), CoreErrors> {
+  deliver(sink, msg, kind).map_err(|kind| CoreErrors::TransportIssue {
+    kind,
+    round: None,
+    party: None,
+    source: Some(Box::new(SendError(()))),
+  })
+}
+
+/// Emits the result of a run followed by `Quit`.
+pub(crate) fn finish(sink: &dyn EventSink, result: Message) -> Result<(), CoreErrors> {
+  log(sink, "Send result".to_string())?;
+  complete(sink, result, TransportKind::ResultUndelivered)?;
+  quit(sink)
+}
+
+/// Marks the end of our sends, gives the transport a bounded chance to
+/// deliver them, then emits `Quit`. A flush that doesn't finish only logs a
+/// warning: our own result is already out.
+pub(crate) fn quit(sink: &dyn EventSink) -> Result<(), CoreErrors> {
+  complete(sink, Message::SendsIssued, TransportKind::QuitUndelivered)?;
+  let flushed =
+    panic::catch_unwind(AssertUnwindSafe(|| sink.flush(FLUSH_TIMEOUT))).unwrap_or(false);
+  if !flushed {
+    log(
+      sink,
+      format!(
+        "Warning: outgoing messages not confirmed delivered within {} ms",
+        FLUSH_TIMEOUT.as_millis()
+      ),
+    )?;
+  }
+  log(sink, "Send quit".to_string())?;
+  complete(sink, Message::Quit, TransportKind::QuitUndelivered)
+}
+
+/// Abort policy of the channel entry points: a failed run reports the error
+/// as a log line followed by the matching `Errors` signal and its
+/// classification.
+pub(crate) fn halt_on_error(sink: &dyn EventSink, result: Result<(), CoreErrors>) {
+  if let Err(e) = result {
+    let _ = deliver(
+      sink,
+      Message::Log(format!("Error: {}", e)),
+      TransportKind::Disconnected,
+    );
+    let _ = deliver(
+      sink,
+      Message::Error(Errors::from_core_error(&e)),
+      TransportKind::Disconnected,
+    );
+    let _ = deliver(
+      sink,
+      Message::Classification(e.classification()),
+      TransportKind::Disconnected,
+    );
+  }
+}
+
+// Where the run on this thread currently is; kept up to date by the
+// `RoundRunner`, so `catch_panic` can name the round and reach the peers
+// after the runner itself is gone.
+#[derive(Clone, Copy)]
+struct PanicSite {
+  party_id: u8,
+  participants: u8,
+  id_offset: u8,
+  round: Option<RoundId>,
+}
+
+thread_local! {
+  static PANIC_SITE: Cell<Option<PanicSite>> = Cell::new(None);
+  #[cfg(any(test, feature = "test-utils"))]
+  static INJECTED_PANIC: Cell<Option<RoundId>> = Cell::new(None);
+}
+
+/// Makes runs on the current thread panic when they start collecting
+/// `round`, until `None` is set again. Stands in for a panic in the crypto
+/// libraries; see `testing::malicious::panic_in_round`.
+#[cfg(any(test, feature = "test-utils"))]
+pub(crate) fn inject_panic(round: Option<RoundId>) {
+  INJECTED_PANIC.with(|injected| injected.set(round));
+}
+
+/// Runs the rounds of a `protocol`, turning a panic inside them (typically
+/// in the crypto libraries, on malformed data from a peer) into an error, so
+/// the failure reaches the caller instead of unwinding its thread. The error
+/// names the round the panic happened in, and the other parties are sent an
+/// abort through `sink` so they don't wait for us until they time out. Also
+/// where ceremonies are counted, see `telemetry`.
+///
+/// Asserting unwind safety is sound: everything the rounds mutate is either
+/// owned by the closure and dropped with it, or behind a `Mutex` (signing
+/// guard, nonce registry) whose poisoning callers already report as
+/// `CryptoKind::Panicked`. Panics in the sink are contained by `deliver`.
+pub(crate) fn catch_panic<T, F>(
+  protocol: &'static str,
+  sink: &dyn EventSink,
+  rounds: F,
+) -> Result<T, CoreErrors>
+where
+  F: FnOnce() -> Result<T, CoreErrors>,
+{
+  telemetry::ceremony_started(protocol);
+  PANIC_SITE.with(|site| site.set(None));
+  let result = panic::catch_unwind(AssertUnwindSafe(rounds)).unwrap_or_else(|payload| {
+    let site = PANIC_SITE.with(|site| site.get());
+    let round = site.and_then(|site| site.round);
+    if let Some(site) = site {
+      // The payload may quote secret data, so peers only learn the round.
+      let reason = match round {
+        Some(round) => format!("Panicked in {}", round),
+        None => "Panicked".to_string(),
+      };
+      let abort = Arc::new(MessageData::Abort(reason));
+      for target in (0..site.participants).filter(|p| *p != site.party_id) {
+        let _ = send(sink, target, site.party_id, site.id_offset, &abort);
+      }
+    }
+    Err(CoreErrors::ExecutionIssue {
+      kind: CryptoKind::Panicked(panic_reason(payload)),
+      round,
+      party: None,
+    })
+  });
+  PANIC_SITE.with(|site| site.set(None));
+  telemetry::ceremony_finished(protocol, result.as_ref().err());
+  result
+}
+
+fn parse_incoming(msg: Message) -> Result<(u8, u8, MessageData), CoreErrors> {
+  match msg {
+    Message::Send {
+      sender,
+      target,
+      data,
+      ..
+    } => Ok((sender, target, unshare(data))),
+    _ => Err(CoreErrors::invalid_data(ValidationKind::UnexpectedMessage(
+      format!("{}", msg),
+    ))),
+  }
+}
+
+fn is_later_round(candidate: Option<RoundId>, current: RoundId) -> bool {
+  match candidate {
+    Some(candidate) => discriminant(&candidate) == discriminant(&current) && candidate > current,
+    None => false,
+  }
+}
+
+/// Outcome of a broadcast. Every other party is attempted, so one broken
+/// link does not keep the message from the remaining parties.
+#[derive(Debug)]
+pub(crate) enum Broadcast {
+  Delivered,
+  Partial { unreachable: Vec<u8> },
+  Failed { unreachable: Vec<u8> },
+}
+
+impl Broadcast {
+  fn error(unreachable: Vec<u8>, round: RoundId) -> CoreErrors {
+    let party = match unreachable[..] {
+      [party] => Some(party),
+      _ => None,
+    };
+    let mut error = CoreErrors::transport(TransportKind::Unreachable(unreachable));
+    if let Some(party) = party {
+      error = error.with_party(party);
+    }
+    error.in_round(round)
+  }
+
+  /// Policy for rounds that need every party's contribution: any unreachable
+  /// party aborts the run.
+  pub(crate) fn require_all(self, round: RoundId) -> Result<(), CoreErrors> {
+    match self {
+      Broadcast::Delivered => Ok(()),
+      Broadcast::Partial { unreachable } | Broadcast::Failed { unreachable } => {
+        Err(Broadcast::error(unreachable, round))
+      }
+    }
+  }
+
+  /// Policy for rounds that can do without some parties: only a broadcast
+  /// that reached nobody aborts the run.
+  pub(crate) fn require_any(self, round: RoundId) -> Result<(), CoreErrors> {
+    match self {
+      Broadcast::Delivered | Broadcast::Partial { .. } => Ok(()),
+      Broadcast::Failed { unreachable } => Err(Broadcast::error(unreachable, round)),
+    }
+  }
+}
+
+// Incoming side of a protocol run. Messages that belong to a later round of
+// the same protocol than the one being collected are parked here and handed
+// out once that round is collected.
+struct Inbox<'a> {
+  receiver: RefCell<Box<dyn ProtocolReceiver + 'a>>,
+  parked: RefCell<VecDeque<(u8, MessageData)>>,
+}
+
+impl Inbox<'_> {
+  fn unpark(&self, round: RoundId) -> Option<(u8, MessageData)> {
+    let mut parked = self.parked.borrow_mut();
+    let position = parked
+      .iter()
+      .position(|(_, data)| data.round() == Some(round))?;
+    parked.remove(position)
+  }
+}
+
+/// Timing and counts of collecting one round.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct CollectRoundMetrics {
+  pub(crate) round_start: Instant,
+  pub(crate) round_end: Instant,
+  pub(crate) messages_received: usize,
+  pub(crate) duplicate_messages_rejected: usize,
+}
+
+impl CollectRoundMetrics {
+  fn start() -> Self {
+    let now = Instant::now();
+    CollectRoundMetrics {
+      round_start: now,
+      round_end: now,
+      messages_received: 0,
+      duplicate_messages_rejected: 0,
+    }
+  }
+
+  fn report(&self, round: RoundId) -> RoundMetrics {
+    RoundMetrics {
+      round,
+      duration_ms: self.round_end.duration_since(self.round_start).as_millis() as u64,
+      messages_received: self.messages_received,
+      duplicate_messages_rejected: self.duplicate_messages_rejected,
+    }
+  }
+}
+
+/// Context of one party in one protocol run: who we are, who takes part and
+/// the channels to them. Protocols are written as a sequence of rounds over
+/// its send and collect primitives.
+pub(crate) struct RoundRunner<'a> {
+  party_id: u8,
+  participants: u8,
+  outgoing: &'a dyn EventSink,
+  inbox: Inbox<'a>,
+  attempt: Option<u32>,
+  max_message_age_ms: Option<u64>,
+  // Added to party ids on the wire, see `CompatibilityMode`.
+  id_offset: u8,
+  #[cfg(feature = "debug-dump")]
+  dumper: Option<RoundDumper>,
+}
+
+impl<'a> RoundRunner<'a> {
+  pub(crate) fn new<R: ProtocolReceiver + 'a>(
+    party_id: u8,
+    participants: u8,
+    outgoing: &'a dyn EventSink,
+    incoming_receiver: R,
+  ) -> Self {
+    let runner = RoundRunner {
+      party_id,
+      participants,
+      outgoing,
+      inbox: Inbox {
+        receiver: RefCell::new(Box::new(incoming_receiver)),
+        parked: RefCell::new(VecDeque::new()),
+      },
+      attempt: None,
+      max_message_age_ms: None,
+      id_offset: 0,
+      #[cfg(feature = "debug-dump")]
+      dumper: None,
+    };
+    runner.enter(None);
+    runner
+  }
+
+  /// Takes the staleness limits and wire party numbering of `config`;
+  /// without them every message is accepted regardless of attempt or age,
+  /// and parties are numbered from 0.
+  pub(crate) fn with_config(mut self, config: &ProtocolConfig) -> Self {
+    self.attempt = config.attempt;
+    self.max_message_age_ms = config.max_message_age_ms;
+    self.id_offset = config.compatibility.id_offset();
+    #[cfg(feature = "debug-dump")]
+    {
+      self.dumper = config
+        .debug_dump
+        .clone()
+        .map(|dump| RoundDumper::new(dump, self.party_id));
+    }
+    self.enter(None);
+    self
+  }
+
+  // Records where the run is for `catch_panic`.
+  fn enter(&self, round: Option<RoundId>) {
+    let site = PanicSite {
+      party_id: self.party_id,
+      participants: self.participants,
+      id_offset: self.id_offset,
+      round,
+    };
+    PANIC_SITE.with(|current| current.set(Some(site)));
+  }
+
+  // Why `msg` belongs to an earlier attempt, if it does. A matching attempt
+  // id settles it; the clock is only consulted without one.
+  fn stale_reason(&self, msg: &Message) -> Option<String> {
+    let (sent_at_ms, attempt) = match msg {
+      Message::Send {
+        sent_at_ms,
+        attempt,
+        ..
+      } => (*sent_at_ms, *attempt),
+      _ => return None,
+    };
+
+    if let (Some(ours), Some(theirs)) = (self.attempt, attempt) {
+      if ours != theirs {
+        return Some(format!("attempt {}", theirs));
+      }
+      return None;
+    }
+    if let (Some(max_age), Some(sent_at_ms)) = (self.max_message_age_ms, sent_at_ms) {
+      let age = now_ms().saturating_sub(sent_at_ms);
+      if age > max_age {
+        return Some(format!("{} ms old", age));
+      }
+    }
+    None
+  }
+
+  pub(crate) fn log<S: Into<String>>(&self, msg: S) -> Result<(), CoreErrors> {
+    log(self.outgoing, msg.into())
+  }
+
+  fn metrics(&self, metrics: RoundMetrics) -> Result<(), CoreErrors> {
+    let msg = Message::Metrics(metrics);
+    let error_msg = format!("{}", msg);
+    deliver(self.outgoing, msg, TransportKind::SendFailed(error_msg)).map_err(CoreErrors::transport)
+  }
+
+  // A snapshot that can't be written is logged; it never fails the run.
+  #[cfg(feature = "debug-dump")]
+  fn dump(&self, metrics: RoundMetrics) -> Result<(), CoreErrors> {
+    match &self.dumper {
+      Some(dumper) => match dumper.round_done(metrics) {
+        Ok(()) => Ok(()),
+        Err(e) => self.log(format!("Failed to dump {}: {}", metrics.round, e)),
+      },
+      None => Ok(()),
+    }
+  }
+
+  pub(crate) fn send(&self, target: u8, data: &MessageData) -> Result<(), CoreErrors> {
+    self.send_shared(target, &Arc::new(data.clone()))
+  }
+
+  fn send_shared(&self, target: u8, data: &Arc<MessageData>) -> Result<(), CoreErrors> {
+    if data.round().is_some() {
+      self.enter(data.round());
+    }
+    #[cfg(feature = "debug-dump")]
+    if let Some(dumper) = &self.dumper {
+      dumper.sent(target, data);
+    }
+    send(self.outgoing, target, self.party_id, self.id_offset, data)
+  }
+
+  /// Sends `data` to every other party, carrying on past failed targets.
+  /// The caller applies its round's policy to the outcome. All targets
+  /// share one copy of `data`.
+  pub(crate) fn broadcast(&self, data: &MessageData) -> Broadcast {
+    let data = Arc::new(data.clone());
+    let targets: Vec<u8> = (0..self.participants)
+      .filter(|p| *p != self.party_id)
+      .collect();
+    let unreachable: Vec<u8> = targets
+      .iter()
+      .copied()
+      .filter(|p| self.send_shared(*p, &data).is_err())
+      .collect();
+
+    if unreachable.is_empty() {
+      Broadcast::Delivered
+    } else if unreachable.len() == targets.len() {
+      Broadcast::Failed { unreachable }
+    } else {
+      Broadcast::Partial { unreachable }
+    }
+  }
+
+  /// Tells every other party that we are not taking part, so their runs end
+  /// at once instead of timing out. Best effort: unreachable parties time
+  /// out as before.
+  pub(crate) fn abort(&self, reason: &str) {
+    let _ = self.broadcast(&MessageData::Abort(reason.to_string()));
+  }
+
+  pub(crate) fn finish(&self, result: Message) -> Result<(), CoreErrors> {
+    finish(self.outgoing, result)
+  }
+
+  pub(crate) fn quit(&self) -> Result<(), CoreErrors> {
+    quit(self.outgoing)
+  }
+
+  /// Takes the next message of `round`, waiting up to `wait` for one to
+  /// arrive. Returns at once with a message that is already there.
+  fn receive_within<T>(&self, round: RoundId, wait: Duration) -> Result<Option<(u8, T)>, CoreErrors>
+  where
+    T: FromData,
+  {
+    let (sender, data) = match self.inbox.unpark(round) {
+      Some(parked) => parked,
+      None => {
+        let received = self.inbox.receiver.borrow_mut().recv_timeout(wait);
+        let result = match received {
+          Ok(result) => result,
+          Err(RecvTimeoutError::Disconnected) => {
+            return Err(CoreErrors::transport(TransportKind::Disconnected).in_round(round))
+          }
+          Err(RecvTimeoutError::Timeout) => return Ok(None),
+        };
+
+        if let Some(reason) = self.stale_reason(&result) {
+          self.log(format!("Dropped stale message ({}): {}", reason, result))?;
+          return Ok(None);
+        }
+
+        let (wire_sender, wire_target, data) =
+          parse_incoming(result).map_err(|e| e.in_round(round))?;
+        if wire_target != self.party_id + self.id_offset {
+          return Err(
+            CoreErrors::invalid_data(ValidationKind::UnexpectedMessage(format!(
+              "{} for party {}",
+              data, wire_target
+            )))
+            .in_round(round),
+          );
+        }
+        let sender = wire_sender.wrapping_sub(self.id_offset);
+        if sender >= self.participants || sender == self.party_id {
+          return Err(
+            CoreErrors::invalid_data(ValidationKind::UnexpectedMessage(format!(
+              "{} from party {}",
+              data, wire_sender
+            )))
+            .in_round(round),
+          );
+        }
+        self.log(format!("Received {} from {}", &data, sender))?;
+
+        if let MessageData::Abort(reason) = data {
+          return Err(
+            CoreErrors::transport(TransportKind::PeerAborted(reason))
+              .in_round(round)
+              .with_party(sender),
+          );
+        }
+
+        if is_later_round(data.round(), round) {
+          self.log(format!("Parked {} from {} during {}", &data, sender, round))?;
+          self.inbox.parked.borrow_mut().push_back((sender, data));
+          return Ok(None);
+        }
+
+        (sender, data)
+      }
+    };
+
+    let err = CoreErrors::invalid_data(ValidationKind::UnexpectedData(format!("{}", data)))
+      .in_round(round)
+      .with_party(sender);
+    #[cfg(feature = "debug-dump")]
+    if let Some(dumper) = &self.dumper {
+      dumper.collected(sender, &data);
+    }
+    let tvalue = T::get_from_data(data).ok_or(err)?;
+    Ok(Some((sender, tvalue)))
+  }
+
+  /// Waits for the first `T` of the round from any party.
+  pub(crate) fn receive_one<T>(&self, round: RoundId) -> Result<(u8, T), CoreErrors>
+  where
+    T: FromData,
+  {
+    self.enter(Some(round));
+    let deadline = Instant::now() + COLLECT_TIMEOUT;
+    loop {
+      let now = Instant::now();
+      if now >= deadline {
+        return Err(CoreErrors::Timeout {
+          round,
+          missing: vec![],
+        });
+      }
+      if let Some(received) = self.receive_within(round, COLLECT_POLL.min(deadline - now))? {
+        return Ok(received);
+      }
+    }
+  }
+
+  /// Fills the empty slots of other parties in `vec` until every party
+  /// delivered or the collection time is over; slots of parties that stayed
+  /// silent are left as `None`. Our own slot is left as passed in, and a
+  /// filled slot keeps the first value that arrived for it. The
+  /// round's metrics are also reported as `Message::Metrics`.
+  pub(crate) fn collect_available<T>(
+    &self,
+    mut vec: Vec<Option<T>>,
+    round: RoundId,
+  ) -> Result<(Vec<Option<T>>, CollectRoundMetrics), CoreErrors>
+  where
+    T: FromData,
+  {
+    self.enter(Some(round));
+    #[cfg(any(test, feature = "test-utils"))]
+    if INJECTED_PANIC.with(|injected| injected.get()) == Some(round) {
+      panic!("injected panic in {}", round);
+    }
+    let deadline = Instant::now() + COLLECT_TIMEOUT;
+    let mut metrics = CollectRoundMetrics::start();
+    // Local clock only, so the gap is free of skew between parties.
+    let mut accepted: Option<(Instant, Instant)> = None;
+
+    loop {
+      let now = Instant::now();
+      if now >= deadline || self.missing(&vec).is_empty() {
+        if let Some((first, last)) = accepted {
+          self.log(format!(
+            "Processing gap of {}: {} ms",
+            round,
+            last.duration_since(first).as_millis()
+          ))?;
+        }
+        metrics.round_end = Instant::now();
+        let report = metrics.report(round);
+        telemetry::round_collected(self.participants, &report);
+        #[cfg(feature = "debug-dump")]
+        self.dump(report)?;
+        self.metrics(report)?;
+        return Ok((vec, metrics));
+      }
+
+      // Messages that arrived while we were computing or sending are taken
+      // right away; otherwise this wakes up as soon as the next one lands.
+      if let Some((sender, tvalue)) =
+        self.receive_within(round, COLLECT_POLL.min(deadline - now))?
+      {
+        metrics.messages_received += 1;
+        // The first value a party sent for the round stands; a resend or an
+        // equivocating second value is dropped.
+        if vec[sender as usize].is_some() {
+          self.log(format!("Dropped duplicate {} from {}", round, sender))?;
+          metrics.duplicate_messages_rejected += 1;
+          continue;
+        }
+        vec[sender as usize] = Some(tvalue);
+        let now = Instant::now();
+        accepted = Some((accepted.map_or(now, |(first, _)| first), now));
+      }
+    }
+  }
+
+  fn missing<T>(&self, vec: &[Option<T>]) -> Vec<u8> {
+    (0..self.participants)
+      .filter(|p| *p != self.party_id && vec[*p as usize].is_none())
+      .collect()
+  }
+
+  fn collect_all<T>(
+    &self,
+    vec: Vec<Option<T>>,
+    round: RoundId,
+  ) -> Result<(Vec<Option<T>>, CollectRoundMetrics), CoreErrors>
+  where
+    T: FromData,
+  {
+    let (vec, metrics) = self.collect_available(vec, round)?;
+    let missing = self.missing(&vec);
+    if !missing.is_empty() {
+      self.log("Collecting data timeout achived. Halt the process")?;
+      return Err(CoreErrors::Timeout { round, missing });
+    }
+    Ok((vec, metrics))
+  }
+
+  /// Collects the round's value from every party, with `my_value` at our
+  /// own index.
+  pub(crate) fn collect<T>(&self, round: RoundId, my_value: T) -> Result<Vec<T>, CoreErrors>
+  where
+    T: FromData + Clone + Debug,
+  {
+    let mut vec: Vec<Option<T>> = vec![None; self.participants as usize];
+    vec[self.party_id as usize] = Some(my_value);
+
+    let (vec, _) = self.collect_all(vec, round)?;
+    Ok(vec.into_iter().flatten().collect())
+  }
+
+  /// Collects the round's value from every other party, in party order.
+  /// For point-to-point rounds where we hold no value of our own.
+  pub(crate) fn collect_others<T>(&self, round: RoundId) -> Result<Vec<T>, CoreErrors>
+  where
+    T: FromData + Clone + Debug,
+  {
+    let vec: Vec<Option<T>> = vec![None; self.participants as usize];
+    let (vec, _) = self.collect_all(vec, round)?;
+    Ok(
+      vec
+        .into_iter()
+        .enumerate()
+        .filter(|(p, _)| *p != self.party_id as usize)
+        .flat_map(|(_, v)| v)
+        .collect(),
+    )
+  }
+
+  /// Broadcasts `my_value` and collects everyone's value for the round.
+  pub(crate) fn exchange<T>(
+    &self,
+    round: RoundId,
+    my_value: T,
+    wrap: fn(T) -> MessageData,
+  ) -> Result<Vec<T>, CoreErrors>
+  where
+    T: FromData + Clone + Debug,
+  {
+    self.log(format!("Broadcasting {}", round))?;
+    self.broadcast(&wrap(my_value.clone())).require_all(round)?;
+    self.log(format!("Collecting {}", round))?;
+    self.collect(round, my_value)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use curv::cryptographic_primitives::secret_sharing::feldman_vss::VerifiableSS;
+  use curv::elliptic::curves::traits::ECScalar;
+  use curv::FE;
+  use std::sync::mpsc::channel;
+
+  #[test]
+  fn second_send_from_same_sender_is_dropped() {
+    let (outgoing_sender, _outgoing_receiver) = channel::<Message>();
+    let (incoming_sender, incoming_receiver) = channel::<Message>();
+    let first: FE = ECScalar::new_random();
+    let second: FE = ECScalar::new_random();
+    let third: FE = ECScalar::new_random();
+    // Party 2 comes last so the round is still open for the duplicate.
+    for (sender, value) in vec![(1, first), (1, second), (2, third)] {
+      incoming_sender
+        .send(Message::make_send(
+          sender,
+          0,
+          &MessageData::SignRound3(value),
+        ))
+        .unwrap();
+    }
+
+    let runner = RoundRunner::new(0, 3, &outgoing_sender, incoming_receiver);
+    let mine: FE = ECScalar::new_random();
+    let (vec, metrics) = runner
+      .collect_available(vec![Some(mine), None, None], RoundId::Sign(3))
+      .unwrap();
+
+    assert_eq!(vec, vec![Some(mine), Some(first), Some(third)]);
+    assert_eq!(metrics.messages_received, 3);
+    assert_eq!(metrics.duplicate_messages_rejected, 1);
+  }
+
+  #[test]
+  fn broadcast_shares_one_payload_between_all_targets() {
+    let (outgoing_sender, outgoing_receiver) = channel::<Message>();
+    let (_incoming_sender, incoming_receiver) = channel::<Message>();
+    let runner = RoundRunner::new(0, 9, &outgoing_sender, incoming_receiver);
+    let (vss, _) = VerifiableSS::share(4, 9, &ECScalar::new_random());
+    assert!(matches!(
+      runner.broadcast(&MessageData::KeyGenRound4(vss)),
+      Broadcast::Delivered
+    ));
+
+    let sent: Vec<(u8, Arc<MessageData>)> = outgoing_receiver
+      .try_iter()
+      .filter_map(|msg| match msg {
+        Message::Send { target, data, .. } => Some((target, data)),
+        _ => None,
+      })
+      .collect();
+    assert_eq!(
+      sent.iter().map(|(target, _)| *target).collect::<Vec<u8>>(),
+      (1..9).collect::<Vec<u8>>()
+    );
+    // One payload for all 8 peers, and the runner kept no copy of it.
+    assert!(sent.iter().all(|(_, data)| Arc::ptr_eq(data, &sent[0].1)));
+    assert_eq!(Arc::strong_count(&sent[0].1), 8);
+  }
+}
@@ -0,0 +1,1002 @@
+use crate::common::channel::{ChannelSink, ProtocolReceiver, ProtocolSender};
+use crate::common::config::{ProtocolConfig, ProtocolVersion};
+use crate::common::events::EventSink;
+use crate::common::hash::{effective_digest, DigestBuilder, HashAlg};
+use crate::common::messages::*;
+use crate::common::types::{Digest, Digest32, Keystore, RoundId, Signature};
+use crate::common::utils::{check_vss_shape, normalize_signature};
+use crate::errors::{CoreErrors, CryptoKind, RequestField, TransportKind, ValidationKind};
+use crate::scenarios::guard::{Decision, SigningGuard};
+use crate::scenarios::nonce::NonceRegistry;
+use crate::scenarios::per_signer::PerSigner;
+use crate::scenarios::policy::{PolicyDecision, SigningPolicy, SigningRequest};
+use crate::scenarios::rotation::paillier_keys_hash;
+use crate::scenarios::runner::{catch_panic, halt_on_error, RoundRunner};
+use crate::scenarios::sign_rate_limiter::RateLimiter;
+use curv::{
+  cryptographic_primitives::{
+    proofs::sigma_correct_homomorphic_elgamal_enc::HomoELGamalProof, proofs::sigma_dlog::DLogProof,
+    secret_sharing::feldman_vss::VerifiableSS,
+  },
+  BigInt, FE, GE,
+};
+use multi_party_ecdsa::protocols::multi_party_ecdsa::gg_2018::{
+  mta::{MessageA, MessageB},
+  party_i::{
+    Keys, LocalSignature, PartyPrivate, Phase5ADecom1, SharedKeys, SignBroadcastPhase1, SignKeys,
+  },
+};
+use paillier::EncryptionKey;
+use std::collections::BTreeMap;
+use std::io::Read;
+use std::sync::Mutex;
+
+pub fn sign(
+  participants: u8,
+  threshold: u8,
+  party_num_id: u8,
+  keystore: &Keystore,
+  digest: &Digest32,
+  signers_vec: &Vec<usize>,
+  outgoing_sender: OutgoingChannel,
+  incoming_receiver: IncomingChannel,
+) {
+  sign_with_config(
+    participants,
+    threshold,
+    party_num_id,
+    keystore,
+    digest,
+    None,
+    signers_vec,
+    &ProtocolConfig::default(),
+    outgoing_sender,
+    incoming_receiver,
+  )
+}
+
+pub fn sign_with_config(
+  participants: u8,
+  threshold: u8,
+  party_num_id: u8,
+  keystore: &Keystore,
+  digest: &Digest32,
+  context: Option<&[u8]>,
+  signers_vec: &Vec<usize>,
+  config: &ProtocolConfig,
+  outgoing_sender: OutgoingChannel,
+  incoming_receiver: IncomingChannel,
+) {
+  let result = safe_sign_with_config(
+    participants,
+    threshold,
+    party_num_id,
+    keystore,
+    digest,
+    context,
+    signers_vec,
+    config,
+    outgoing_sender.clone(),
+    incoming_receiver,
+  );
+  halt_on_error(&outgoing_sender, result);
+}
+
+#[deprecated(note = "pass a `Digest32` to `sign`")]
+pub fn sign_bigint(
+  participants: u8,
+  threshold: u8,
+  party_num_id: u8,
+  keystore: &Keystore,
+  digest: &Digest,
+  signers_vec: &Vec<usize>,
+  outgoing_sender: OutgoingChannel,
+  incoming_receiver: IncomingChannel,
+) {
+  #[allow(deprecated)]
+  let result = safe_sign_bigint(
+    participants,
+    threshold,
+    party_num_id,
+    keystore,
+    digest,
+    signers_vec,
+    outgoing_sender.clone(),
+    incoming_receiver,
+  );
+  halt_on_error(&outgoing_sender, result);
+}
+
+// Cross-checks a signing request against the keystore before anything is
+// sent, so a misconfigured caller doesn't drag its peers into a doomed run.
+#[allow(deprecated)]
+pub(crate) fn check_sign_request(
+  participants: u8,
+  threshold: u8,
+  party_num_id: u8,
+  keystore: &Keystore,
+  signers_vec: &Vec<usize>,
+) -> Result<(), CoreErrors> {
+  let expect = |field, expected: usize, got: usize| {
+    if expected == got {
+      Ok(())
+    } else {
+      Err(CoreErrors::mismatch(field, expected, got))
+    }
+  };
+
+  if keystore.threshold().get() < 1 {
+    return Err(CoreErrors::invalid_data(ValidationKind::UnsupportedSetup(
+      "threshold must be at least 1".to_string(),
+    )));
+  }
+
+  let signers = signers_vec.len();
+  expect(
+    RequestField::SignerCount,
+    keystore.signer_count().get() as usize,
+    signers,
+  )?;
+  expect(RequestField::Participants, signers, participants as usize)?;
+  expect(
+    RequestField::Threshold,
+    keystore.threshold().get() as usize,
+    threshold as usize,
+  )?;
+
+  let share_count = keystore.share_count() as usize;
+  expect(
+    RequestField::ShareCount,
+    share_count,
+    keystore.vss_scheme_vec.len(),
+  )?;
+  expect(
+    RequestField::ShareCount,
+    share_count,
+    keystore.paillier_key_vec.len(),
+  )?;
+  for (i, vss) in keystore.vss_scheme_vec.iter().enumerate() {
+    check_vss_shape(
+      vss,
+      keystore.threshold().get(),
+      keystore.share_count(),
+      None,
+    )
+    .map_err(|e| e.with_party(i as u8))?;
+  }
+  if let Some(index) = signers_vec.iter().find(|i| **i >= share_count) {
+    return Err(CoreErrors::mismatch(
+      RequestField::SignerIndex,
+      share_count,
+      *index,
+    ));
+  }
+
+  let own_index = signers_vec.get(party_num_id as usize).ok_or_else(|| {
+    CoreErrors::mismatch(RequestField::SignerPosition, signers, party_num_id as usize)
+  })?;
+  expect(RequestField::OwnIndex, keystore.party_index(), *own_index)
+}
+
+// MtA ciphertexts must lie in (0, N^2) for the key they were encrypted
+// under. Checked as they are collected, so a malformed one is blamed on its
+// sender before any decryption or proof verification spends time on it.
+fn check_ciphertext(c: &BigInt, ek: &EncryptionKey) -> Result<(), CoreErrors> {
+  if *c <= BigInt::from(0) || *c >= ek.nn {
+    return Err(CoreErrors::invalid_data(ValidationKind::Malformed(
+      "Paillier ciphertext out of range".to_string(),
+    )));
+  }
+  Ok(())
+}
+
+// A round-2 response names who it was computed for. One meant for another
+// signer says nothing about its sender, so it is blamed on the transport.
+fn check_round2_addressee(
+  m: &SignRound2Data,
+  sender: u8,
+  own: u8,
+  own_digest: &Digest32,
+) -> Result<(), CoreErrors> {
+  let (recipient, responding_to) = match (m.recipient, m.responding_to.as_ref()) {
+    (Some(recipient), Some(responding_to)) => (recipient, responding_to),
+    _ => {
+      return Err(
+        CoreErrors::invalid_data(ValidationKind::Malformed(
+          "round 2 response without recipient".to_string(),
+        ))
+        .in_round(RoundId::Sign(2))
+        .with_party(sender),
+      )
+    }
+  };
+  if recipient != own || responding_to != own_digest {
+    return Err(
+      CoreErrors::transport(TransportKind::Misrouted {
+        sender,
+        intended: recipient,
+      })
+      .in_round(RoundId::Sign(2))
+      .with_party(sender),
+    );
+  }
+  Ok(())
+}
+
+pub fn safe_sign(
+  participants: u8,
+  threshold: u8,
+  party_num_id: u8,
+  keystore: &Keystore,
+  digest: &Digest32,
+  signers_vec: &Vec<usize>,
+  outgoing_sender: OutgoingChannel,
+  incoming_receiver: IncomingChannel,
+) -> Result<(), CoreErrors> {
+  safe_sign_with_config(
+    participants,
+    threshold,
+    party_num_id,
+    keystore,
+    digest,
+    None,
+    signers_vec,
+    &ProtocolConfig::default(),
+    outgoing_sender,
+    incoming_receiver,
+  )
+}
+
+/// `safe_sign` for digests still held as integers. Rejects values that are
+/// zero or do not fit in 32 bytes instead of signing them.
+#[deprecated(note = "pass a `Digest32` to `safe_sign`")]
+pub fn safe_sign_bigint(
+  participants: u8,
+  threshold: u8,
+  party_num_id: u8,
+  keystore: &Keystore,
+  digest: &Digest,
+  signers_vec: &Vec<usize>,
+  outgoing_sender: OutgoingChannel,
+  incoming_receiver: IncomingChannel,
+) -> Result<(), CoreErrors> {
+  safe_sign(
+    participants,
+    threshold,
+    party_num_id,
+    keystore,
+    &Digest32::from_bigint(digest.as_bigint())?,
+    signers_vec,
+    outgoing_sender,
+    incoming_receiver,
+  )
+}
+
+/// Like `safe_sign`, with protocol options and an optional domain-separation
+/// `context`. With a context the digest actually signed is
+/// `hash::domain_separated(context, digest)`; every signer has to pass the same
+/// context or the run aborts after round 1.
+pub fn safe_sign_with_config(
+  participants: u8,
+  threshold: u8,
+  party_num_id: u8,
+  keystore: &Keystore,
+  digest: &Digest32,
+  context: Option<&[u8]>,
+  signers_vec: &Vec<usize>,
+  config: &ProtocolConfig,
+  outgoing_sender: OutgoingChannel,
+  incoming_receiver: IncomingChannel,
+) -> Result<(), CoreErrors> {
+  run_sign(
+    participants,
+    threshold,
+    party_num_id,
+    keystore,
+    digest,
+    context,
+    None,
+    signers_vec,
+    config,
+    SignHooks::default(),
+    &outgoing_sender,
+    incoming_receiver,
+  )
+}
+
+pub fn sign_with_hooks(
+  participants: u8,
+  threshold: u8,
+  party_num_id: u8,
+  keystore: &Keystore,
+  digest: &Digest32,
+  context: Option<&[u8]>,
+  signers_vec: &Vec<usize>,
+  config: &ProtocolConfig,
+  hooks: SignHooks,
+  outgoing_sender: OutgoingChannel,
+  incoming_receiver: IncomingChannel,
+) {
+  let result = safe_sign_with_hooks(
+    participants,
+    threshold,
+    party_num_id,
+    keystore,
+    digest,
+    context,
+    signers_vec,
+    config,
+    hooks,
+    outgoing_sender.clone(),
+    incoming_receiver,
+  );
+  halt_on_error(&outgoing_sender, result);
+}
+
+/// Like `safe_sign_with_config`, consulting `hooks` before round 1. See
+/// `SignHooks` for what each hook does when it refuses.
+pub fn safe_sign_with_hooks(
+  participants: u8,
+  threshold: u8,
+  party_num_id: u8,
+  keystore: &Keystore,
+  digest: &Digest32,
+  context: Option<&[u8]>,
+  signers_vec: &Vec<usize>,
+  config: &ProtocolConfig,
+  hooks: SignHooks,
+  outgoing_sender: OutgoingChannel,
+  incoming_receiver: IncomingChannel,
+) -> Result<(), CoreErrors> {
+  run_sign(
+    participants,
+    threshold,
+    party_num_id,
+    keystore,
+    digest,
+    context,
+    None,
+    signers_vec,
+    config,
+    hooks,
+    &outgoing_sender,
+    incoming_receiver,
+  )
+}
+
+pub fn sign_with_sink(
+  participants: u8,
+  threshold: u8,
+  party_num_id: u8,
+  keystore: &Keystore,
+  digest: &Digest32,
+  context: Option<&[u8]>,
+  signers_vec: &Vec<usize>,
+  config: &ProtocolConfig,
+  sink: &dyn EventSink,
+  incoming_receiver: IncomingChannel,
+) {
+  let result = safe_sign_with_sink(
+    participants,
+    threshold,
+    party_num_id,
+    keystore,
+    digest,
+    context,
+    signers_vec,
+    config,
+    sink,
+    incoming_receiver,
+  );
+  halt_on_error(sink, result);
+}
+
+/// Like `safe_sign_with_config`, delivering everything the run emits to
+/// `sink` instead of a channel.
+pub fn safe_sign_with_sink(
+  participants: u8,
+  threshold: u8,
+  party_num_id: u8,
+  keystore: &Keystore,
+  digest: &Digest32,
+  context: Option<&[u8]>,
+  signers_vec: &Vec<usize>,
+  config: &ProtocolConfig,
+  sink: &dyn EventSink,
+  incoming_receiver: IncomingChannel,
+) -> Result<(), CoreErrors> {
+  run_sign(
+    participants,
+    threshold,
+    party_num_id,
+    keystore,
+    digest,
+    context,
+    None,
+    signers_vec,
+    config,
+    SignHooks::default(),
+    sink,
+    incoming_receiver,
+  )
+}
+
+pub fn sign_with_channels<S: ProtocolSender + Clone, R: ProtocolReceiver>(
+  participants: u8,
+  threshold: u8,
+  party_num_id: u8,
+  keystore: &Keystore,
+  digest: &Digest32,
+  context: Option<&[u8]>,
+  signers_vec: &Vec<usize>,
+  config: &ProtocolConfig,
+  outgoing_sender: S,
+  incoming_receiver: R,
+) {
+  let result = safe_sign_with_channels(
+    participants,
+    threshold,
+    party_num_id,
+    keystore,
+    digest,
+    context,
+    signers_vec,
+    config,
+    outgoing_sender.clone(),
+    incoming_receiver,
+  );
+  halt_on_error(&ChannelSink(outgoing_sender), result);
+}
+
+/// Like `safe_sign_with_config`, over any channel implementation, e.g.
+/// crossbeam or tokio channels with the matching feature.
+pub fn safe_sign_with_channels<S: ProtocolSender, R: ProtocolReceiver>(
+  participants: u8,
+  threshold: u8,
+  party_num_id: u8,
+  keystore: &Keystore,
+  digest: &Digest32,
+  context: Option<&[u8]>,
+  signers_vec: &Vec<usize>,
+  config: &ProtocolConfig,
+  outgoing_sender: S,
+  incoming_receiver: R,
+) -> Result<(), CoreErrors> {
+  run_sign(
+    participants,
+    threshold,
+    party_num_id,
+    keystore,
+    digest,
+    context,
+    None,
+    signers_vec,
+    config,
+    SignHooks::default(),
+    &ChannelSink(outgoing_sender),
+    incoming_receiver,
+  )
+}
+
+pub fn sign_reader<R: Read>(
+  participants: u8,
+  threshold: u8,
+  party_num_id: u8,
+  keystore: &Keystore,
+  reader: R,
+  alg: HashAlg,
+  signers_vec: &Vec<usize>,
+  outgoing_sender: OutgoingChannel,
+  incoming_receiver: IncomingChannel,
+) {
+  let result = safe_sign_reader(
+    participants,
+    threshold,
+    party_num_id,
+    keystore,
+    reader,
+    alg,
+    signers_vec,
+    outgoing_sender.clone(),
+    incoming_receiver,
+  );
+  halt_on_error(&outgoing_sender, result);
+}
+
+/// Signs the `alg` digest of everything `reader` yields, hashing it as it
+/// streams in. The result records `alg` so a verifier can rebuild the digest
+/// with `hash::DigestBuilder`.
+pub fn safe_sign_reader<R: Read>(
+  participants: u8,
+  threshold: u8,
+  party_num_id: u8,
+  keystore: &Keystore,
+  reader: R,
+  alg: HashAlg,
+  signers_vec: &Vec<usize>,
+  outgoing_sender: OutgoingChannel,
+  incoming_receiver: IncomingChannel,
+) -> Result<(), CoreErrors> {
+  let mut builder = DigestBuilder::new(alg);
+  builder.update_reader(reader)?;
+  run_sign(
+    participants,
+    threshold,
+    party_num_id,
+    keystore,
+    &builder.finalize(),
+    None,
+    Some(alg),
+    signers_vec,
+    &ProtocolConfig::default(),
+    SignHooks::default(),
+    &outgoing_sender,
+    incoming_receiver,
+  )
+}
+
+/// Checks a signing run consults on top of the protocol, all before
+/// round 1, in the order rate limiter, policy, guard, nonce registry.
+///
+/// - `with_rate_limiter`: over the limit the run fails with
+///   `RateLimitExceeded` and sends the other signers an abort.
+/// - `with_policy`: `policy` decides on the request, with the caller's
+///   `session_id` and `metadata`. A denial fails with `PolicyDenied` and
+///   sends the other signers an abort; the annotations of an approval end
+///   up in the signing result.
+/// - `with_guard`: a refusal fails with `DuplicateDigest` or
+///   `SigningRefused` and sends the other signers an abort, so their runs
+///   fail at once rather than time out.
+/// - `with_nonce_registry`: the run refuses to go on if this party's nonce
+///   `k_i` was already used by a run registered there.
+#[derive(Default)]
+pub struct SignHooks<'a> {
+  nonces: Option<&'a Mutex<NonceRegistry>>,
+  guard: Option<&'a Mutex<dyn SigningGuard>>,
+  policy: Option<PolicyCheck<'a>>,
+  rate_limiter: Option<&'a mut RateLimiter>,
+}
+
+impl<'a> SignHooks<'a> {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn with_nonce_registry(mut self, nonces: &'a Mutex<NonceRegistry>) -> Self {
+    self.nonces = Some(nonces);
+    self
+  }
+
+  pub fn with_guard(mut self, guard: &'a Mutex<dyn SigningGuard>) -> Self {
+    self.guard = Some(guard);
+    self
+  }
+
+  pub fn with_policy(
+    mut self,
+    policy: &'a dyn SigningPolicy,
+    session_id: Option<&'a [u8]>,
+    metadata: &'a BTreeMap<String, String>,
+  ) -> Self {
+    self.policy = Some(PolicyCheck {
+      policy,
+      session_id,
+      metadata,
+    });
+    self
+  }
+
+  pub fn with_rate_limiter(mut self, rate_limiter: &'a mut RateLimiter) -> Self {
+    self.rate_limiter = Some(rate_limiter);
+    self
+  }
+}
+
+struct PolicyCheck<'a> {
+  policy: &'a dyn SigningPolicy,
+  session_id: Option<&'a [u8]>,
+  metadata: &'a BTreeMap<String, String>,
+}
+
+fn run_sign<R: ProtocolReceiver>(
+  participants: u8,
+  threshold: u8,
+  party_num_id: u8,
+  keystore: &Keystore,
+  digest: &Digest32,
+  context: Option<&[u8]>,
+  hash_alg: Option<HashAlg>,
+  signers_vec: &Vec<usize>,
+  config: &ProtocolConfig,
+  hooks: SignHooks,
+  sink: &dyn EventSink,
+  incoming_receiver: R,
+) -> Result<(), CoreErrors> {
+  catch_panic("sign", sink, || {
+    sign_rounds(
+      participants,
+      threshold,
+      party_num_id,
+      keystore,
+      digest,
+      context,
+      hash_alg,
+      signers_vec,
+      config,
+      hooks,
+      sink,
+      incoming_receiver,
+    )
+  })
+}
+
+#[allow(deprecated)]
+fn sign_rounds<R: ProtocolReceiver>(
+  participants: u8,
+  threshold: u8,
+  party_num_id: u8,
+  keystore: &Keystore,
+  digest: &Digest32,
+  context: Option<&[u8]>,
+  hash_alg: Option<HashAlg>,
+  signers_vec: &Vec<usize>,
+  config: &ProtocolConfig,
+  hooks: SignHooks,
+  sink: &dyn EventSink,
+  incoming_receiver: R,
+) -> Result<(), CoreErrors> {
+  check_sign_request(participants, threshold, party_num_id, keystore, signers_vec)?;
+  let runner =
+    RoundRunner::new(party_num_id, participants, sink, incoming_receiver).with_config(config);
+
+  runner.log("Start signature generation")?;
+
+  if let Some(rate_limiter) = hooks.rate_limiter {
+    if let Err(e) = rate_limiter.check() {
+      runner.abort(&e.to_string());
+      return Err(e.in_round(RoundId::Sign(1)));
+    }
+  }
+
+  let mut policy_annotations = vec![];
+  if let Some(check) = hooks.policy {
+    let request = SigningRequest {
+      digest,
+      context,
+      signers: signers_vec,
+      session_id: check.session_id,
+      metadata: check.metadata,
+    };
+    match check.policy.evaluate(&request) {
+      PolicyDecision::Approve { annotations } => {
+        for annotation in &annotations {
+          runner.log(format!("Policy annotation: {}", annotation))?;
+        }
+        policy_annotations = annotations;
+      }
+      PolicyDecision::Deny { reason } => {
+        let kind = ValidationKind::PolicyDenied(reason);
+        runner.abort(&kind.to_string());
+        return Err(CoreErrors::invalid_data(kind).in_round(RoundId::Sign(1)));
+      }
+    }
+  }
+
+  if let Some(guard) = hooks.guard {
+    let decision = guard
+      .lock()
+      .map_err(|_| {
+        CoreErrors::execution(
+          CryptoKind::Panicked("signing guard poisoned".to_string()),
+          RoundId::Sign(1),
+        )
+      })?
+      .check(digest, context, signers_vec);
+    let refusal = match decision {
+      Decision::Approve => None,
+      Decision::Duplicate => Some(ValidationKind::DuplicateDigest),
+      Decision::Deny(reason) => Some(ValidationKind::SigningRefused(reason)),
+    };
+    if let Some(kind) = refusal {
+      runner.abort(&kind.to_string());
+      return Err(CoreErrors::invalid_data(kind).in_round(RoundId::Sign(1)));
+    }
+  }
+
+  let (party_keys, shared_keys, _party_id, vss_scheme_vec, paillier_key_vector, y_sum): (
+    &Keys,
+    &SharedKeys,
+    usize,
+    &Vec<VerifiableSS>,
+    &Vec<EncryptionKey>,
+    &GE,
+  ) = (
+    &keystore.party_key,
+    &keystore.shared_keys,
+    keystore.party_index,
+    &keystore.vss_scheme_vec,
+    &keystore.paillier_key_vec,
+    &keystore.y_sum,
+  );
+
+  let party_num_id = party_num_id as usize;
+  // Checked against `signers_vec` above; every round visits all of them.
+  let signer_count = keystore.signer_count().get() as usize;
+  let private = PartyPrivate::set_private(party_keys.clone(), shared_keys.clone());
+  let sign_keys = SignKeys::create(
+    &private,
+    &vss_scheme_vec[signers_vec[party_num_id]],
+    signers_vec[party_num_id],
+    &signers_vec,
+  );
+
+  if let Some(nonces) = hooks.nonces {
+    nonces
+      .lock()
+      .map_err(|_| {
+        CoreErrors::execution(
+          CryptoKind::Panicked("nonce registry poisoned".to_string()),
+          RoundId::Sign(1),
+        )
+      })?
+      .check_and_register(&sign_keys.k_i)?;
+  }
+
+  let xi_com_vec = Keys::get_commitments_to_xi(&vss_scheme_vec);
+  let (com, decommit) = sign_keys.phase1_broadcast();
+  let m_a_k = MessageA::a(&sign_keys.k_i, &party_keys.ek);
+
+  // Positions every phase has to cover: all signers for broadcasts, the
+  // others for point-to-point rounds and the MtA shares.
+  let all: Vec<usize> = (0..signer_count).collect();
+  let others: Vec<usize> = all.iter().copied().filter(|i| *i != party_num_id).collect();
+
+  let own_keys_hash = paillier_keys_hash(paillier_key_vector);
+  let msg = SignRound1Data {
+    com: com.clone(),
+    enc: m_a_k.clone(),
+    context: context.map(|c| c.to_vec()),
+    paillier_keys_hash: Some(own_keys_hash.clone()),
+  };
+  let round_1 =
+    PerSigner::from_all(runner.exchange(RoundId::Sign(1), msg, MessageData::SignRound1)?);
+  round_1.expect_keys(&all, RoundId::Sign(1))?;
+
+  if let Some((i, _)) = round_1.all().find(|(_, m)| m.context.as_deref() != context) {
+    return Err(
+      CoreErrors::invalid_data(ValidationKind::ContextMismatch)
+        .in_round(RoundId::Sign(1))
+        .with_party(i as u8),
+    );
+  }
+
+  // A signer on a keystore from before or after a rotation the others
+  // didn't complete. Peers that predate the hash don't send it.
+  if let Some((i, _)) = round_1.others(party_num_id).find(|(_, m)| {
+    m.paillier_keys_hash
+      .as_ref()
+      .map_or(false, |hash| *hash != own_keys_hash)
+  }) {
+    return Err(
+      CoreErrors::execution(CryptoKind::RotationMismatch, RoundId::Sign(1)).with_party(i as u8),
+    );
+  }
+
+  for (i, m) in round_1.all() {
+    check_ciphertext(&m.enc.c, &paillier_key_vector[signers_vec[i]])
+      .map_err(|e| e.in_round(RoundId::Sign(1)).with_party(i as u8))?;
+  }
+
+  let bc1: PerSigner<SignBroadcastPhase1> = round_1.map(|m| m.com.clone());
+  let m_a: PerSigner<MessageA> = round_1.map(|m| m.enc.clone());
+
+  // Our MtA responses to every other signer, for gamma_i and w_i: the
+  // messages to send and our additive shares.
+  let m_b_send: PerSigner<((MessageB, FE), (MessageB, FE))> = m_a
+    .others(party_num_id)
+    .map(|(i, m_a_i)| {
+      let ek = &paillier_key_vector[signers_vec[i]];
+      let gamma = MessageB::b(&sign_keys.gamma_i, ek, m_a_i.clone());
+      let w = MessageB::b(&sign_keys.w_i, ek, m_a_i.clone());
+      (i, (gamma, w))
+    })
+    .collect();
+  m_b_send.expect_keys(&others, RoundId::Sign(2))?;
+  let beta: PerSigner<FE> = m_b_send.map(|((_, beta_gamma), _)| *beta_gamma);
+  let ni: PerSigner<FE> = m_b_send.map(|(_, (_, beta_wi))| *beta_wi);
+
+  let addressed = config.protocol_version >= ProtocolVersion::V4;
+  runner.log("Sending round 2")?;
+  for (i, ((g, _), (w, _))) in m_b_send.all() {
+    let m_a_i = m_a
+      .require(i, RoundId::Sign(1))
+      .map_err(|e| e.with_party(i as u8))?;
+    runner.send(
+      i as u8,
+      &MessageData::SignRound2(SignRound2Data {
+        g: g.clone(),
+        w: w.clone(),
+        recipient: addressed.then(|| i as u8),
+        responding_to: addressed.then(|| SignRound2Data::message_a_digest(m_a_i)),
+      }),
+    )?;
+  }
+
+  runner.log("Collecting round 2")?;
+  let round_2 = PerSigner::from_others(
+    runner.collect_others::<SignRound2Data>(RoundId::Sign(2))?,
+    party_num_id,
+  );
+  round_2.expect_keys(&others, RoundId::Sign(2))?;
+  if addressed {
+    let own_digest = SignRound2Data::message_a_digest(&m_a_k);
+    for (sender, m) in round_2.all() {
+      check_round2_addressee(m, sender as u8, party_num_id as u8, &own_digest)?;
+    }
+  }
+  // After the addressee check: a misrouted response is encrypted under
+  // someone else's key and would otherwise be blamed on its sender.
+  for (sender, m) in round_2.all() {
+    check_ciphertext(&m.g.c, &party_keys.ek)
+      .and_then(|_| check_ciphertext(&m.w.c, &party_keys.ek))
+      .map_err(|e| e.in_round(RoundId::Sign(2)).with_party(sender as u8))?;
+  }
+
+  let mut shares: Vec<(usize, FE, FE)> = Vec::new();
+  for (i, m) in round_2.all() {
+    let alpha_ij_gamma = m
+      .g
+      .verify_proofs_get_alpha(&party_keys.dk, &sign_keys.k_i)
+      .map_err(|e| {
+        CoreErrors::execution(
+          CryptoKind::AlphaProofGamma(format!("{:?}", e)),
+          RoundId::Sign(2),
+        )
+        .with_party(i as u8)
+      })?;
+    let alpha_ij_wi = m
+      .w
+      .verify_proofs_get_alpha(&party_keys.dk, &sign_keys.k_i)
+      .map_err(|e| {
+        CoreErrors::execution(
+          CryptoKind::AlphaProofW(format!("{:?}", e)),
+          RoundId::Sign(2),
+        )
+        .with_party(i as u8)
+      })?;
+    let g_w_i = Keys::update_commitments_to_xi(
+      &xi_com_vec[signers_vec[i]],
+      &vss_scheme_vec[signers_vec[i]],
+      signers_vec[i],
+      &signers_vec,
+    );
+    if m.w.b_proof.pk != g_w_i {
+      return Err(
+        CoreErrors::execution(CryptoKind::ProofPointMismatch, RoundId::Sign(2)).with_party(i as u8),
+      );
+    }
+    shares.push((i, alpha_ij_gamma, alpha_ij_wi));
+  }
+  let alpha: PerSigner<FE> = shares.iter().map(|(i, a, _)| (*i, *a)).collect();
+  let miu: PerSigner<FE> = shares.iter().map(|(i, _, m)| (*i, *m)).collect();
+  alpha.expect_keys(&others, RoundId::Sign(2))?;
+  miu.expect_keys(&others, RoundId::Sign(2))?;
+
+  let delta_i = sign_keys.phase2_delta_i(&alpha.values(), &beta.values());
+  let sigma = sign_keys.phase2_sigma_i(&miu.values(), &ni.values());
+
+  let delta =
+    PerSigner::from_all(runner.exchange(RoundId::Sign(3), delta_i, MessageData::SignRound3)?);
+  delta.expect_keys(&all, RoundId::Sign(3))?;
+  let delta_inv = SignKeys::phase3_reconstruct_delta(&delta.values());
+
+  let decommit =
+    PerSigner::from_all(runner.exchange(RoundId::Sign(4), decommit, MessageData::SignRound4)?);
+  decommit.expect_keys(&all, RoundId::Sign(4))?;
+  let b_proof_vec = round_2
+    .all()
+    .map(|(_, m)| &m.g.b_proof)
+    .collect::<Vec<&DLogProof>>();
+
+  let r = SignKeys::phase4(
+    &delta_inv,
+    &b_proof_vec,
+    decommit.others_values(party_num_id),
+    &bc1.others_values(party_num_id),
+  )
+  .map_err(|e| {
+    CoreErrors::execution(
+      CryptoKind::BadDecommit(format!("{:?}", e)),
+      RoundId::Sign(4),
+    )
+  })?;
+  let decomm_i = decommit.require(party_num_id, RoundId::Sign(4))?;
+  let r = r + decomm_i.g_gamma_i * delta_inv;
+
+  let message_bn = effective_digest(context, digest).to_bigint();
+
+  let local_sig = LocalSignature::phase5_local_sig(&sign_keys.k_i, &message_bn, &r, &sigma, &y_sum);
+
+  let (phase5_com, phase_5a_decom, helgamal_proof) = local_sig.phase5a_broadcast_5b_zkproof();
+
+  let commit5a =
+    PerSigner::from_all(runner.exchange(RoundId::Sign(5), phase5_com, MessageData::SignRound5)?);
+  commit5a.expect_keys(&all, RoundId::Sign(5))?;
+
+  let data = SignRound6Data {
+    com: phase_5a_decom.clone(),
+    proof: helgamal_proof.clone(),
+  };
+  let decommit5a_and_elgamal =
+    PerSigner::from_all(runner.exchange(RoundId::Sign(6), data, MessageData::SignRound6)?);
+  decommit5a_and_elgamal.expect_keys(&all, RoundId::Sign(6))?;
+
+  let phase_5a_decomm_vec = decommit5a_and_elgamal
+    .others(party_num_id)
+    .map(|(_, m)| m.com.clone())
+    .collect::<Vec<Phase5ADecom1>>();
+  let phase_5a_elgamal_vec = decommit5a_and_elgamal
+    .others(party_num_id)
+    .map(|(_, m)| m.proof.clone())
+    .collect::<Vec<HomoELGamalProof>>();
+  let (phase5_com2, phase_5d_decom2) = local_sig
+    .phase5c(
+      &phase_5a_decomm_vec,
+      &commit5a.others_values(party_num_id),
+      &phase_5a_elgamal_vec,
+      &phase_5a_decom.V_i,
+      &r,
+    )
+    .map_err(|e| CoreErrors::execution(CryptoKind::Phase5(format!("{:?}", e)), RoundId::Sign(6)))?;
+
+  let commit5c =
+    PerSigner::from_all(runner.exchange(RoundId::Sign(7), phase5_com2, MessageData::SignRound7)?);
+  commit5c.expect_keys(&all, RoundId::Sign(7))?;
+  let decommit5d = PerSigner::from_all(runner.exchange(
+    RoundId::Sign(8),
+    phase_5d_decom2,
+    MessageData::SignRound8,
+  )?);
+  decommit5d.expect_keys(&all, RoundId::Sign(8))?;
+
+  let phase_5a_decomm_vec_includes_i = decommit5a_and_elgamal
+    .all()
+    .map(|(_, m)| m.com.clone())
+    .collect::<Vec<Phase5ADecom1>>();
+
+  let s_i = local_sig
+    .phase5d(
+      &decommit5d.values(),
+      &commit5c.values(),
+      &phase_5a_decomm_vec_includes_i,
+    )
+    .map_err(|e| {
+      CoreErrors::execution(
+        CryptoKind::Phase5Commitment(format!("{:?}", e)),
+        RoundId::Sign(8),
+      )
+    })?;
+
+  let s = PerSigner::from_all(runner.exchange(RoundId::Sign(9), s_i, MessageData::SignRound9)?);
+  s.expect_keys(&all, RoundId::Sign(9))?;
+  let s_i_vec = s.others_values(party_num_id);
+
+  let sig = local_sig.output_signature(&s_i_vec).map_err(|e| {
+    CoreErrors::execution(
+      CryptoKind::SignatureVerification(format!("{:?}", e)),
+      RoundId::Sign(9),
+    )
+  })?;
+
+  let mut signature = Signature::new(sig);
+  if config.enforce_low_s {
+    signature = normalize_signature(signature);
+  }
+
+  runner.finish(Message::make_complete_signature_with_annotations(
+    signature,
+    context.map(|c| c.to_vec()),
+    hash_alg,
+    policy_annotations,
+  ))
+}
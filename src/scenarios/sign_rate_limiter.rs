@@ -0,0 +1,46 @@
+use crate::errors::{CoreErrors, ValidationKind};
+use std::time::{Duration, Instant};
+
+const WINDOW: Duration = Duration::from_secs(1);
+
+/// Caps how many signing runs a party starts per second with one keystore,
+/// so a compromised coordinator can't keep it busy signing. Counts in fixed
+/// one-second windows; every run that passes `check` counts, whether it
+/// succeeds or not.
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+  max_per_second: u32,
+  window_start: Instant,
+  count: u32,
+}
+
+impl RateLimiter {
+  pub fn new(max_per_second: u32) -> Self {
+    RateLimiter {
+      max_per_second,
+      window_start: Instant::now(),
+      count: 0,
+    }
+  }
+
+  pub fn max_per_second(&self) -> u32 {
+    self.max_per_second
+  }
+
+  /// Counts one more run, or fails with `RateLimitExceeded` if the current
+  /// window is used up. A refused run doesn't count.
+  pub fn check(&mut self) -> Result<(), CoreErrors> {
+    let now = Instant::now();
+    if now.duration_since(self.window_start) >= WINDOW {
+      self.window_start = now;
+      self.count = 0;
+    }
+    if self.count >= self.max_per_second {
+      return Err(CoreErrors::invalid_data(ValidationKind::RateLimitExceeded(
+        self.max_per_second,
+      )));
+    }
+    self.count += 1;
+    Ok(())
+  }
+}
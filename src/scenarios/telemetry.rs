@@ -0,0 +1,65 @@
+// Metrics through the `metrics` facade, behind the `metrics` feature. Without
+// the feature, or without an installed recorder, every call is a no-op.
+//
+// Labels are kept to a bounded set: `protocol` (see `RoundId::protocol`),
+// `round`, `parties` and `error`; nothing per session.
+//
+// corelib_ceremonies_started_total{protocol}          counter
+// corelib_ceremonies_completed_total{protocol}        counter
+// corelib_ceremonies_failed_total{protocol, error}    counter, `error` as in
+//                                                     the `Errors` signal
+// corelib_active_sessions{protocol}                   gauge
+// corelib_round_duration_seconds{protocol, round, parties}  histogram
+// corelib_round_messages_received_total{protocol, round, parties}  counter
+// corelib_round_duplicates_total{protocol, round, parties}  counter
+
+#[cfg(feature = "metrics")]
+use crate::common::messages::Errors;
+use crate::common::messages::RoundMetrics;
+use crate::errors::CoreErrors;
+#[cfg(feature = "metrics")]
+use ::metrics::{counter, gauge, histogram};
+
+#[cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+pub(crate) fn ceremony_started(protocol: &'static str) {
+  #[cfg(feature = "metrics")]
+  {
+    counter!("corelib_ceremonies_started_total", "protocol" => protocol).increment(1);
+    gauge!("corelib_active_sessions", "protocol" => protocol).increment(1.0);
+  }
+}
+
+#[cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+pub(crate) fn ceremony_finished(protocol: &'static str, error: Option<&CoreErrors>) {
+  #[cfg(feature = "metrics")]
+  {
+    gauge!("corelib_active_sessions", "protocol" => protocol).decrement(1.0);
+    match error {
+      None => counter!("corelib_ceremonies_completed_total", "protocol" => protocol).increment(1),
+      Some(e) => counter!(
+        "corelib_ceremonies_failed_total",
+        "protocol" => protocol,
+        "error" => format!("{:?}", Errors::from_core_error(e))
+      )
+      .increment(1),
+    }
+  }
+}
+
+#[cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+pub(crate) fn round_collected(parties: u8, metrics: &RoundMetrics) {
+  #[cfg(feature = "metrics")]
+  {
+    let labels = [
+      ("protocol", metrics.round.protocol().to_string()),
+      ("round", metrics.round.number().to_string()),
+      ("parties", parties.to_string()),
+    ];
+    histogram!("corelib_round_duration_seconds", &labels)
+      .record(metrics.duration_ms as f64 / 1000.0);
+    counter!("corelib_round_messages_received_total", &labels)
+      .increment(metrics.messages_received as u64);
+    counter!("corelib_round_duplicates_total", &labels)
+      .increment(metrics.duplicate_messages_rejected as u64);
+  }
+}
@@ -0,0 +1,768 @@
+#![allow(deprecated)]
+
+use crate::common::config::ProtocolConfig;
+use crate::common::messages::*;
+use crate::common::types::{Digest32, HealthReport, Keystore, RoundId, Threshold};
+use crate::common::utils::shamir_combine;
+use crate::errors::{CoreErrors, CryptoKind, RequestField, ValidationKind};
+use crate::scenarios::local::run_parties;
+use crate::scenarios::runner::RoundRunner;
+use crate::scenarios::{
+  health_check, health_check_respond, run_local_keygen, run_local_sign, safe_keygeneration,
+  safe_rotate_paillier, safe_sign, safe_sign_with_hooks, Decision, PolicyDecision, RateLimiter,
+  RecentDigests, SignHooks, SigningGuard, SigningPolicy, SigningRequest,
+};
+use crate::testing::fixtures::generate_test_keystore_2_of_3;
+use crate::testing::malicious::{Deviation, MaliciousParty};
+use curv::arithmetic::traits::Converter;
+use curv::cryptographic_primitives::proofs::sigma_dlog::DLogProof;
+use curv::elliptic::curves::traits::{ECPoint, ECScalar};
+use curv::{BigInt, GE};
+use multi_party_ecdsa::protocols::multi_party_ecdsa::gg_2018::party_i::{
+  Keys, Parameters, PartyPrivate, SignKeys,
+};
+use std::collections::BTreeMap;
+use std::sync::mpsc::channel;
+use std::sync::Mutex;
+
+fn digest() -> Digest32 {
+  Digest32::from_bytes(&[0x42; 32]).unwrap()
+}
+
+// Runs one party with nobody else on the channels; enough for requests that
+// must be refused before anything is sent.
+fn keygen_alone(participants: u8, threshold: u8, party_id: u8) -> Result<(), CoreErrors> {
+  let (outgoing_sender, _outgoing_receiver) = channel::<Message>();
+  let (_incoming_sender, incoming_receiver) = channel::<Message>();
+  safe_keygeneration(
+    participants,
+    threshold,
+    party_id,
+    outgoing_sender,
+    incoming_receiver,
+  )
+}
+
+fn sign_alone(
+  participants: u8,
+  threshold: u8,
+  party_num_id: u8,
+  keystore: &Keystore,
+  signers_vec: &Vec<usize>,
+) -> Result<(), CoreErrors> {
+  let (outgoing_sender, _outgoing_receiver) = channel::<Message>();
+  let (_incoming_sender, incoming_receiver) = channel::<Message>();
+  safe_sign(
+    participants,
+    threshold,
+    party_num_id,
+    keystore,
+    &digest(),
+    signers_vec,
+    outgoing_sender,
+    incoming_receiver,
+  )
+}
+
+fn unsupported_reason(result: Result<(), CoreErrors>) -> String {
+  match result {
+    Err(CoreErrors::InvalidData {
+      kind: ValidationKind::UnsupportedSetup(reason),
+      ..
+    }) => reason,
+    other => panic!("expected an unsupported setup, got {:?}", other),
+  }
+}
+
+#[test]
+fn threshold_equals_zero_reject() {
+  assert_eq!(
+    unsupported_reason(keygen_alone(3, 0, 0)),
+    "threshold must be at least 1"
+  );
+
+  let mut keystore = generate_test_keystore_2_of_3().remove(0);
+  keystore.params.threshold = Threshold::new(0);
+  assert_eq!(
+    unsupported_reason(sign_alone(1, 0, 0, &keystore, &vec![0])),
+    "threshold must be at least 1"
+  );
+}
+
+// A 2-of-3 keygen in which party 2 corrupts its round-3 shares.
+fn keygen_with_corrupt_party_2() -> Result<Vec<Option<RoundResult>>, CoreErrors> {
+  let malicious = MaliciousParty::new(vec![Deviation::CorruptAead]);
+  run_parties(
+    3,
+    move |party_id, outgoing_sender, incoming_receiver| {
+      if party_id == 2 {
+        malicious.keygeneration(3, 1, party_id, outgoing_sender, incoming_receiver);
+        Ok(())
+      } else {
+        safe_keygeneration(3, 1, party_id, outgoing_sender, incoming_receiver)
+      }
+    },
+    |_, _, _| {},
+  )
+}
+
+#[test]
+fn keygen_abort_recovery() {
+  assert!(keygen_with_corrupt_party_2().is_err());
+
+  // The same ceremony rerun from scratch, party 2 now honest, carries
+  // nothing over from the failed attempt.
+  let keystores = run_local_keygen(3, 1).unwrap();
+  for (i, keystore) in keystores.iter().enumerate() {
+    assert_eq!(keystore.party_index(), i);
+    assert_eq!(keystore.share_count(), 3);
+  }
+  let signatures = run_local_sign(&keystores, &[0, 2], &digest()).unwrap();
+  assert!(signatures[0].verify(&keystores[0].public_key(), &digest()));
+}
+
+#[test]
+fn honest_parties_regroup_as_2_of_2_after_an_aborted_keygen() {
+  assert!(keygen_with_corrupt_party_2().is_err());
+
+  let keystores = run_local_keygen(2, 1).unwrap();
+  for (i, keystore) in keystores.iter().enumerate() {
+    assert_eq!(keystore.party_index(), i);
+    assert_eq!(keystore.share_count(), 2);
+  }
+  let signatures = run_local_sign(&keystores, &[0, 1], &digest()).unwrap();
+  assert!(signatures[0].verify(&keystores[0].public_key(), &digest()));
+}
+
+// Party 0 checks the 2-of-3 fixture wallet; `responder` plays the others.
+fn run_health_check<R>(responder: R) -> HealthReport
+where
+  R: Fn(u8, Keystore, OutgoingChannel, IncomingChannel) + Send + Sync + 'static,
+{
+  let keystores = generate_test_keystore_2_of_3();
+  let results = run_parties(
+    3,
+    move |party_id, outgoing_sender, incoming_receiver| {
+      let keystore = keystores[party_id as usize].clone();
+      if party_id == 0 {
+        health_check(&keystore, b"session", outgoing_sender, incoming_receiver);
+      } else {
+        responder(party_id, keystore, outgoing_sender, incoming_receiver);
+      }
+      Ok(())
+    },
+    |_, _, _| {},
+  )
+  .unwrap();
+  results[0]
+    .as_ref()
+    .and_then(|r| r.as_health_report())
+    .cloned()
+    .unwrap()
+}
+
+#[test]
+fn health_check_all_healthy() {
+  let report = run_health_check(|_, keystore, outgoing_sender, incoming_receiver| {
+    health_check_respond(&keystore, outgoing_sender, incoming_receiver)
+  });
+  assert_eq!(report.healthy, vec![0, 1, 2]);
+  assert!(report.all_healthy());
+}
+
+#[test]
+fn health_check_party_offline() {
+  let report = run_health_check(|party_id, keystore, outgoing_sender, incoming_receiver| {
+    if party_id != 2 {
+      health_check_respond(&keystore, outgoing_sender, incoming_receiver)
+    }
+  });
+  assert_eq!(report.healthy, vec![0, 1]);
+  assert_eq!(report.silent, vec![2]);
+  assert!(report.invalid.is_empty());
+}
+
+#[test]
+fn health_check_wrong_share() {
+  let report = run_health_check(
+    |party_id, mut keystore, outgoing_sender, incoming_receiver| {
+      if party_id == 2 {
+        keystore.shared_keys.x_i = ECScalar::new_random();
+      }
+      health_check_respond(&keystore, outgoing_sender, incoming_receiver)
+    },
+  );
+  assert_eq!(report.healthy, vec![0, 1]);
+  assert_eq!(report.invalid, vec![2]);
+  assert!(report.silent.is_empty());
+}
+
+#[test]
+fn key_export_import_test() {
+  let keystores = generate_test_keystore_2_of_3();
+  let public_key = keystores[0].public_key();
+
+  let from_json = keystores
+    .iter()
+    .map(|k| serde_json::from_str(&serde_json::to_string(k).unwrap()).unwrap())
+    .collect::<Vec<Keystore>>();
+  let signatures = run_local_sign(&from_json, &[0, 1], &digest()).unwrap();
+  assert!(signatures[0].verify(&public_key, &digest()));
+
+  #[cfg(feature = "tsslib")]
+  {
+    use crate::interop::tsslib;
+    let from_tsslib = keystores
+      .iter()
+      .map(|k| {
+        tsslib::import(&tsslib::export(k).unwrap().json, 1)
+          .unwrap()
+          .keystore
+      })
+      .collect::<Vec<Keystore>>();
+    let signatures = run_local_sign(&from_tsslib, &[0, 2], &digest()).unwrap();
+    assert!(signatures[0].verify(&public_key, &digest()));
+  }
+
+  #[cfg(feature = "pem")]
+  {
+    use crate::common::utils::{public_key_from_pem, public_key_to_pem};
+    let pem = public_key_to_pem(public_key.as_point());
+    assert_eq!(
+      public_key_from_pem(&pem).unwrap(),
+      from_json[0].public().y_sum
+    );
+  }
+}
+
+#[test]
+fn n_equals_1_reject() {
+  assert_eq!(
+    unsupported_reason(keygen_alone(1, 0, 0)),
+    "share_count must be at least 2"
+  );
+}
+
+#[test]
+fn sign_wrong_key_reject() {
+  let ours = generate_test_keystore_2_of_3();
+  let mut theirs = run_local_keygen(3, 1).unwrap().remove(1);
+  // Same Paillier keys as our ceremony, so the mix-up can only be caught
+  // by the signing protocol itself.
+  theirs.party_key.ek = ours[1].party_key.ek.clone();
+  theirs.party_key.dk = ours[1].party_key.dk.clone();
+  theirs.paillier_key_vec = ours[1].paillier_key_vec.clone();
+  let keystores = vec![ours[0].clone(), theirs];
+
+  let result = run_parties(
+    2,
+    move |party_id, outgoing_sender, incoming_receiver| {
+      safe_sign(
+        2,
+        1,
+        party_id,
+        &keystores[party_id as usize],
+        &digest(),
+        &vec![0, 1],
+        outgoing_sender,
+        incoming_receiver,
+      )
+    },
+    |_, _, _| {},
+  );
+  assert!(matches!(result, Err(CoreErrors::ExecutionIssue { .. })));
+}
+
+fn mismatched_field(result: Result<(), CoreErrors>) -> RequestField {
+  match result {
+    Err(CoreErrors::RequestMismatch { field, .. }) => field,
+    other => panic!("expected a request mismatch, got {:?}", other),
+  }
+}
+
+#[test]
+fn sign_with_extra_party_reject() {
+  let keystore = generate_test_keystore_2_of_3().remove(0);
+  assert_eq!(
+    mismatched_field(sign_alone(3, 1, 0, &keystore, &vec![0, 1, 2])),
+    RequestField::SignerCount
+  );
+}
+
+#[test]
+fn party_id_out_of_range_reject() {
+  assert_eq!(
+    mismatched_field(keygen_alone(3, 1, 3)),
+    RequestField::OwnIndex
+  );
+
+  let keystore = generate_test_keystore_2_of_3().remove(0);
+  assert_eq!(
+    mismatched_field(sign_alone(2, 1, 2, &keystore, &vec![0, 1])),
+    RequestField::SignerPosition
+  );
+}
+
+#[test]
+fn distributed_key_generation_without_trusted_dealer() {
+  let keystores = run_local_keygen(4, 2).unwrap();
+  let g: GE = ECPoint::generator();
+  let y = keystores[0].public().y_sum;
+
+  let shares: Vec<(usize, _)> = keystores
+    .iter()
+    .map(|k| (k.party_index(), k.shared_keys.x_i))
+    .collect();
+  let secret = shamir_combine(&shares[..3]).unwrap();
+  assert_eq!(g * secret, y);
+
+  // Any three shares reconstruct the same secret...
+  for set in &[[0, 1, 3], [0, 2, 3], [1, 2, 3]] {
+    let subset: Vec<_> = set.iter().map(|&i| shares[i]).collect();
+    assert_eq!(shamir_combine(&subset).unwrap(), secret);
+  }
+
+  // ...but no party ever holds it, not even as one of its shares.
+  for keystore in &keystores {
+    assert_ne!(keystore.shared_keys.x_i, secret);
+    for share in &keystore.party_shares {
+      assert_ne!(*share, secret);
+    }
+  }
+
+  // Two shares are below the threshold and interpolate to something else.
+  for i in 0..shares.len() {
+    for j in i + 1..shares.len() {
+      let guess = shamir_combine(&[shares[i], shares[j]]).unwrap();
+      assert_ne!(g * guess, y);
+    }
+  }
+}
+
+#[test]
+fn concurrent_keygens_are_independent() {
+  let ceremonies: Vec<_> = (0..2)
+    .map(|_| std::thread::spawn(|| run_local_keygen(3, 1).unwrap()))
+    .collect();
+  let y_sums: Vec<GE> = ceremonies
+    .into_iter()
+    .map(|ceremony| ceremony.join().unwrap()[0].public().y_sum)
+    .collect();
+  assert_ne!(y_sums[0], y_sums[1]);
+}
+
+#[test]
+fn key_generation_5_of_7_test() {
+  let keystores = run_local_keygen(7, 4).unwrap();
+  assert_eq!(keystores.len(), 7);
+  let public_key = keystores[0].public_key();
+
+  for signers in &[[0, 1, 2, 3, 4], [2, 3, 4, 5, 6], [0, 2, 3, 5, 6]] {
+    let signatures = run_local_sign(&keystores, signers, &digest()).unwrap();
+    assert!(signatures
+      .iter()
+      .all(|sig| sig.verify(&public_key, &digest())));
+  }
+
+  let refused = run_local_sign(&keystores, &[0, 1, 2, 3], &digest());
+  assert!(matches!(
+    refused,
+    Err(CoreErrors::RequestMismatch {
+      field: RequestField::SignerCount,
+      ..
+    })
+  ));
+}
+
+#[test]
+fn keygen_round_2_decommit_verify() {
+  let params = Parameters {
+    threshold: 1,
+    share_count: 3,
+  };
+  let keys: Vec<Keys> = (1..=3).map(Keys::create).collect();
+  let (bc1_vec, decom_vec): (Vec<_>, Vec<_>) = keys
+    .iter()
+    .map(|k| k.phase1_broadcast_phase3_proof_of_correct_key())
+    .unzip();
+  assert!(keys[0]
+    .phase1_verify_com_phase3_verify_correct_key_phase2_distribute(&params, &decom_vec, &bc1_vec)
+    .is_ok());
+
+  let mut tampered = decom_vec.clone();
+  let mut bytes = BigInt::to_vec(&tampered[1].blind_factor);
+  bytes[0] ^= 0x01;
+  tampered[1].blind_factor = BigInt::from(&bytes[..]);
+  assert!(keys[0]
+    .phase1_verify_com_phase3_verify_correct_key_phase2_distribute(&params, &tampered, &bc1_vec)
+    .is_err());
+}
+
+#[test]
+fn sign_r_value_aggregation_test() {
+  let keystores = generate_test_keystore_2_of_3();
+  let signers = vec![0, 2];
+  let sign_keys: Vec<SignKeys> = signers
+    .iter()
+    .map(|i| {
+      let keystore = &keystores[*i];
+      let private =
+        PartyPrivate::set_private(keystore.party_key.clone(), keystore.shared_keys.clone());
+      SignKeys::create(&private, &keystore.vss_scheme_vec[*i], *i, &signers)
+    })
+    .collect();
+  let (bc1_vec, decommit_vec): (Vec<_>, Vec<_>) =
+    sign_keys.iter().map(|k| k.phase1_broadcast()).unzip();
+
+  let k = sign_keys[0].k_i + sign_keys[1].k_i;
+  let gamma = sign_keys[0].gamma_i + sign_keys[1].gamma_i;
+  let delta_inv = SignKeys::phase3_reconstruct_delta(&[k * gamma]);
+
+  // Signer 0's view, as in sign_rounds: phase 4 over the other signer, plus
+  // its own Gamma_i.
+  let b_proof = DLogProof::prove(&sign_keys[1].gamma_i);
+  let r = SignKeys::phase4(
+    &delta_inv,
+    &[&b_proof],
+    vec![decommit_vec[1].clone()],
+    &vec![bc1_vec[1].clone()],
+  )
+  .unwrap()
+    + decommit_vec[0].g_gamma_i * delta_inv;
+
+  let g: GE = ECPoint::generator();
+  assert_eq!(r, g * k.invert());
+}
+
+#[test]
+fn keygen_paillier_proof_verify() {
+  let (bc1, _) = Keys::create(1).phase1_broadcast_phase3_proof_of_correct_key();
+  assert!(bc1.correct_key_proof.verify(&bc1.e).is_ok());
+
+  let mut tampered = bc1.correct_key_proof.clone();
+  tampered.sigma_vec[0] = &tampered.sigma_vec[0] + &BigInt::from(1);
+  assert!(tampered.verify(&bc1.e).is_err());
+}
+
+// Runs party 0 of the 2-of-3 fixtures with signers 0 and 1 and no peer on
+// the channels; returns what it sent.
+fn sign_alone_with_hooks(hooks: SignHooks) -> (Result<(), CoreErrors>, Vec<Message>) {
+  let keystore = generate_test_keystore_2_of_3().remove(0);
+  let (outgoing_sender, outgoing_receiver) = channel::<Message>();
+  let (_incoming_sender, incoming_receiver) = channel::<Message>();
+  let result = safe_sign_with_hooks(
+    2,
+    1,
+    0,
+    &keystore,
+    &digest(),
+    None,
+    &vec![0, 1],
+    &ProtocolConfig::default(),
+    hooks,
+    outgoing_sender,
+    incoming_receiver,
+  );
+  (result, outgoing_receiver.try_iter().collect())
+}
+
+fn sent_data(sent: &[Message]) -> Vec<(u8, &MessageData)> {
+  sent
+    .iter()
+    .filter_map(|m| match m {
+      Message::Send { target, data, .. } => Some((*target, &**data)),
+      _ => None,
+    })
+    .collect()
+}
+
+#[test]
+fn sign_hooks_guard_refuses_a_repeated_digest() {
+  let guard = Mutex::new(RecentDigests::new(8));
+  assert_eq!(
+    guard.lock().unwrap().check(&digest(), None, &[0, 1]),
+    Decision::Approve
+  );
+
+  let (result, sent) = sign_alone_with_hooks(SignHooks::new().with_guard(&guard));
+  assert!(matches!(
+    result,
+    Err(CoreErrors::InvalidData {
+      kind: ValidationKind::DuplicateDigest,
+      ..
+    })
+  ));
+  // The other signer gets an abort instead of round 1.
+  let sent = sent_data(&sent);
+  assert_eq!(sent.len(), 1);
+  assert!(matches!(sent[0], (1, MessageData::Abort(_))));
+}
+
+#[test]
+fn sign_hooks_rate_limiter_refuses_over_the_limit() {
+  let mut rate_limiter = RateLimiter::new(0);
+  let (result, sent) = sign_alone_with_hooks(SignHooks::new().with_rate_limiter(&mut rate_limiter));
+  assert!(matches!(
+    result,
+    Err(CoreErrors::InvalidData {
+      kind: ValidationKind::RateLimitExceeded(0),
+      ..
+    })
+  ));
+  let sent = sent_data(&sent);
+  assert_eq!(sent.len(), 1);
+  assert!(matches!(sent[0], (1, MessageData::Abort(_))));
+}
+
+// Example policy: a per-request amount limit, taken from the metadata.
+struct AmountLimit(u64);
+
+impl SigningPolicy for AmountLimit {
+  fn evaluate(&self, request: &SigningRequest) -> PolicyDecision {
+    let amount = request
+      .metadata
+      .get("amount")
+      .and_then(|a| a.parse::<u64>().ok());
+    match amount {
+      Some(amount) if amount <= self.0 => PolicyDecision::Approve {
+        annotations: vec![format!("amount {} within limit {}", amount, self.0)],
+      },
+      _ => PolicyDecision::Deny {
+        reason: "amount over limit".to_string(),
+      },
+    }
+  }
+}
+
+struct PanickingPolicy;
+
+impl SigningPolicy for PanickingPolicy {
+  fn evaluate(&self, _request: &SigningRequest) -> PolicyDecision {
+    panic!("policy backend unreachable")
+  }
+}
+
+fn amount(value: &str) -> BTreeMap<String, String> {
+  vec![("amount".to_string(), value.to_string())]
+    .into_iter()
+    .collect()
+}
+
+#[test]
+fn sign_policy_denial_sends_no_protocol_messages() {
+  let metadata = amount("500");
+  let (result, sent) =
+    sign_alone_with_hooks(SignHooks::new().with_policy(&AmountLimit(100), None, &metadata));
+  match result {
+    Err(CoreErrors::InvalidData {
+      kind: ValidationKind::PolicyDenied(reason),
+      ..
+    }) => assert_eq!(reason, "amount over limit"),
+    other => panic!("expected a policy denial, got {:?}", other),
+  }
+  let sent = sent_data(&sent);
+  assert_eq!(sent.len(), 1);
+  assert!(matches!(sent[0], (1, MessageData::Abort(_))));
+}
+
+#[test]
+fn sign_policy_panic_is_caught() {
+  let metadata = BTreeMap::new();
+  let (result, sent) =
+    sign_alone_with_hooks(SignHooks::new().with_policy(&PanickingPolicy, None, &metadata));
+  assert!(matches!(
+    result,
+    Err(CoreErrors::ExecutionIssue {
+      kind: CryptoKind::Panicked(_),
+      ..
+    })
+  ));
+  assert!(sent_data(&sent)
+    .iter()
+    .all(|(_, data)| matches!(data, MessageData::Abort(_))));
+}
+
+#[test]
+fn sign_policy_approval_is_recorded() {
+  let keystores = generate_test_keystore_2_of_3();
+  let public_key = keystores[0].public_key();
+  let results = run_parties(
+    2,
+    move |party_id, outgoing_sender, incoming_receiver| {
+      let metadata = amount("50");
+      safe_sign_with_hooks(
+        2,
+        1,
+        party_id,
+        &keystores[party_id as usize],
+        &digest(),
+        None,
+        &vec![0, 1],
+        &ProtocolConfig::default(),
+        SignHooks::new().with_policy(&AmountLimit(100), None, &metadata),
+        outgoing_sender,
+        incoming_receiver,
+      )
+    },
+    |_, _, _| {},
+  )
+  .unwrap();
+  for result in results {
+    match result {
+      Some(RoundResult::Sign {
+        signature,
+        policy_annotations,
+        ..
+      }) => {
+        assert!(signature.verify(&public_key, &digest()));
+        assert_eq!(policy_annotations, vec!["amount 50 within limit 100"]);
+      }
+      other => panic!("expected a signature, got {:?}", other),
+    }
+  }
+}
+
+// Rotates the Paillier keys of every keystore of the group in-process.
+fn rotate_all(keystores: Vec<Keystore>) -> Vec<Keystore> {
+  let participants = keystores.len() as u8;
+  run_parties(
+    participants,
+    move |party_id, outgoing_sender, incoming_receiver| {
+      safe_rotate_paillier(
+        &keystores[party_id as usize],
+        None,
+        outgoing_sender,
+        incoming_receiver,
+      )
+    },
+    |_, _, _| {},
+  )
+  .unwrap()
+  .into_iter()
+  .map(|result| match result {
+    Some(RoundResult::PaillierRotation { private_key }) => private_key,
+    other => panic!("expected a rotated keystore, got {:?}", other),
+  })
+  .collect()
+}
+
+#[test]
+fn rotate_paillier_in_a_3_party_group() {
+  let old = generate_test_keystore_2_of_3();
+  let rotated = rotate_all(old.clone());
+
+  for (i, (old, new)) in old.iter().zip(&rotated).enumerate() {
+    assert_eq!(new.party_index(), i);
+    assert_eq!(new.public_key(), old.public_key());
+    assert_eq!(new.shared_keys.x_i, old.shared_keys.x_i);
+    assert_ne!(new.party_key.ek.n, old.party_key.ek.n);
+    assert_eq!(new.paillier_key_vec[i].n, new.party_key.ek.n);
+    let ns = |k: &Keystore| {
+      k.paillier_key_vec
+        .iter()
+        .map(|ek| ek.n.clone())
+        .collect::<Vec<_>>()
+    };
+    assert_eq!(ns(new), ns(&rotated[0]));
+  }
+}
+
+#[test]
+fn sign_with_rotated_keystores() {
+  let rotated = rotate_all(generate_test_keystore_2_of_3());
+  let signatures = run_local_sign(&rotated, &[0, 2], &digest()).unwrap();
+  for signature in &signatures {
+    assert!(signature.verify(&rotated[0].public_key(), &digest()));
+  }
+}
+
+#[test]
+fn sign_mixing_old_and_rotated_keystores_fails_cleanly() {
+  let old = generate_test_keystore_2_of_3();
+  let rotated = rotate_all(old.clone());
+  let mixed = vec![old[0].clone(), rotated[1].clone()];
+
+  match run_local_sign(&mixed, &[0, 1], &digest()) {
+    Err(CoreErrors::ExecutionIssue {
+      kind: CryptoKind::RotationMismatch,
+      round,
+      party,
+    }) => {
+      assert_eq!(round, Some(RoundId::Sign(1)));
+      assert!(party.is_some());
+    }
+    other => panic!("expected a rotation mismatch, got {:?}", other),
+  }
+}
+
+// Hands `msg` to party 0 of a 2-party run collecting sign round 3 and
+// returns the delta it accepted from party 1.
+fn collect_sign_round_3(msg: Message) -> Result<curv::FE, CoreErrors> {
+  let (outgoing_sender, _outgoing_receiver) = channel::<Message>();
+  let (incoming_sender, incoming_receiver) = channel::<Message>();
+  incoming_sender.send(msg).unwrap();
+  // Nothing else arrives; a message that isn't taken fails the round at once.
+  drop(incoming_sender);
+  let runner = RoundRunner::new(0, 2, &outgoing_sender, incoming_receiver);
+  let mine: curv::FE = ECScalar::new_random();
+  let (collected, _) = runner.collect_available(vec![Some(mine), None], RoundId::Sign(3))?;
+  collected[1].ok_or(CoreErrors::invalid_data(ValidationKind::EmptyResult))
+}
+
+#[test]
+fn bit_flipped_message_is_rejected() {
+  let delta: curv::FE = ECScalar::new_random();
+  let sent = Message::make_send(1, 0, &MessageData::SignRound3(delta));
+  let encoded = serde_json::to_vec(&sent).unwrap();
+  assert_eq!(collect_sign_round_3(sent).unwrap(), delta);
+
+  // Messages carry no MAC yet, so a flip inside the scalar itself decodes
+  // to another valid delta; every other byte is covered.
+  let payload = serde_json::to_vec(&delta).unwrap();
+  let payload_start = encoded
+    .windows(payload.len())
+    .position(|w| w == payload.as_slice())
+    .unwrap();
+  let payload_span = payload_start..payload_start + payload.len();
+
+  for position in (0..encoded.len()).filter(|p| !payload_span.contains(p)) {
+    let mut corrupted = encoded.clone();
+    corrupted[position] ^= 0x01;
+    let accepted = serde_json::from_slice::<Message>(&corrupted)
+      .ok()
+      .and_then(|msg| collect_sign_round_3(msg).ok());
+    // A flip the round lets through must not have changed what it takes:
+    // one in the name of the defaulted `session` field is ignored.
+    if let Some(accepted) = accepted {
+      assert_eq!(
+        accepted,
+        delta,
+        "flip at {} in {}",
+        position,
+        String::from_utf8_lossy(&corrupted)
+      );
+    }
+  }
+}
+
+// Looks for state carried from one ceremony into the next.
+#[cfg(feature = "stress-tests")]
+#[test]
+fn stress_sign_100() {
+  let keystores = run_local_keygen(3, 1).unwrap();
+  let before = serde_json::to_string(&keystores).unwrap();
+  let public_key = keystores[0].public_key();
+
+  for i in 0..100u8 {
+    let mut bytes = [0x42; 32];
+    bytes[0] = i;
+    let digest = Digest32::from_bytes(&bytes).unwrap();
+    let signers = [[0, 1], [0, 2], [1, 2]][i as usize % 3];
+    let signatures = run_local_sign(&keystores, &signers, &digest).unwrap();
+    for signature in &signatures {
+      assert!(signature.verify(&public_key, &digest), "digest {}", i);
+    }
+  }
+
+  assert_eq!(serde_json::to_string(&keystores).unwrap(), before);
+}
@@ -0,0 +1,170 @@
+use crate::common::config::ProtocolConfig;
+use crate::common::events::{EventSink, SinkClosed};
+use crate::common::messages::*;
+use crate::common::types::{Digest32, Keystore, RoundId, Signature};
+use crate::errors::{CoreErrors, ErrorClass, ValidationKind};
+use crate::scenarios::safe_sign_with_sink;
+use serde::{Deserialize, Serialize};
+use std::sync::mpsc::channel;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// Caller-chosen id of a signing session, as carried in the `session` field
+/// of `Message`.
+pub type SessionId = Vec<u8>;
+
+/// Record of one party's view of a signing run, for audits.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct SigningTranscript {
+  pub session_id: SessionId,
+  /// `(sender, target)` and data of every message this party sent or
+  /// received, by round: entry `r` holds sign round `r + 1`. Ids are the
+  /// ones on the wire.
+  pub round_messages: Vec<Vec<(u8, u8, MessageData)>>,
+  pub final_signature: Signature,
+}
+
+type Records = Arc<Mutex<Vec<(u8, u8, MessageData)>>>;
+
+struct Recorder<'a> {
+  outgoing: &'a OutgoingChannel,
+  records: Records,
+  signature: Mutex<Option<Signature>>,
+}
+
+impl EventSink for Recorder<'_> {
+  fn on_send(&self, sender: u8, target: u8, data: MessageData) -> Result<(), SinkClosed> {
+    record(&self.records, sender, target, &data);
+    self.outgoing.on_send(sender, target, data)
+  }
+
+  fn on_send_shared(
+    &self,
+    sender: u8,
+    target: u8,
+    data: Arc<MessageData>,
+  ) -> Result<(), SinkClosed> {
+    record(&self.records, sender, target, &data);
+    self.outgoing.on_send_shared(sender, target, data)
+  }
+
+  fn on_log(&self, line: String) -> Result<(), SinkClosed> {
+    self.outgoing.on_log(line)
+  }
+
+  fn on_progress(&self, metrics: RoundMetrics) -> Result<(), SinkClosed> {
+    self.outgoing.on_progress(metrics)
+  }
+
+  fn on_error(&self, error: Errors) -> Result<(), SinkClosed> {
+    self.outgoing.on_error(error)
+  }
+
+  fn on_error_class(&self, class: ErrorClass) -> Result<(), SinkClosed> {
+    self.outgoing.on_error_class(class)
+  }
+
+  fn on_sends_issued(&self) -> Result<(), SinkClosed> {
+    self.outgoing.on_sends_issued()
+  }
+
+  fn flush(&self, timeout: Duration) -> bool {
+    self.outgoing.flush(timeout)
+  }
+
+  fn on_complete(&self, result: RoundResult) -> Result<(), SinkClosed> {
+    if let Some(signature) = result.as_signature() {
+      *self.signature.lock().unwrap() = Some(signature.clone());
+    }
+    self.outgoing.on_complete(result)
+  }
+
+  fn on_quit(&self) -> Result<(), SinkClosed> {
+    self.outgoing.on_quit()
+  }
+}
+
+fn record(records: &Records, sender: u8, target: u8, data: &MessageData) {
+  if let Some(RoundId::Sign(_)) = data.round() {
+    records.lock().unwrap().push((sender, target, data.clone()));
+  }
+}
+
+/// Like `safe_sign_with_config`, also returning the messages of the run and
+/// its signature as a `SigningTranscript` for `session_id`. Everything is
+/// still reported on `outgoing_sender` as usual. Incoming messages are
+/// recorded as they arrive, including ones the run later drops.
+pub fn safe_sign_with_transcript(
+  participants: u8,
+  threshold: u8,
+  party_num_id: u8,
+  keystore: &Keystore,
+  digest: &Digest32,
+  signers_vec: &Vec<usize>,
+  session_id: SessionId,
+  config: &ProtocolConfig,
+  outgoing_sender: OutgoingChannel,
+  incoming_receiver: IncomingChannel,
+) -> Result<SigningTranscript, CoreErrors> {
+  let records: Records = Arc::new(Mutex::new(vec![]));
+
+  let (tee_sender, tee_receiver) = channel::<Message>();
+  let tee_records = records.clone();
+  thread::spawn(move || {
+    for msg in incoming_receiver {
+      if let Message::Send {
+        sender,
+        target,
+        data,
+        ..
+      } = &msg
+      {
+        record(&tee_records, *sender, *target, data);
+      }
+      if tee_sender.send(msg).is_err() {
+        return;
+      }
+    }
+  });
+
+  let recorder = Recorder {
+    outgoing: &outgoing_sender,
+    records: records.clone(),
+    signature: Mutex::new(None),
+  };
+  safe_sign_with_sink(
+    participants,
+    threshold,
+    party_num_id,
+    keystore,
+    digest,
+    None,
+    signers_vec,
+    config,
+    &recorder,
+    tee_receiver,
+  )?;
+
+  let final_signature = recorder
+    .signature
+    .into_inner()
+    .unwrap()
+    .ok_or(CoreErrors::invalid_data(ValidationKind::EmptyResult))?;
+  let mut round_messages: Vec<Vec<(u8, u8, MessageData)>> = vec![];
+  for (sender, target, data) in records.lock().unwrap().drain(..) {
+    if let Some(RoundId::Sign(round)) = data.round() {
+      let index = round as usize - 1;
+      if round_messages.len() <= index {
+        round_messages.resize(index + 1, vec![]);
+      }
+      round_messages[index].push((sender, target, data));
+    }
+  }
+  Ok(SigningTranscript {
+    session_id,
+    round_messages,
+    final_signature,
+  })
+}
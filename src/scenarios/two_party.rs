@@ -0,0 +1,217 @@
+use crate::common::messages::*;
+use crate::common::types::{Digest32, Keystore, Signature};
+use crate::errors::{CoreErrors, ValidationKind};
+use crate::scenarios::{safe_keygeneration, safe_sign};
+use std::sync::mpsc::{channel, Receiver, Sender, TryRecvError};
+use std::thread;
+use std::time::Duration;
+
+const PARTICIPANTS: u8 = 2;
+const THRESHOLD: u8 = 1;
+const CLIENT_ID: u8 = 0;
+const SERVER_ID: u8 = 1;
+const PUMP_POLL_MS: u64 = 10;
+
+/// A bidirectional message stream between the client and the server. The
+/// transport behind it is up to the caller, e.g. a thread forwarding both
+/// channels over a socket; `Duplex::pair` connects two ends in memory.
+#[derive(Debug)]
+pub struct Duplex {
+  pub sender: Sender<Message>,
+  pub receiver: Receiver<Message>,
+}
+
+impl Duplex {
+  /// Two ends of an in-memory stream, one for each side.
+  pub fn pair() -> (Duplex, Duplex) {
+    let (a_sender, b_receiver) = channel();
+    let (b_sender, a_receiver) = channel();
+    (
+      Duplex {
+        sender: a_sender,
+        receiver: a_receiver,
+      },
+      Duplex {
+        sender: b_sender,
+        receiver: b_receiver,
+      },
+    )
+  }
+}
+
+/// The device side of a 2-of-2 wallet; plays party 0.
+#[derive(Debug)]
+pub struct TwoPartyClient(TwoParty);
+
+/// The backend side of a 2-of-2 wallet; plays party 1.
+#[derive(Debug)]
+pub struct TwoPartyServer(TwoParty);
+
+impl TwoPartyClient {
+  pub fn new(link: Duplex) -> Self {
+    TwoPartyClient(TwoParty::new(CLIENT_ID, link))
+  }
+
+  /// Stamps every message with `session` and ignores messages of any other
+  /// session. Both sides have to use the same one.
+  pub fn with_session(self, session: Vec<u8>) -> Self {
+    TwoPartyClient(self.0.with_session(session))
+  }
+
+  /// Runs keygen with the server, which has to call `keygen` as well.
+  pub fn keygen(&self) -> Result<Keystore, CoreErrors> {
+    self.0.keygen()
+  }
+
+  /// Signs `digest` with the server, which has to call `sign` with the same
+  /// digest.
+  pub fn sign(&self, keystore: &Keystore, digest: &Digest32) -> Result<Signature, CoreErrors> {
+    self.0.sign(keystore, digest)
+  }
+}
+
+impl TwoPartyServer {
+  pub fn new(link: Duplex) -> Self {
+    TwoPartyServer(TwoParty::new(SERVER_ID, link))
+  }
+
+  /// Stamps every message with `session` and ignores messages of any other
+  /// session. Both sides have to use the same one.
+  pub fn with_session(self, session: Vec<u8>) -> Self {
+    TwoPartyServer(self.0.with_session(session))
+  }
+
+  /// Runs keygen with the client, which has to call `keygen` as well.
+  pub fn keygen(&self) -> Result<Keystore, CoreErrors> {
+    self.0.keygen()
+  }
+
+  /// Signs `digest` with the client, which has to call `sign` with the same
+  /// digest.
+  pub fn sign(&self, keystore: &Keystore, digest: &Digest32) -> Result<Signature, CoreErrors> {
+    self.0.sign(keystore, digest)
+  }
+}
+
+#[derive(Debug)]
+struct TwoParty {
+  party_id: u8,
+  link: Duplex,
+  session: Option<Vec<u8>>,
+}
+
+impl TwoParty {
+  fn new(party_id: u8, link: Duplex) -> Self {
+    TwoParty {
+      party_id,
+      link,
+      session: None,
+    }
+  }
+
+  fn with_session(mut self, session: Vec<u8>) -> Self {
+    self.session = Some(session);
+    self
+  }
+
+  fn keygen(&self) -> Result<Keystore, CoreErrors> {
+    let party_id = self.party_id;
+    let result = self.drive(move |outgoing_sender, incoming_receiver| {
+      safe_keygeneration(
+        PARTICIPANTS,
+        THRESHOLD,
+        party_id,
+        outgoing_sender,
+        incoming_receiver,
+      )
+    })?;
+    result
+      .as_keystore()
+      .cloned()
+      .ok_or(CoreErrors::invalid_data(ValidationKind::EmptyResult))
+  }
+
+  fn sign(&self, keystore: &Keystore, digest: &Digest32) -> Result<Signature, CoreErrors> {
+    let party_id = self.party_id;
+    let keystore = keystore.clone();
+    let digest = *digest;
+    let result = self.drive(move |outgoing_sender, incoming_receiver| {
+      safe_sign(
+        PARTICIPANTS,
+        THRESHOLD,
+        party_id,
+        &keystore,
+        &digest,
+        &vec![CLIENT_ID as usize, SERVER_ID as usize],
+        outgoing_sender,
+        incoming_receiver,
+      )
+    })?;
+    result
+      .as_signature()
+      .cloned()
+      .ok_or(CoreErrors::invalid_data(ValidationKind::EmptyResult))
+  }
+
+  /// Runs `party` on its own thread and bridges its channels to the link
+  /// until it is done. Once the link is closed in either direction the
+  /// party's incoming channel is closed too, so it fails with
+  /// `TransportKind::Disconnected`.
+  fn drive<F>(&self, party: F) -> Result<RoundResult, CoreErrors>
+  where
+    F: FnOnce(OutgoingChannel, IncomingChannel) -> Result<(), CoreErrors> + Send + 'static,
+  {
+    let (outgoing_sender, outgoing_receiver) = channel::<Message>();
+    let (incoming_sender, incoming_receiver) = channel::<Message>();
+    let handle = thread::spawn(move || party(outgoing_sender, incoming_receiver));
+
+    let mut incoming = Some(incoming_sender);
+    let mut result = None;
+    loop {
+      let mut idle = true;
+
+      match outgoing_receiver.try_recv() {
+        Ok(msg) => {
+          idle = false;
+          match msg {
+            Message::Complete(r) => result = Some(r),
+            mut msg @ Message::Send { .. } => {
+              if let Message::Send { session, .. } = &mut msg {
+                *session = self.session.clone();
+              }
+              if self.link.sender.send(msg).is_err() {
+                incoming = None;
+              }
+            }
+            _ => {}
+          }
+        }
+        Err(TryRecvError::Disconnected) => break,
+        Err(TryRecvError::Empty) => {}
+      }
+
+      if let Some(sender) = &incoming {
+        match self.link.receiver.try_recv() {
+          Ok(msg) => {
+            idle = false;
+            // Control messages are not part of the run.
+            if let Message::Send { session, .. } = &msg {
+              if *session == self.session {
+                let _ = sender.send(msg);
+              }
+            }
+          }
+          Err(TryRecvError::Disconnected) => incoming = None,
+          Err(TryRecvError::Empty) => {}
+        }
+      }
+
+      if idle {
+        thread::sleep(Duration::from_millis(PUMP_POLL_MS));
+      }
+    }
+
+    handle.join().expect("two-party run panicked")?;
+    result.ok_or(CoreErrors::invalid_data(ValidationKind::EmptyResult))
+  }
+}
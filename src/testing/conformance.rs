@@ -0,0 +1,567 @@
+// Wire-format conformance kit for relays and transports written outside this
+// crate. It has three parts:
+//
+// - canonical messages: every wire variant, encoded in every supported
+//   format (`fixtures`);
+// - `verify_roundtrip`, which runs a foreign codec over those fixtures and
+//   checks that what it re-encodes still means the same thing to us;
+// - a golden transcript of a complete 2-of-3 keygen and sign, every message
+//   in order, for `verify_transcript` to replay through a transport.
+//
+// Protocol messages carry fresh randomness, so the fixture directory is
+// recorded once with `write_fixtures` and only ever checked afterwards with
+// `check_fixtures`. Regenerate it from a dedicated, explicitly run test and
+// never as a side effect of the checks, so that drift fails instead of
+// quietly rewriting the fixtures.
+
+use crate::common::messages::*;
+use crate::common::types::{CipherId, Digest32, RoundId, SchnorrSignature, SealedData, AEAD};
+use crate::errors::{CoreErrors, ErrorClass, ValidationKind};
+use crate::scenarios::local::{run_keygen_observed, run_sign_observed};
+use crate::testing::fixtures::generate_test_keystore_2_of_3;
+use curv::arithmetic::traits::Converter;
+use curv::elliptic::curves::traits::{ECPoint, ECScalar};
+use curv::{BigInt, FE, GE};
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+/// File of the golden transcript inside each format's fixture directory,
+/// one encoded message per line.
+pub const GOLDEN_TRANSCRIPT_FILE: &str = "golden_2_of_3.jsonl";
+
+/// Digest signed in the golden transcript.
+pub fn golden_digest() -> Digest32 {
+  Digest32::from_bytes(&[0x42; 32]).expect("golden digest is non-zero")
+}
+
+/// An encoding of `Message` that transports may put on the wire.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WireFormat {
+  /// serde's default, externally tagged JSON.
+  Json,
+  /// The adjacently tagged JSON of `common::json`.
+  #[cfg(feature = "json")]
+  TaggedJson,
+}
+
+impl WireFormat {
+  pub fn all() -> Vec<WireFormat> {
+    vec![
+      WireFormat::Json,
+      #[cfg(feature = "json")]
+      WireFormat::TaggedJson,
+    ]
+  }
+
+  /// Name of the format's fixture directory.
+  pub fn name(&self) -> &'static str {
+    match self {
+      WireFormat::Json => "json",
+      #[cfg(feature = "json")]
+      WireFormat::TaggedJson => "tagged_json",
+    }
+  }
+
+  pub fn encode(&self, msg: &Message) -> Result<String, CoreErrors> {
+    match self {
+      WireFormat::Json => serde_json::to_string(msg).map_err(malformed),
+      #[cfg(feature = "json")]
+      WireFormat::TaggedJson => crate::common::json::to_string(msg),
+    }
+  }
+
+  pub fn decode(&self, encoded: &str) -> Result<Message, CoreErrors> {
+    match self {
+      WireFormat::Json => serde_json::from_str(encoded).map_err(malformed),
+      #[cfg(feature = "json")]
+      WireFormat::TaggedJson => crate::common::json::from_str(encoded),
+    }
+  }
+}
+
+/// One canonical message in one wire format.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fixture {
+  pub name: String,
+  pub format: WireFormat,
+  pub encoded: String,
+}
+
+/// Both halves of the golden 2-of-3 run. Party ids in `sign` are signer
+/// positions, as on the wire.
+#[derive(Debug, Clone)]
+pub struct GoldenTranscript {
+  pub keygen: Vec<Message>,
+  pub sign: Vec<Message>,
+}
+
+impl GoldenTranscript {
+  /// Every message of the run in order, keygen first.
+  pub fn messages(&self) -> impl Iterator<Item = &Message> {
+    self.keygen.iter().chain(self.sign.iter())
+  }
+}
+
+fn malformed(e: serde_json::Error) -> CoreErrors {
+  CoreErrors::invalid_data(ValidationKind::Malformed(e.to_string()))
+}
+
+fn nonconforming(context: &str, reason: impl std::fmt::Display) -> CoreErrors {
+  CoreErrors::invalid_data(ValidationKind::Malformed(format!(
+    "{}: {}",
+    context, reason
+  )))
+}
+
+fn scalar(n: u32) -> FE {
+  ECScalar::from(&BigInt::from(n))
+}
+
+fn point(n: u32) -> GE {
+  GE::generator() * scalar(n)
+}
+
+fn send(sender: u8, target: u8, data: MessageData) -> Message {
+  Message::Send {
+    sender,
+    target,
+    session: None,
+    data: Arc::new(data),
+    sent_at_ms: None,
+    attempt: None,
+  }
+}
+
+fn data_name(data: &MessageData) -> String {
+  match (data, data.round()) {
+    (MessageData::Abort(_), _) => "abort".to_string(),
+    (MessageData::KeyGenRound3Sealed(_), _) => "keygen_3_sealed".to_string(),
+    (MessageData::KeyGenRound1Attested(_), _) => "keygen_1_attested".to_string(),
+    (_, Some(round)) => format!("{}_{}", round.protocol(), round.number()),
+    (_, None) => "none".to_string(),
+  }
+}
+
+/// Messages whose content can be fixed without running a protocol: the
+/// control messages, the optional envelope fields, and every data variant
+/// built from constants or the fixed test keystores.
+#[allow(deprecated)]
+pub fn canonical_messages() -> Vec<(String, Message)> {
+  let keystores = generate_test_keystore_2_of_3();
+  let mut messages = vec![
+    ("none".to_string(), send(0, 1, MessageData::None)),
+    (
+      "abort".to_string(),
+      send(
+        2,
+        0,
+        MessageData::Abort("Signing refused (duplicate)".to_string()),
+      ),
+    ),
+    (
+      "keygen_3".to_string(),
+      send(
+        0,
+        2,
+        MessageData::KeyGenRound3(AEAD {
+          ciphertext: (0..32).collect(),
+          tag: vec![0xff; 16],
+        }),
+      ),
+    ),
+    (
+      "keygen_3_sealed".to_string(),
+      send(
+        0,
+        2,
+        MessageData::KeyGenRound3Sealed(SealedData::V1 {
+          cipher: CipherId::Aes256Gcm,
+          nonce: [7; 12],
+          ciphertext: (0..32).collect(),
+          tag: [0xff; 16],
+        }),
+      ),
+    ),
+    (
+      "keygen_4".to_string(),
+      send(
+        1,
+        0,
+        MessageData::KeyGenRound4(keystores[1].vss_scheme_vec[1].clone()),
+      ),
+    ),
+    (
+      "sign_3".to_string(),
+      send(0, 1, MessageData::SignRound3(scalar(3))),
+    ),
+    (
+      "sign_9".to_string(),
+      send(1, 0, MessageData::SignRound9(scalar(9))),
+    ),
+    (
+      "paillier_rotation_2".to_string(),
+      send(
+        2,
+        1,
+        MessageData::PaillierRotationRound2(PaillierRotationConfirm {
+          keys_hash: BigInt::from_hex(&"fe".repeat(32)),
+        }),
+      ),
+    ),
+    (
+      "health_check_1".to_string(),
+      send(
+        0,
+        1,
+        MessageData::HealthCheckChallenge(HealthCheckChallenge {
+          session_id: b"conformance".to_vec(),
+          challenge: BigInt::from(0x0102_0304),
+        }),
+      ),
+    ),
+    (
+      "health_check_2".to_string(),
+      send(
+        1,
+        0,
+        MessageData::HealthCheckResponse(HealthCheckResponse {
+          commitment: point(5),
+          response: scalar(6),
+        }),
+      ),
+    ),
+    (
+      "schnorr_sign_1".to_string(),
+      send(
+        0,
+        1,
+        MessageData::SchnorrSignRound1(SchnorrNonces {
+          d: point(7),
+          e: point(8),
+        }),
+      ),
+    ),
+    (
+      "schnorr_sign_2".to_string(),
+      send(
+        1,
+        0,
+        MessageData::SchnorrSignRound2(SchnorrPartial { s: scalar(10) }),
+      ),
+    ),
+    (
+      "broadcast".to_string(),
+      Message::Broadcast {
+        sender: 2,
+        session: None,
+        data: Arc::new(MessageData::SignRound3(scalar(11))),
+        sent_at_ms: None,
+        attempt: None,
+      },
+    ),
+    (
+      "envelope".to_string(),
+      Message::Send {
+        sender: 1,
+        target: 2,
+        session: Some(b"session".to_vec()),
+        data: Arc::new(MessageData::SignRound9(scalar(12))),
+        sent_at_ms: Some(1_700_000_000_000),
+        attempt: Some(3),
+      },
+    ),
+    (
+      "complete".to_string(),
+      Message::Complete(RoundResult::SchnorrSign {
+        sig: SchnorrSignature::new(point(13), scalar(14)),
+      }),
+    ),
+    ("quit".to_string(), Message::Quit),
+    ("error".to_string(), Message::Error(Errors::CollectTimeout)),
+    (
+      "classification".to_string(),
+      Message::Classification(ErrorClass::RetryDifferentPeers { exclude: vec![2] }),
+    ),
+    ("log".to_string(), Message::Log("conformance".to_string())),
+    ("sends_issued".to_string(), Message::SendsIssued),
+    (
+      "metrics".to_string(),
+      Message::Metrics(RoundMetrics {
+        round: RoundId::Sign(2),
+        duration_ms: 15,
+        messages_received: 2,
+        duplicate_messages_rejected: 1,
+      }),
+    ),
+  ];
+  messages.sort_by(|a, b| a.0.cmp(&b.0));
+  messages
+}
+
+/// Runs a 2-of-3 keygen and signs `golden_digest()` with parties 0 and 1,
+/// recording every message in the order it was routed. Each run gives a new
+/// transcript; the golden one is the run saved by `write_fixtures`.
+pub fn record_golden_transcript() -> Result<GoldenTranscript, CoreErrors> {
+  let mut keygen = vec![];
+  let keystores = run_keygen_observed(3, 1, |sender, target, data| {
+    keygen.push(send(sender, target, data.clone()))
+  })?;
+  let mut sign = vec![];
+  run_sign_observed(
+    &keystores,
+    &[0, 1],
+    &golden_digest(),
+    |sender, target, data| sign.push(send(sender, target, data.clone())),
+  )?;
+  Ok(GoldenTranscript { keygen, sign })
+}
+
+/// The canonical messages in every format, plus the first message of each
+/// data variant in `transcript`, which covers the variants that cannot be
+/// built from constants.
+pub fn fixtures(transcript: &GoldenTranscript) -> Result<Vec<Fixture>, CoreErrors> {
+  let mut messages = canonical_messages();
+  for msg in transcript.messages() {
+    if let Message::Send { data, .. } = msg {
+      let name = data_name(data);
+      if !messages.iter().any(|(n, _)| *n == name) {
+        messages.push((name, msg.clone()));
+      }
+    }
+  }
+
+  let mut fixtures = vec![];
+  for format in WireFormat::all() {
+    for (name, msg) in &messages {
+      fixtures.push(Fixture {
+        name: name.clone(),
+        format,
+        encoded: format.encode(msg)?,
+      });
+    }
+  }
+  Ok(fixtures)
+}
+
+/// Runs a foreign codec over `fixtures`: `decoder` parses each encoding into
+/// the implementation's own representation and `encoder` writes it back. The
+/// result must decode here and re-encode to the fixture exactly, so dropped
+/// fields, renamed tags and truncated numbers are all caught.
+pub fn verify_roundtrip<T, D, E>(
+  fixtures: &[Fixture],
+  mut decoder: D,
+  mut encoder: E,
+) -> Result<(), CoreErrors>
+where
+  D: FnMut(WireFormat, &str) -> Result<T, String>,
+  E: FnMut(WireFormat, &T) -> Result<String, String>,
+{
+  for fixture in fixtures {
+    let context = format!("{} ({})", fixture.name, fixture.format.name());
+    let decoded = decoder(fixture.format, &fixture.encoded)
+      .map_err(|e| nonconforming(&context, format!("decoder failed: {}", e)))?;
+    let encoded = encoder(fixture.format, &decoded)
+      .map_err(|e| nonconforming(&context, format!("encoder failed: {}", e)))?;
+    let msg = fixture
+      .format
+      .decode(&encoded)
+      .map_err(|e| nonconforming(&context, format!("re-encoding does not decode: {}", e)))?;
+    if fixture.format.encode(&msg)? != fixture.encoded {
+      return Err(nonconforming(&context, "re-encoding changed the message"));
+    }
+  }
+  Ok(())
+}
+
+/// Replays `transcript` through a transport. `relay` is given each message,
+/// encoded in `format`, and returns what each party received as `(party,
+/// bytes)`; a message must reach exactly its target, unchanged.
+pub fn verify_transcript<R>(
+  transcript: &[Message],
+  format: WireFormat,
+  mut relay: R,
+) -> Result<(), CoreErrors>
+where
+  R: FnMut(&str) -> Result<Vec<(u8, String)>, String>,
+{
+  for (i, msg) in transcript.iter().enumerate() {
+    let context = format!("transcript message {} ({})", i, format.name());
+    let target = match msg.relay_target() {
+      RelayDecision::To(target) => target,
+      _ => return Err(nonconforming(&context, "not a routed message")),
+    };
+    let encoded = format.encode(msg)?;
+    let delivered = relay(&encoded).map_err(|e| nonconforming(&context, e))?;
+    match delivered.as_slice() {
+      [(party, bytes)] if *party == target && *bytes == encoded => {}
+      [(party, _)] if *party != target => {
+        return Err(nonconforming(
+          &context,
+          format!("delivered to {} instead of {}", party, target),
+        ))
+      }
+      [_] => return Err(nonconforming(&context, "bytes changed in transit")),
+      _ => {
+        return Err(nonconforming(
+          &context,
+          format!("delivered {} times", delivered.len()),
+        ))
+      }
+    }
+  }
+  Ok(())
+}
+
+/// Records a golden transcript and writes it, with the fixtures built from
+/// it, to `dir/<format>/`. Overwrites what is there; only call it when the
+/// wire format is meant to change.
+pub fn write_fixtures(dir: &Path) -> Result<(), CoreErrors> {
+  let transcript = record_golden_transcript()?;
+  for format in WireFormat::all() {
+    let format_dir = dir.join(format.name());
+    fs::create_dir_all(&format_dir).map_err(|e| nonconforming("fixtures", e))?;
+    let lines = transcript
+      .messages()
+      .map(|msg| format.encode(msg))
+      .collect::<Result<Vec<String>, CoreErrors>>()?;
+    fs::write(
+      format_dir.join(GOLDEN_TRANSCRIPT_FILE),
+      lines.join("\n") + "\n",
+    )
+    .map_err(|e| nonconforming("fixtures", e))?;
+  }
+  for fixture in fixtures(&transcript)? {
+    let path = dir
+      .join(fixture.format.name())
+      .join(format!("{}.json", fixture.name));
+    fs::write(path, fixture.encoded + "\n").map_err(|e| nonconforming("fixtures", e))?;
+  }
+  Ok(())
+}
+
+/// Loads the golden transcript written by `write_fixtures` in `format`.
+pub fn read_golden_transcript(dir: &Path, format: WireFormat) -> Result<Vec<Message>, CoreErrors> {
+  let path = dir.join(format.name()).join(GOLDEN_TRANSCRIPT_FILE);
+  let lines = fs::read_to_string(&path).map_err(|e| nonconforming("fixtures", e))?;
+  lines
+    .lines()
+    .filter(|l| !l.is_empty())
+    .map(|l| format.decode(l))
+    .collect()
+}
+
+/// Loads the fixtures written by `write_fixtures`, in every format.
+pub fn read_fixtures(dir: &Path) -> Result<Vec<Fixture>, CoreErrors> {
+  let mut fixtures = vec![];
+  for format in WireFormat::all() {
+    let format_dir = dir.join(format.name());
+    let mut entries = fs::read_dir(&format_dir)
+      .map_err(|e| nonconforming("fixtures", e))?
+      .map(|entry| entry.map(|e| e.path()))
+      .collect::<Result<Vec<_>, _>>()
+      .map_err(|e| nonconforming("fixtures", e))?;
+    entries.sort();
+    for path in entries {
+      if path.extension().map_or(true, |ext| ext != "json") {
+        continue;
+      }
+      let name = path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or_default()
+        .to_string();
+      let encoded = fs::read_to_string(&path).map_err(|e| nonconforming("fixtures", e))?;
+      fixtures.push(Fixture {
+        name,
+        format,
+        encoded: encoded.trim_end().to_string(),
+      });
+    }
+  }
+  Ok(fixtures)
+}
+
+/// Checks the fixture directory against this crate: the canonical messages
+/// still encode as recorded, the crate's own codecs pass `verify_roundtrip`
+/// and `verify_transcript`, and no variant has lost its fixture.
+pub fn check_fixtures(dir: &Path) -> Result<(), CoreErrors> {
+  let on_disk = read_fixtures(dir)?;
+  for format in WireFormat::all() {
+    for (name, msg) in canonical_messages() {
+      let encoded = format.encode(&msg)?;
+      let recorded = on_disk
+        .iter()
+        .find(|f| f.format == format && f.name == name)
+        .ok_or_else(|| nonconforming(&name, "fixture missing"))?;
+      if recorded.encoded != encoded {
+        return Err(nonconforming(
+          &format!("{} ({})", name, format.name()),
+          "encoding drifted from the fixture",
+        ));
+      }
+    }
+
+    let transcript = read_golden_transcript(dir, format)?;
+    for msg in &transcript {
+      if let Message::Send { data, .. } = msg {
+        let name = data_name(data);
+        if !on_disk.iter().any(|f| f.format == format && f.name == name) {
+          return Err(nonconforming(&name, "fixture missing"));
+        }
+      }
+    }
+    verify_transcript(&transcript, format, |encoded| {
+      let msg = format.decode(encoded).map_err(|e| e.to_string())?;
+      let target = match msg.relay_target() {
+        RelayDecision::To(target) => target,
+        _ => return Err("not a routed message".to_string()),
+      };
+      Ok(vec![(
+        target,
+        format.encode(&msg).map_err(|e| e.to_string())?,
+      )])
+    })?;
+  }
+
+  verify_roundtrip(
+    &on_disk,
+    |format, encoded| format.decode(encoded).map_err(|e| e.to_string()),
+    |format, msg| format.encode(msg).map_err(|e| e.to_string()),
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::path::PathBuf;
+
+  fn fixture_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/conformance")
+  }
+
+  // Only run when the wire format is meant to change:
+  // cargo test --all-features regenerate_conformance_fixtures -- --ignored
+  #[test]
+  #[ignore]
+  fn regenerate_conformance_fixtures() {
+    write_fixtures(&fixture_dir()).unwrap();
+  }
+
+  #[test]
+  fn conformance_fixtures_are_current() {
+    check_fixtures(&fixture_dir()).unwrap();
+  }
+
+  #[test]
+  fn fresh_transcript_roundtrips_through_own_codecs() {
+    let transcript = record_golden_transcript().unwrap();
+    let fixtures = fixtures(&transcript).unwrap();
+    verify_roundtrip(
+      &fixtures,
+      |format, encoded| format.decode(encoded).map_err(|e| e.to_string()),
+      |format, msg| format.encode(msg).map_err(|e| e.to_string()),
+    )
+    .unwrap();
+  }
+}
@@ -0,0 +1,190 @@
+// Fixed keystores for tests. Everything, including every private share and
+// Paillier key, is derived from constants in this file.
+//
+// WARNING: the private keys of these keystores are public. Never use them,
+// or anything signed with them, outside of tests.
+
+#![allow(deprecated)]
+
+use crate::common::types::{Keystore, KeystoreParameters, Threshold};
+use curv::arithmetic::traits::Converter;
+use curv::cryptographic_primitives::hashing::{hash_sha256::HSha256, traits::Hash};
+use curv::cryptographic_primitives::secret_sharing::feldman_vss::{
+  ShamirSecretSharing, VerifiableSS,
+};
+use curv::elliptic::curves::traits::{ECPoint, ECScalar};
+use curv::{BigInt, FE, GE};
+use multi_party_ecdsa::protocols::multi_party_ecdsa::gg_2018::party_i::{Keys, SharedKeys};
+use paillier::{DecryptionKey, EncryptionKey};
+
+const SEED: &[u8] = b"corelib test keystore";
+
+// 1024-bit primes, one pair per party.
+const PAILLIER_PRIMES: [(&str, &str); 5] = [
+  (
+    concat!(
+      "e2d07790126071cf6487ad20a9f71c0b37cda37a8ac585743d9d2f43f80aabb0",
+      "2849bfa9a5383f9c8cc9acbf31717b1ef6b5317d3dc06982893577ccd191a374",
+      "02b56978f5bfd4cf624be8eec765d7b2d741c2193232ff40f74c6dc08aa09851",
+      "870346b7748be6ae2602f2d2a15cbf3776c662a885f66440e7dfd79e64b096a3"
+    ),
+    concat!(
+      "ed92843ebf34d4ac7fee9def437f0e2ca3ca7c3b3d39b11141dc3dc75613a1a7",
+      "b14c64b089b13ac8642363cfae0d0328ff0f89438b1a996b7e89b844f2b5565d",
+      "48e2c3b66e9f6b8ac57e859567b3122277af73f30741242f5dd426f82afe023c",
+      "405ef5fcc3c7084b0a2e4e7f7a95382d4902e9524dd11bb640cc47480de889e3"
+    ),
+  ),
+  (
+    concat!(
+      "df204b138ac3ce8eb220391372b7367381b6c4a0d4b2eb96f79758ad11e7daec",
+      "05953243c9daf033fdb7d36512d9bbbdfea2a523eeb93ded2e9cac02e9dea95f",
+      "c4693bda8e294e65956246d3d0705670dc341a5d3cb529b36938ea9615ec52b9",
+      "43a72b06d4928ee8f05520774601b88c0b17f697dbc24b6c9732192f3a8a87e9"
+    ),
+    concat!(
+      "c0462df0c4c603a16ea2a73bf1fd2576dc0bae1c63124c39f5b22fd16f4d347c",
+      "5aa7079e3182434512bbc24367ec173376aa6ff1c9d708602c39ff01fb395740",
+      "30cfa71bac04fe7d97d8182fd372c2cd48aaa1340ffe6d9ff076938e4e15d72c",
+      "3e8398181f0802593d13a0ebe71d53a6dc5695d29701d4ef36eca41af19ce635"
+    ),
+  ),
+  (
+    concat!(
+      "fb2692097d36590feb17c879c97670924ed893e7010a7d7d6fbe479c49754c52",
+      "7d05d5f3faaf7f116834898b2de12ff02a8736e7679867671200e5fe29c55436",
+      "d36b89a549e6c21d3619591c93d97fa3e94d628a29221d25f7cc1201f3b7e5dc",
+      "48e280915fe9df87190ffeee0d24b70f366625b9b95908b68e69264d6ec5f5d7"
+    ),
+    concat!(
+      "ecdfec9ab2837a3575330300b792fc0547ecd10351741567a7a74603c214232a",
+      "c754b7619ac8c68b1bf3ba69ae4100f5bfe1a3316316ce5cc2bd40a255ad7e81",
+      "4d578d4d5ef5524901b1d7ab47c05af34d8a2d8c762c745e5276bdf5305686c4",
+      "9b7086babba511cee9ef0fb06fa85cb853c583943c6cca80752db5d90c8989bd"
+    ),
+  ),
+  (
+    concat!(
+      "c4dcabee772edcc09d1a0b7ab4851b7f5efe32d9d36b49e2bfb3ac5440ffb717",
+      "fe46f8a466d31572e12b0443c9b09b79475d207e7c3736aaded08ecace8d45ed",
+      "2abba098f981e2cda8153f5006c57575863d24868f31f75d8b99761385d1b63c",
+      "f43731667675c1772976d9bd030f76c32059c9f338f046692bb90b42fb1221f5"
+    ),
+    concat!(
+      "fab58f6107177d1e2a168642c9099b6b7b9fdbc7da2ccc22d9aca788a235f6b8",
+      "9a1ab49a509dfdb90c9aa54588bffb5f01513b8fc7dfd3e015f97ef487a4a7fb",
+      "140af199b52e286993d8b55a060e7bdba5ad6701b5b6feefa45a63e2eb25e7a7",
+      "7b3aae40210a12b85ed418f504731842184e12aaabf90058e83dda2c1b31b881"
+    ),
+  ),
+  (
+    concat!(
+      "d1d9a47105275768cc736a556fd454b6c94e1732d577959d1c716f9189f0c995",
+      "8817f1bdf0736b1693e4d81ededacac39134a8a6d64a57405e2cdd1a4600e047",
+      "014fd2879dedd1585920d8e687537644c4606cc40769e6ee7a96adf746345000",
+      "dd4c1feb584cbfa7a3534dc112b0dbfa242207ba85a4161f4ff5df1c40ba6685"
+    ),
+    concat!(
+      "c02621878a762bd0ec3db9d325a7aca9d7c6f9eacf80e486780ee94452703754",
+      "7936d566388e89f3a9374f590679c4b8969afe0061946afb6b09d8df2ac4751f",
+      "f6e1e2cdab71cafa84489afb0719b084a6a7ca0348777c053c2ab765c9c3ed2d",
+      "a22b2eddfd7d21548ad14631847c3e6091c39ee51d4e9d76181700635764acaf"
+    ),
+  ),
+];
+
+// Coefficient `j` of party `k`'s keygen polynomial.
+fn coefficient(k: usize, j: usize) -> FE {
+  let h = HSha256::create_hash(&[
+    &BigInt::from(SEED),
+    &BigInt::from(k as u64),
+    &BigInt::from(j as u64),
+  ]);
+  ECScalar::from(&h)
+}
+
+fn evaluate(coefficients: &[FE], x: usize) -> FE {
+  let x: FE = ECScalar::from(&BigInt::from(x as u64));
+  let (last, rest) = coefficients.split_last().unwrap();
+  rest.iter().rev().fold(*last, |acc, c| acc * x + *c)
+}
+
+fn paillier_keys(k: usize) -> (EncryptionKey, DecryptionKey) {
+  let (p, q) = PAILLIER_PRIMES[k];
+  let p = BigInt::from_hex(p);
+  let q = BigInt::from_hex(q);
+  let n = &p * &q;
+  (EncryptionKey { nn: &n * &n, n }, DecryptionKey { p, q })
+}
+
+// The keystores a `threshold`-of-`share_count` keygen would produce if
+// every party drew the polynomial and Paillier key fixed above.
+fn fixed_keystores(threshold: u16, share_count: u16) -> Vec<Keystore> {
+  let (t, n) = (threshold as usize, share_count as usize);
+  let g: GE = ECPoint::generator();
+
+  let polynomials: Vec<Vec<FE>> = (0..n)
+    .map(|k| (0..=t).map(|j| coefficient(k, j)).collect())
+    .collect();
+  let vss_scheme_vec: Vec<VerifiableSS> = polynomials
+    .iter()
+    .map(|coefficients| VerifiableSS {
+      parameters: ShamirSecretSharing {
+        threshold: t,
+        share_count: n,
+      },
+      commitments: coefficients.iter().map(|c| g * *c).collect(),
+    })
+    .collect();
+  let paillier: Vec<(EncryptionKey, DecryptionKey)> = (0..n).map(paillier_keys).collect();
+  let paillier_key_vec: Vec<EncryptionKey> = paillier.iter().map(|(ek, _)| ek.clone()).collect();
+  let y_sum = vss_scheme_vec[1..]
+    .iter()
+    .fold(vss_scheme_vec[0].commitments[0], |acc, vss| {
+      acc + &vss.commitments[0]
+    });
+
+  (0..n)
+    .map(|i| {
+      let party_shares: Vec<FE> = polynomials.iter().map(|f| evaluate(f, i + 1)).collect();
+      let x_i = party_shares[1..]
+        .iter()
+        .fold(party_shares[0], |acc, s| acc + *s);
+      let u_i = polynomials[i][0];
+      let (ek, dk) = paillier[i].clone();
+      Keystore {
+        params: KeystoreParameters {
+          threshold: Threshold::new(threshold),
+          share_count,
+        },
+        party_key: Keys {
+          u_i,
+          y_i: g * u_i,
+          dk,
+          ek,
+          party_index: i + 1,
+        },
+        party_shares,
+        shared_keys: SharedKeys { y: y_sum, x_i },
+        party_index: i,
+        vss_scheme_vec: vss_scheme_vec.clone(),
+        paillier_key_vec: paillier_key_vec.clone(),
+        y_sum,
+      }
+    })
+    .collect()
+}
+
+/// 2-of-3 keystores (threshold 1), identical on every call.
+///
+/// WARNING: the private key is publicly known. Test use only.
+pub fn generate_test_keystore_2_of_3() -> Vec<Keystore> {
+  fixed_keystores(1, 3)
+}
+
+/// 3-of-5 keystores (threshold 2), identical on every call.
+///
+/// WARNING: the private key is publicly known. Test use only.
+pub fn generate_test_keystore_3_of_5() -> Vec<Keystore> {
+  fixed_keystores(2, 5)
+}
@@ -0,0 +1,158 @@
+use crate::common::messages::*;
+use crate::common::types::{Digest32, Keystore, RoundId, SealedData};
+use crate::scenarios::{inject_panic, keygeneration, sign};
+use curv::elliptic::curves::traits::ECScalar;
+use curv::{BigInt, FE};
+use std::sync::mpsc::channel;
+use std::thread;
+
+/// A single scripted departure from the honest protocol.
+#[derive(Debug, Clone)]
+pub enum Deviation {
+  /// Broadcast a random scalar instead of `delta_i` in sign round 3.
+  RandomDelta,
+  /// Send a different keygen round-2 decommitment to each peer.
+  EquivocateDecommit,
+  /// Flip a ciphertext byte of every keygen round-3 AEAD.
+  CorruptAead,
+  /// Replace the message of the same round with a previously recorded one.
+  StaleCommitment(MessageData),
+  /// Stop sending protocol messages after the given round.
+  SilentAfter(RoundId),
+  /// Deliver every protocol message this many times.
+  Flood(usize),
+}
+
+/// Runs the honest scenarios but rewrites their outgoing traffic according
+/// to a deviation script, so tests can drive abort paths declaratively.
+#[derive(Debug, Clone, Default)]
+pub struct MaliciousParty {
+  deviations: Vec<Deviation>,
+}
+
+impl MaliciousParty {
+  pub fn new(deviations: Vec<Deviation>) -> Self {
+    MaliciousParty { deviations }
+  }
+
+  /// Returns a sender for an honest scenario; everything written to it is
+  /// rewritten by the script and forwarded to `outgoing_sender`.
+  pub fn intercept(&self, outgoing_sender: OutgoingChannel) -> OutgoingChannel {
+    let (sender, receiver) = channel::<Message>();
+    let deviations = self.deviations.clone();
+    thread::spawn(move || {
+      for msg in receiver {
+        for msg in apply(&deviations, msg) {
+          if outgoing_sender.send(msg).is_err() {
+            return;
+          }
+        }
+      }
+    });
+    sender
+  }
+
+  pub fn keygeneration(
+    &self,
+    participants: u8,
+    threshold: u8,
+    party_id: u8,
+    outgoing_sender: OutgoingChannel,
+    incoming_receiver: IncomingChannel,
+  ) {
+    keygeneration(
+      participants,
+      threshold,
+      party_id,
+      self.intercept(outgoing_sender),
+      incoming_receiver,
+    )
+  }
+
+  pub fn sign(
+    &self,
+    participants: u8,
+    threshold: u8,
+    party_num_id: u8,
+    keystore: &Keystore,
+    digest: &Digest32,
+    signers_vec: &Vec<usize>,
+    outgoing_sender: OutgoingChannel,
+    incoming_receiver: IncomingChannel,
+  ) {
+    sign(
+      participants,
+      threshold,
+      party_num_id,
+      keystore,
+      digest,
+      signers_vec,
+      self.intercept(outgoing_sender),
+      incoming_receiver,
+    )
+  }
+}
+
+/// Runs `run` with every protocol run it starts on this thread panicking
+/// once it starts collecting `round`, as if the crypto libraries had
+/// panicked on a crafted message. The run should fail with
+/// `CryptoKind::Panicked` in `round` and abort its peers.
+pub fn panic_in_round<T, F: FnOnce() -> T>(round: RoundId, run: F) -> T {
+  inject_panic(Some(round));
+  let result = run();
+  inject_panic(None);
+  result
+}
+
+fn apply(deviations: &[Deviation], msg: Message) -> Vec<Message> {
+  let (sender, target, mut data) = match msg {
+    Message::Send {
+      sender,
+      target,
+      data,
+      ..
+    } => (sender, target, unshare(data)),
+    other => return vec![other],
+  };
+
+  let mut copies = 1;
+  for deviation in deviations {
+    match (deviation, &mut data) {
+      (Deviation::RandomDelta, MessageData::SignRound3(delta)) => {
+        *delta = FE::new_random();
+      }
+      (Deviation::EquivocateDecommit, MessageData::KeyGenRound2(decom)) => {
+        decom.blind_factor = decom.blind_factor.clone() + BigInt::from(target as u64);
+      }
+      (Deviation::CorruptAead, MessageData::KeyGenRound3(aead)) => {
+        if let Some(byte) = aead.ciphertext.first_mut() {
+          *byte ^= 0xff;
+        }
+      }
+      (
+        Deviation::CorruptAead,
+        MessageData::KeyGenRound3Sealed(SealedData::V1 { ciphertext, .. }),
+      ) => {
+        if let Some(byte) = ciphertext.first_mut() {
+          *byte ^= 0xff;
+        }
+      }
+      (Deviation::StaleCommitment(stale), current) => {
+        if stale.round() == current.round() {
+          *current = stale.clone();
+        }
+      }
+      (Deviation::SilentAfter(last), current) => {
+        if current.round().map_or(false, |round| round > *last) {
+          return vec![];
+        }
+      }
+      (Deviation::Flood(times), _) => copies = *times,
+      _ => {}
+    }
+  }
+
+  (0..copies)
+    .map(|_| Message::make_send(sender, target, &data))
+    .collect()
+}
@@ -0,0 +1,3 @@
+pub mod conformance;
+pub mod fixtures;
+pub mod malicious;
@@ -0,0 +1,62 @@
+// Only names corelib types, and none of the deprecated raw keystore fields.
+#![deny(deprecated)]
+#![allow(dead_code)]
+
+use corelib::common::messages::Message;
+use corelib::common::types::{Digest32, Keystore, PublicKey};
+use corelib::errors::CoreErrors;
+use corelib::scenarios::{run_local_keygen, run_local_sign, sign};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread;
+
+fn spawn_sign(
+  keystore: Keystore,
+  party: u8,
+  signers: Vec<usize>,
+  digest: [u8; 32],
+) -> (Receiver<Message>, Sender<Message>) {
+  let (outgoing_sender, outgoing_receiver) = channel();
+  let (incoming_sender, incoming_receiver) = channel();
+  thread::spawn(move || {
+    sign(
+      signers.len() as u8,
+      keystore.threshold().get() as u8,
+      party,
+      &keystore,
+      &Digest32::from_bytes(&digest).expect("digest is all zero"),
+      &signers,
+      outgoing_sender,
+      incoming_receiver,
+    )
+  });
+  (outgoing_receiver, incoming_sender)
+}
+
+fn signature_bytes(
+  message: &Message,
+  public_key: &PublicKey,
+  digest: &Digest32,
+) -> Option<[u8; 64]> {
+  match message {
+    Message::Complete(result) => result
+      .as_signature()
+      .filter(|sig| sig.verify(public_key, digest))
+      .map(|sig| sig.to_bytes()),
+    _ => None,
+  }
+}
+
+fn keystore_summary(keystore: &Keystore) -> (usize, u16, u16, Vec<u8>) {
+  (
+    keystore.party_index(),
+    keystore.threshold().get(),
+    keystore.share_count(),
+    keystore.public_key().to_bytes(),
+  )
+}
+
+fn local_round_trip(digest: &Digest32) -> Result<Vec<[u8; 64]>, CoreErrors> {
+  let keystores = run_local_keygen(3, 1)?;
+  let signatures = run_local_sign(&keystores, &[0, 1], digest)?;
+  Ok(signatures.iter().map(|sig| sig.to_bytes()).collect())
+}